@@ -15,6 +15,13 @@ impl From<ProcessCommand> for Formatter {
 }
 
 impl Formatter {
+    /// Run the formatter in `current_dir`, so that it picks up the configuration of the project.
+    pub fn set_current_dir(self, current_dir: Option<std::path::PathBuf>) -> Self {
+        Self {
+            process_command: self.process_command.set_current_dir(current_dir),
+        }
+    }
+
     pub fn command_string(&self) -> String {
         self.process_command.to_string()
     }
@@ -44,9 +44,48 @@ pub struct Language {
     /// This will be used when we can't load the language file using `tree_sitter_grammar_config`.
     pub(crate) language_fallback: Option<CargoLinkedTreesitterLanguage>,
     pub(crate) highlight_query: Option<&'static str>,
+    /// Captures `@local.scope`, `@local.definition.*` and `@local.reference`,
+    /// as in the locals queries of nvim-treesitter.
+    pub(crate) locals_query: Option<&'static str>,
     pub(crate) formatter_command: Option<Command>,
+    /// Pairs that are closed automatically when their opening character is typed.
+    pub(crate) auto_pairs: &'static [(char, char)],
+    /// Pairs that can be used to surround a selection.
+    pub(crate) surround_pairs: &'static [(char, char)],
+    /// Files or directories that mark the root of a project of this language,
+    /// for example, `Cargo.toml` for Rust.
+    pub(crate) root_markers: &'static [&'static str],
+    /// Keywords that introduce a definition, followed by the name being defined,
+    /// used as a fallback for go-to-definition without a language server.
+    pub(crate) definition_keywords: &'static [&'static str],
+    /// Characters besides alphanumerics and `_` that can be part of an identifier,
+    /// for example, `-` in the kebab-case identifiers of CSS.
+    pub(crate) identifier_chars: &'static [char],
 }
 
+pub(crate) const DEFAULT_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+pub(crate) const DEFAULT_DEFINITION_KEYWORDS: &[&str] =
+    &["fn", "let", "const", "struct", "def", "class"];
+
+/// For languages where `<` and `>` enclose generics or templates.
+pub(crate) const PAIRS_WITH_ANGULAR_BRACKETS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('<', '>'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CargoLinkedTreesitterLanguage {
     Typescript,
@@ -121,10 +160,16 @@ impl Language {
             file_names: &[""],
             lsp_language_id: None,
             highlight_query: None,
+            locals_query: None,
             lsp_command: None,
             tree_sitter_grammar_config: None,
             formatter_command: None,
             language_fallback: None,
+            auto_pairs: DEFAULT_PAIRS,
+            surround_pairs: DEFAULT_PAIRS,
+            root_markers: &[],
+            definition_keywords: DEFAULT_DEFINITION_KEYWORDS,
+            identifier_chars: &[],
         }
     }
 
@@ -203,7 +248,7 @@ impl Language {
     }
 
     pub fn locals_query(&self) -> Option<&'static str> {
-        None
+        self.locals_query
     }
 
     pub fn injection_query(&self) -> Option<&'static str> {
@@ -233,6 +278,26 @@ impl Language {
     pub fn formatter(&self) -> Option<Formatter> {
         self.formatter_command().map(Formatter::from)
     }
+
+    pub fn auto_pairs(&self) -> &'static [(char, char)] {
+        self.auto_pairs
+    }
+
+    pub fn surround_pairs(&self) -> &'static [(char, char)] {
+        self.surround_pairs
+    }
+
+    pub fn root_markers(&self) -> &'static [&'static str] {
+        self.root_markers
+    }
+
+    pub fn definition_keywords(&self) -> &'static [&'static str] {
+        self.definition_keywords
+    }
+
+    pub fn identifier_chars(&self) -> &'static [char] {
+        self.identifier_chars
+    }
 }
 
 pub fn from_path(path: &CanonicalizedPath) -> Option<Language> {
@@ -256,8 +321,6 @@ pub(crate) fn from_filename(path: &CanonicalizedPath) -> Option<Language> {
         .map(|language| (*language).clone())
 }
 
-use regex::Regex;
-
 /// Detect the language from the first line of the file content.
 ///
 /// Standard shebang format is checked as well as vim's `ft=` method and various
@@ -267,6 +330,7 @@ use regex::Regex;
 /// detected as bash.
 ///
 /// - `#!/bin/bash`
+/// - `#!/usr/bin/env bash`
 /// - `# vim: ft=bash`
 /// - `# mode: bash
 ///
@@ -274,10 +338,11 @@ use regex::Regex;
 pub fn from_content_directive(content: &str) -> Option<Language> {
     let first_line = content.lines().next()?;
 
-    let re = Regex::new(r"(?:(?:^#!.*/)|(?:mode:)|(?:ft\s*=))\s*(\w+)").unwrap();
-    let language_id = re
-        .captures(first_line)
-        .and_then(|captures| captures.get(1).map(|mode| mode.as_str().to_string()));
+    // The interpreter of a shebang can be run via `env`, and can have a version suffix
+    let language_id =
+        lazy_regex::regex!(r"(?:(?:^#!.*/(?:env\s+)?)|(?:mode:)|(?:ft\s*=))\s*(\w+?)[\d.]*\b")
+            .captures(first_line)
+            .and_then(|captures| captures.get(1).map(|mode| mode.as_str().to_string()));
 
     language_id.and_then(|id| {
         LANGUAGES
@@ -326,6 +391,8 @@ mod test_language {
 
         run_test_case("#!/bin/bash", "bash")?;
         run_test_case("#!/usr/local/bin/bash", "bash")?;
+        run_test_case("#!/usr/bin/env python", "python")?;
+        run_test_case("#!/usr/bin/python3", "python")?;
         run_test_case("// mode: python", "python")?;
         run_test_case("-- tab_spaces: 5, mode: bash, use_tabs: false", "bash")?;
         run_test_case("-- tab_spaces: 5, mode:bash, use_tabs: false", "bash")?;
@@ -333,4 +400,15 @@ mod test_language {
 
         Ok(())
     }
+
+    #[test]
+    fn test_pairs() {
+        let rust = from_extension("rs").unwrap();
+        assert!(rust.auto_pairs().contains(&('<', '>')));
+        assert!(rust.surround_pairs().contains(&('<', '>')));
+
+        let markdown = from_extension("md").unwrap();
+        assert!(!markdown.auto_pairs().contains(&('<', '>')));
+        assert!(!markdown.surround_pairs().contains(&('<', '>')));
+    }
 }
@@ -1,6 +1,8 @@
 use crate::language::CargoLinkedTreesitterLanguage;
 
-use super::language::{Command, GrammarConfig, Language, LanguageId, LspCommand};
+use super::language::{
+    Command, GrammarConfig, Language, LanguageId, LspCommand, PAIRS_WITH_ANGULAR_BRACKETS,
+};
 
 pub const LANGUAGES: &[&Language] = &[
     &bash(),
@@ -105,6 +107,7 @@ const fn c() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::C),
+        root_markers: &["CMakeLists.txt", "Makefile", "compile_commands.json"],
         ..Language::new()
     }
 }
@@ -136,6 +139,7 @@ const fn common_lisp() -> Language {
             commit: "master",
             subpath: None,
         }),
+        identifier_chars: &['-'],
         ..Language::new()
     }
 }
@@ -159,6 +163,9 @@ const fn cpp() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::CPP),
+        auto_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        surround_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        root_markers: &["CMakeLists.txt", "Makefile", "compile_commands.json"],
         ..Language::new()
     }
 }
@@ -188,6 +195,7 @@ const fn css() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::CSS),
+        identifier_chars: &['-'],
         ..Language::new()
     }
 }
@@ -235,6 +243,7 @@ const fn elixir() -> Language {
             subpath: None,
         }),
         language_fallback: None,
+        root_markers: &["mix.exs"],
         ..Language::new()
     }
 }
@@ -320,6 +329,7 @@ const fn gleam() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Gleam),
+        root_markers: &["gleam.toml"],
         ..Language::new()
     }
 }
@@ -340,6 +350,8 @@ const fn golang() -> Language {
             commit: "master",
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Go),
+        root_markers: &["go.mod"],
+        definition_keywords: &["func", "var", "const", "type"],
         ..Language::new()
     }
 }
@@ -416,6 +428,7 @@ const fn html() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::HTML),
+        identifier_chars: &['-'],
         ..Language::new()
     }
 }
@@ -455,6 +468,8 @@ const fn javascript() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Javascript),
+        root_markers: &["package.json"],
+        definition_keywords: &["function", "class", "const", "let", "var"],
         ..Language::new()
     }
 }
@@ -475,6 +490,7 @@ const fn javascript_react() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::JSX),
+        root_markers: &["package.json"],
         ..Language::new()
     }
 }
@@ -543,6 +559,8 @@ const fn markdown() -> Language {
             subpath: Some("tree-sitter-markdown"),
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Markdown),
+        // Single quotes are mostly apostrophes in prose
+        auto_pairs: &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('`', '`')],
         ..Language::new()
     }
 }
@@ -563,6 +581,7 @@ const fn nix() -> Language {
             subpath: None,
         }),
         language_fallback: None,
+        identifier_chars: &['-'],
         ..Language::new()
     }
 }
@@ -583,6 +602,8 @@ const fn python() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Python),
+        root_markers: &["pyproject.toml", "setup.py", "requirements.txt"],
+        definition_keywords: &["def", "class"],
         ..Language::new()
     }
 }
@@ -629,6 +650,7 @@ const fn ruby() -> Language {
             subpath: None,
         }),
         language_fallback: None,
+        root_markers: &["Gemfile"],
         ..Language::new()
     }
 }
@@ -666,10 +688,40 @@ const fn rust() -> Language {
             subpath: None,
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Rust),
+        locals_query: Some(RUST_LOCALS_QUERY),
+        // Single quotes are not auto-paired because of lifetimes
+        auto_pairs: &[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>'), ('"', '"')],
+        surround_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        root_markers: &["Cargo.toml"],
+        definition_keywords: &[
+            "fn", "let", "const", "static", "struct", "enum", "trait", "type", "mod",
+        ],
         ..Language::new()
     }
 }
 
+const RUST_LOCALS_QUERY: &str = r#"
+[
+  (block)
+  (function_item)
+  (closure_expression)
+  (for_expression)
+  (if_expression)
+  (while_expression)
+  (match_arm)
+] @local.scope
+
+(parameter pattern: (identifier) @local.definition.var)
+(closure_parameters (identifier) @local.definition.var)
+(let_declaration pattern: (identifier) @local.definition.var)
+(let_condition pattern: (_ (identifier) @local.definition.var))
+(for_expression pattern: (identifier) @local.definition.var)
+(match_arm pattern: (match_pattern (identifier) @local.definition.var))
+(match_arm pattern: (match_pattern (_ (identifier) @local.definition.var)))
+
+(identifier) @local.reference
+"#;
+
 const fn sql() -> Language {
     Language {
         extensions: &["sql", "pgsql", "mssql", "mysql"],
@@ -700,6 +752,7 @@ const fn swift() -> Language {
             subpath: None,
         }),
         language_fallback: None,
+        root_markers: &["Package.swift"],
         ..Language::new()
     }
 }
@@ -714,6 +767,7 @@ const fn toml() -> Language {
             subpath: None,
         }),
         language_fallback: None,
+        identifier_chars: &['-'],
         ..Language::new()
     }
 }
@@ -747,6 +801,19 @@ const fn typescript() -> Language {
             subpath: Some("typescript"),
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Typescript),
+        auto_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        surround_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        root_markers: &["package.json", "tsconfig.json"],
+        definition_keywords: &[
+            "function",
+            "class",
+            "const",
+            "let",
+            "var",
+            "interface",
+            "type",
+            "enum",
+        ],
         ..Language::new()
     }
 }
@@ -767,6 +834,9 @@ const fn typescript_react() -> Language {
             subpath: Some("tsx"),
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::TSX),
+        auto_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        surround_pairs: PAIRS_WITH_ANGULAR_BRACKETS,
+        root_markers: &["package.json", "tsconfig.json"],
         ..Language::new()
     }
 }
@@ -815,6 +885,7 @@ const fn zig() -> Language {
             commit: "master",
         }),
         language_fallback: Some(CargoLinkedTreesitterLanguage::Zig),
+        root_markers: &["build.zig"],
         ..Language::new()
     }
 }
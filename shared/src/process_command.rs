@@ -1,10 +1,15 @@
 use anyhow::Context;
-use std::io::{Read, Write};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
 
 #[derive(Debug)]
 pub struct ProcessCommand {
     command: String,
     args: Vec<String>,
+    /// The working directory of the spawned process, which defaults to that of Ki.
+    current_dir: Option<PathBuf>,
 }
 
 pub enum SpawnCommandResult {
@@ -28,6 +33,14 @@ impl ProcessCommand {
         Self {
             command: command.to_string(),
             args: args.iter().map(|s| s.to_string()).collect(),
+            current_dir: None,
+        }
+    }
+
+    pub fn set_current_dir(self, current_dir: Option<PathBuf>) -> Self {
+        Self {
+            current_dir,
+            ..self
         }
     }
 
@@ -39,8 +52,12 @@ impl ProcessCommand {
                 command_name: self.command.clone(),
             }
         } else {
+            let mut command = std::process::Command::new(&self.command);
+            if let Some(current_dir) = &self.current_dir {
+                command.current_dir(current_dir);
+            }
             SpawnCommandResult::Spawned(
-                std::process::Command::new(&self.command)
+                command
                     .args(&self.args)
                     .stdin(std::process::Stdio::piped())
                     .stdout(std::process::Stdio::piped())
@@ -115,6 +132,15 @@ impl std::fmt::Display for ProcessCommand {
 mod test_process_command {
     use super::ProcessCommand;
 
+    #[test]
+    fn runs_in_current_dir() {
+        let output = ProcessCommand::new("pwd", &[])
+            .set_current_dir(Some("/".into()))
+            .run_with_input("")
+            .unwrap();
+        assert_eq!(output, "/\n");
+    }
+
     #[test]
     fn failed_command_includes_exit_code_and_stderr() {
         let err = ProcessCommand::new("bash", &["-c", "yo"])
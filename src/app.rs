@@ -1,5 +1,5 @@
 use crate::{
-    buffer::{Buffer, BufferOwner},
+    buffer::{Buffer, BufferOwner, CharSearch},
     clipboard::CopiedTexts,
     components::{
         component::{Component, ComponentId, GetGridResult},
@@ -16,6 +16,7 @@ use crate::{
     context::{
         Context, GlobalMode, GlobalSearchConfig, LocalSearchConfigMode, QuickfixListSource, Search,
     },
+    cursor_position_store::CursorPositionStore,
     frontend::Frontend,
     git,
     grid::{Grid, LineUpdate},
@@ -80,6 +81,10 @@ pub(crate) struct App<T: Frontend> {
 
     syntax_highlight_request_sender: Option<Sender<SyntaxHighlightRequest>>,
 
+    /// Remembers the last cursor position of each file across sessions.
+    /// This is `None` in tests, so that they do not read or write any sidecar file.
+    cursor_position_store: Option<CursorPositionStore>,
+
     status_line_components: Vec<StatusLineComponent>,
     last_action_description: Option<String>,
     last_action_short_description: Option<String>,
@@ -105,6 +110,9 @@ pub(crate) enum StatusLineComponent {
     Help,
     KeyboardLayout,
     Reveal,
+    SelectionSize,
+    CurrentFilePath,
+    CursorPosition,
 }
 
 impl<T: Frontend> App<T> {
@@ -157,6 +165,7 @@ impl<T: Frontend> App<T> {
             working_directory,
             frontend,
             syntax_highlight_request_sender: None,
+            cursor_position_store: None,
             global_title: None,
             status_line_components,
             last_action_description: None,
@@ -394,6 +403,18 @@ impl<T: Frontend> App<T> {
                                 .ok()
                                 .unwrap_or_else(|| self.working_directory.display_absolute()),
                         ),
+                        StatusLineComponent::CurrentFilePath => {
+                            let component = self.current_component();
+                            let component = component.borrow();
+                            let buffer = component.editor().buffer();
+                            Some(
+                                buffer.display_path(
+                                    &buffer
+                                        .project_root()
+                                        .unwrap_or_else(|| self.working_directory.clone()),
+                                ),
+                            )
+                        }
                         StatusLineComponent::GitBranch => self.current_branch(),
                         StatusLineComponent::Mode => {
                             let mode = self
@@ -411,6 +432,16 @@ impl<T: Frontend> App<T> {
                                 .editor()
                                 .display_selection_mode(),
                         ),
+                        StatusLineComponent::SelectionSize => self
+                            .current_component()
+                            .borrow()
+                            .editor()
+                            .display_selection_size(),
+                        StatusLineComponent::CursorPosition => self
+                            .current_component()
+                            .borrow()
+                            .editor()
+                            .display_cursor_position(),
                         StatusLineComponent::LastDispatch => self.last_action_description.clone(),
                         StatusLineComponent::LastSearchString => last_search_string.clone(),
                         StatusLineComponent::Help => {
@@ -541,6 +572,25 @@ impl<T: Frontend> App<T> {
                 if_current_not_found,
             } => self.open_search_prompt(scope, if_current_not_found)?,
             Dispatch::OpenPipeToShellPrompt => self.open_pipe_to_shell_prompt()?,
+            Dispatch::OpenWrapInCallPrompt => self.open_wrap_in_call_prompt()?,
+            Dispatch::OpenExtractToFilePrompt => self.open_extract_to_file_prompt()?,
+            Dispatch::OpenGoToPercentPrompt => self.open_go_to_percent_prompt()?,
+            Dispatch::OpenMaxLinesPrompt => self.open_max_lines_prompt()?,
+            Dispatch::OpenInsertCounterPrompt => self.open_insert_counter_prompt()?,
+            Dispatch::OpenShiftIndentPrompt => self.open_shift_indent_prompt()?,
+            Dispatch::OpenIndentOutlinePrompt => self.open_indent_outline_prompt()?,
+            Dispatch::OpenRetainSelectionsPrompt { invert } => {
+                self.open_retain_selections_prompt(invert)?
+            }
+            Dispatch::OpenSplitSelectionsPrompt => self.open_split_selections_prompt()?,
+            Dispatch::OpenSelectMatchingLinesPrompt => self.open_select_matching_lines_prompt()?,
+            Dispatch::OpenDeleteMatchingLinesPrompt { invert } => {
+                self.open_delete_matching_lines_prompt(invert)?
+            }
+            Dispatch::OpenFindCharPrompt {
+                direction,
+                inclusive,
+            } => self.open_find_char_prompt(direction, inclusive)?,
             Dispatch::OpenFile { path, owner, focus } => {
                 self.open_file(&path, owner, true, focus)?;
             }
@@ -675,18 +725,27 @@ impl<T: Frontend> App<T> {
                     )?;
                 }
             }
-            Dispatch::RequestCodeAction { diagnostics } => {
+            Dispatch::RequestCodeAction { range, diagnostics } => {
                 if let Some(params) = self.get_request_params() {
                     self.lsp_manager.send_message(
                         params.path.clone(),
                         FromEditor::TextDocumentCodeAction {
                             params,
+                            range,
                             diagnostics,
                         },
                     )?;
                     self.send_integration_event(IntegrationEvent::RequestLspCodeAction);
                 }
             }
+            Dispatch::RequestSelectionRanges => {
+                if let Some(params) = self.get_request_params() {
+                    self.lsp_manager.send_message(
+                        params.path.clone(),
+                        FromEditor::TextDocumentSelectionRange(params),
+                    )?;
+                }
+            }
             Dispatch::RequestSignatureHelp => {
                 if let Some(params) = self.get_request_params() {
                     self.lsp_manager.send_message(
@@ -722,6 +781,7 @@ impl<T: Frontend> App<T> {
                 }
             }
             Dispatch::DocumentDidSave { path } => {
+                self.save_cursor_position_of(&path);
                 // Emit an integration event for buffer save
                 // Find the component that has this path
                 for component in self.layout.components() {
@@ -762,6 +822,7 @@ impl<T: Frontend> App<T> {
             Dispatch::GotoLocation(location) => self.go_to_location(&location, true)?,
             Dispatch::OpenMoveToIndexPrompt => self.open_move_to_index_prompt()?,
             Dispatch::QuitAll => self.quit_all()?,
+            Dispatch::ConfirmQuitAll => self.confirm_quit_all()?,
             Dispatch::SaveQuitAll => self.save_quit_all()?,
             Dispatch::RevealInExplorer(path) => self.reveal_path_in_explorer(&path)?,
             Dispatch::OpenMoveFilePrompt => self.open_move_file_prompt()?,
@@ -789,6 +850,10 @@ impl<T: Frontend> App<T> {
             Dispatch::HandleKeyEvent(key_event) => {
                 self.handle_event(Event::Key(key_event))?;
             }
+            #[cfg(test)]
+            Dispatch::HandleMouseEvent(mouse_event) => {
+                self.handle_event(Event::Mouse(mouse_event))?;
+            }
             Dispatch::GetRepoGitHunks(diff_mode) => self.get_repo_git_hunks(diff_mode)?,
             Dispatch::SaveAll => self.save_all()?,
             #[cfg(test)]
@@ -926,7 +991,35 @@ impl<T: Frontend> App<T> {
         self.layout.get_current_component()
     }
 
+    /// Best-effort, failing to remember the cursor position must not prevent saving or closing.
+    fn save_cursor_position(&self, component: &Rc<RefCell<dyn Component>>) {
+        let Some(store) = self.cursor_position_store.as_ref() else {
+            return;
+        };
+        let component = component.borrow();
+        let editor = component.editor();
+        if let Err(error) = editor
+            .buffer()
+            .save_cursor_position(store, &editor.selection_set)
+        {
+            log::error!("Unable to save cursor position: {:?}", error);
+        }
+    }
+
+    fn save_cursor_position_of(&self, path: &CanonicalizedPath) {
+        if let Some(component) = self
+            .layout
+            .components()
+            .into_iter()
+            .map(|component| component.component())
+            .find(|component| component.borrow().path().as_ref() == Some(path))
+        {
+            self.save_cursor_position(&component);
+        }
+    }
+
     fn close_current_window(&mut self) -> anyhow::Result<()> {
+        self.save_cursor_position(&self.current_component());
         if let Some(removed_path) = self.layout.close_current_window(&self.context) {
             if let Some(path) = self.context.unmark_path(removed_path).cloned() {
                 self.open_file(&path, BufferOwner::User, true, true)?;
@@ -1199,12 +1292,23 @@ impl<T: Frontend> App<T> {
 
         let mut buffer = Buffer::from_path(path, true)?;
         buffer.set_owner(owner);
+        buffer.set_max_trailing_blank_lines(self.context.max_trailing_blank_lines());
 
         let language = buffer.language();
         let content = buffer.content();
         let batch_id = buffer.batch_id().clone();
+        let restored_selection_set = self
+            .cursor_position_store
+            .as_ref()
+            .and_then(|store| buffer.restored_selection_set(store));
         let buffer = Rc::new(RefCell::new(buffer));
-        let editor = SuggestiveEditor::from_buffer(buffer, SuggestiveEditorFilter::CurrentWord);
+        let mut editor = SuggestiveEditor::from_buffer(buffer, SuggestiveEditorFilter::CurrentWord);
+        editor.set_user_trigger_characters(self.context.user_trigger_characters().to_vec());
+        if let Some(selection_set) = restored_selection_set {
+            editor
+                .editor_mut()
+                .set_selection_set(selection_set, &self.context);
+        }
         let component_id = editor.id();
         let component = Rc::new(RefCell::new(editor));
 
@@ -1337,6 +1441,9 @@ impl<T: Frontend> App<T> {
             LspNotification::CompletionItemResolve(completion_item) => {
                 self.update_current_completion_item(completion_item.into())
             }
+            LspNotification::SelectionRanges(path, selection_ranges) => {
+                self.update_selection_ranges(path, selection_ranges)
+            }
         }
     }
 
@@ -1355,6 +1462,21 @@ impl<T: Frontend> App<T> {
         Ok(())
     }
 
+    fn update_selection_ranges(
+        &mut self,
+        path: CanonicalizedPath,
+        selection_ranges: Vec<lsp_types::SelectionRange>,
+    ) -> anyhow::Result<()> {
+        let component = self.open_file(&path, BufferOwner::System, false, false)?;
+
+        component
+            .borrow_mut()
+            .editor_mut()
+            .buffer_mut()
+            .set_selection_ranges(selection_ranges);
+        Ok(())
+    }
+
     pub(crate) fn get_quickfix_list(&self) -> Option<QuickfixList> {
         self.context.quickfix_list_state().as_ref().map(|state| {
             QuickfixList::new(
@@ -1583,6 +1705,25 @@ impl<T: Frontend> App<T> {
         self.sender.clone()
     }
 
+    fn confirm_quit_all(&mut self) -> anyhow::Result<()> {
+        let unsaved_paths = Buffer::unsaved_user_buffer_paths(&self.layout.buffers());
+        if unsaved_paths.is_empty() {
+            return self.quit_all();
+        }
+        self.open_yes_no_prompt(YesNoPrompt {
+            title: format!(
+                "Quit without saving {}?",
+                unsaved_paths
+                    .iter()
+                    .map(|path| path
+                        .display_relative_to(&self.working_directory)
+                        .unwrap_or_else(|_| path.display_absolute()))
+                    .join(", ")
+            ),
+            yes: Box::new(Dispatch::QuitAll),
+        })
+    }
+
     fn save_quit_all(&mut self) -> anyhow::Result<()> {
         self.save_all()?;
         self.quit_all()?;
@@ -1814,6 +1955,10 @@ impl<T: Frontend> App<T> {
         self.syntax_highlight_request_sender = Some(sender);
     }
 
+    pub(crate) fn set_cursor_position_store(&mut self, store: CursorPositionStore) {
+        self.cursor_position_store = Some(store);
+    }
+
     pub(crate) fn get_current_file_path(&self) -> Option<CanonicalizedPath> {
         self.current_component().borrow().path()
     }
@@ -2523,6 +2668,226 @@ impl<T: Frontend> App<T> {
         )
     }
 
+    fn open_wrap_in_call_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Wrap in call (function name)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::WrapInCall,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::WrapInCall,
+            },
+            None,
+        )
+    }
+
+    fn open_extract_to_file_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Extract lines to file (path)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::ExtractToFile,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::ExtractToFile,
+            },
+            None,
+        )
+    }
+
+    fn open_go_to_percent_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Go to percentage of file".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::GoToPercent,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::GoToPercent,
+            },
+            None,
+        )
+    }
+
+    fn open_max_lines_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Limit lines when tailing (empty for no limit)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::SetMaxLines,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::MaxLines,
+            },
+            None,
+        )
+    }
+
+    fn open_insert_counter_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Insert counter (format, e.g. `{:02}. `)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::InsertCounter,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::InsertCounter,
+            },
+            None,
+        )
+    }
+
+    fn open_shift_indent_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Shift indentation by (levels, e.g. `2` or `-1`)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::ShiftIndent,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::ShiftIndent,
+            },
+            None,
+        )
+    }
+
+    fn open_indent_outline_prompt(&mut self) -> anyhow::Result<()> {
+        let outline = self
+            .current_component()
+            .borrow()
+            .editor()
+            .buffer()
+            .indent_outline();
+        self.open_prompt(
+            PromptConfig {
+                title: "Outline (by indentation)".to_string(),
+                items: outline
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        DropdownItem::new(format!(
+                            "{}{}",
+                            "  ".repeat(entry.indent_level),
+                            entry.text
+                        ))
+                        .set_rank(Some(Box::from([index].to_vec())))
+                        .set_dispatches(Dispatches::one(
+                            Dispatch::ToEditor(SelectLineAt(entry.line)),
+                        ))
+                    })
+                    .collect_vec(),
+                on_enter: DispatchPrompt::Null,
+                enter_selects_first_matching_item: true,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::Outline,
+            },
+            None,
+        )
+    }
+
+    fn open_retain_selections_prompt(&mut self, invert: bool) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: format!(
+                    "{} selections matching (regex)",
+                    if invert { "Remove" } else { "Keep" }
+                ),
+                items: Default::default(),
+                on_enter: DispatchPrompt::RetainSelectionsMatching { invert },
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::RetainSelectionsMatching,
+            },
+            None,
+        )
+    }
+
+    fn open_split_selections_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Split selections by (regex)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::SplitSelections,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::SplitSelections,
+            },
+            None,
+        )
+    }
+
+    fn open_select_matching_lines_prompt(&mut self) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: "Select lines matching (regex)".to_string(),
+                items: Default::default(),
+                on_enter: DispatchPrompt::SelectMatchingLines,
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::MatchingLines,
+            },
+            None,
+        )
+    }
+
+    fn open_delete_matching_lines_prompt(&mut self, invert: bool) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: format!(
+                    "{} lines matching (regex)",
+                    if invert { "Keep only" } else { "Delete" }
+                ),
+                items: Default::default(),
+                on_enter: DispatchPrompt::DeleteMatchingLines { invert },
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::MatchingLines,
+            },
+            None,
+        )
+    }
+
+    fn open_find_char_prompt(
+        &mut self,
+        direction: Direction,
+        inclusive: bool,
+    ) -> anyhow::Result<()> {
+        self.open_prompt(
+            PromptConfig {
+                title: format!(
+                    "{} char {}",
+                    if inclusive { "Find" } else { "Till" },
+                    match direction {
+                        Direction::Start => "backward",
+                        Direction::End => "forward",
+                    }
+                ),
+                items: Default::default(),
+                on_enter: DispatchPrompt::FindChar {
+                    direction,
+                    inclusive,
+                },
+                enter_selects_first_matching_item: false,
+                leaves_current_line_empty: true,
+                fire_dispatches_on_change: None,
+                prompt_history_key: PromptHistoryKey::FindChar,
+            },
+            None,
+        )
+    }
+
     fn use_last_non_contiguous_selection_mode(
         &mut self,
         if_current_not_found: IfCurrentNotFound,
@@ -2712,7 +3077,7 @@ impl<T: Frontend> App<T> {
 
             // Compare the checksum of of the content of the buffer in Ki with that of the host application (e.g. VS Code)
             // This step is necessary to detect unsynchronized buffer
-            if content_hash != crc32fast::hash(component.borrow().content().as_bytes()) {
+            if content_hash != component.borrow().editor().buffer().content_hash() {
                 // If the buffer is desync, request the latest content
                 // before handling this event
                 self.integration_event_sender
@@ -2869,6 +3234,7 @@ pub(crate) enum Dispatch {
     },
     RequestCompletion,
     RequestSignatureHelp,
+    RequestSelectionRanges,
     RequestHover,
     RequestDefinitions(Scope),
     RequestDeclarations(Scope),
@@ -2880,6 +3246,7 @@ pub(crate) enum Dispatch {
     },
     PrepareRename,
     RequestCodeAction {
+        range: Option<lsp_types::Range>,
         diagnostics: Vec<lsp_types::Diagnostic>,
     },
     RenameSymbol {
@@ -2909,6 +3276,8 @@ pub(crate) enum Dispatch {
     GotoLocation(Location),
     OpenMoveToIndexPrompt,
     QuitAll,
+    /// Like `QuitAll`, but asks for confirmation if any user buffer has unsaved changes
+    ConfirmQuitAll,
     SaveQuitAll,
     RevealInExplorer(CanonicalizedPath),
     OpenMoveFilePrompt,
@@ -2934,6 +3303,8 @@ pub(crate) enum Dispatch {
     #[cfg(test)]
     HandleKeyEvent(event::KeyEvent),
     #[cfg(test)]
+    HandleMouseEvent(crossterm::event::MouseEvent),
+    #[cfg(test)]
     HandleKeyEvents(Vec<event::KeyEvent>),
     GetRepoGitHunks(git::DiffMode),
     SaveAll,
@@ -2997,6 +3368,25 @@ pub(crate) enum Dispatch {
     OpenThemePrompt,
     ResolveCompletionItem(lsp_types::CompletionItem),
     OpenPipeToShellPrompt,
+    OpenWrapInCallPrompt,
+    OpenExtractToFilePrompt,
+    OpenGoToPercentPrompt,
+    OpenMaxLinesPrompt,
+    OpenInsertCounterPrompt,
+    OpenShiftIndentPrompt,
+    OpenIndentOutlinePrompt,
+    OpenRetainSelectionsPrompt {
+        invert: bool,
+    },
+    OpenSplitSelectionsPrompt,
+    OpenSelectMatchingLinesPrompt,
+    OpenDeleteMatchingLinesPrompt {
+        invert: bool,
+    },
+    OpenFindCharPrompt {
+        direction: Direction,
+        inclusive: bool,
+    },
     SetLastNonContiguousSelectionMode(Either<SelectionMode, GlobalMode>),
     UseLastNonContiguousSelectionMode(IfCurrentNotFound),
     SetLastActionDescription {
@@ -3180,6 +3570,24 @@ pub(crate) enum DispatchPrompt {
         maintain: bool,
     },
     SetKeyboardLayoutKind,
+    WrapInCall,
+    ExtractToFile,
+    GoToPercent,
+    SetMaxLines,
+    InsertCounter,
+    ShiftIndent,
+    RetainSelectionsMatching {
+        invert: bool,
+    },
+    SplitSelections,
+    SelectMatchingLines,
+    DeleteMatchingLines {
+        invert: bool,
+    },
+    FindChar {
+        direction: Direction,
+        inclusive: bool,
+    },
 }
 impl DispatchPrompt {
     pub(crate) fn to_dispatches(&self, text: &str) -> anyhow::Result<Dispatches> {
@@ -3304,6 +3712,77 @@ impl DispatchPrompt {
                     keyboard_layout_kind,
                 )))
             }
+            DispatchPrompt::WrapInCall => Ok(Dispatches::one(Dispatch::ToEditor(
+                DispatchEditor::WrapInCall {
+                    function_name: text.to_string(),
+                },
+            ))),
+            DispatchPrompt::ExtractToFile => Ok(Dispatches::one(Dispatch::ToEditor(
+                DispatchEditor::ExtractToFile { path: text.into() },
+            ))),
+            DispatchPrompt::GoToPercent => {
+                let percent = text.trim().trim_end_matches('%').parse::<usize>()?;
+                Ok(Dispatches::one(Dispatch::ToEditor(
+                    DispatchEditor::GoToPercent(percent),
+                )))
+            }
+            DispatchPrompt::InsertCounter => Ok(Dispatches::one(Dispatch::ToEditor(
+                DispatchEditor::InsertCounter {
+                    format: if text.is_empty() { "{}" } else { text }.to_string(),
+                },
+            ))),
+            DispatchPrompt::ShiftIndent => {
+                let levels = text.trim().parse::<i32>()?;
+                Ok(Dispatches::one(Dispatch::ToEditor(
+                    DispatchEditor::ShiftIndent { levels },
+                )))
+            }
+            DispatchPrompt::RetainSelectionsMatching { invert } => Ok(Dispatches::one(
+                Dispatch::ToEditor(DispatchEditor::RetainSelectionsMatching {
+                    pattern: text.to_string(),
+                    invert,
+                }),
+            )),
+            DispatchPrompt::SplitSelections => Ok(Dispatches::one(Dispatch::ToEditor(
+                DispatchEditor::SplitSelections {
+                    separator: text.to_string(),
+                },
+            ))),
+            DispatchPrompt::SelectMatchingLines => Ok(Dispatches::one(Dispatch::ToEditor(
+                DispatchEditor::SelectMatchingLines {
+                    pattern: text.to_string(),
+                },
+            ))),
+            DispatchPrompt::DeleteMatchingLines { invert } => Ok(Dispatches::one(
+                Dispatch::ToEditor(DispatchEditor::DeleteMatchingLines {
+                    pattern: text.to_string(),
+                    invert,
+                }),
+            )),
+            DispatchPrompt::SetMaxLines => {
+                let max_lines = match text.trim() {
+                    "" => None,
+                    text => Some(text.parse::<usize>()?).filter(|max_lines| *max_lines > 0),
+                };
+                Ok(Dispatches::one(Dispatch::ToEditor(
+                    DispatchEditor::SetMaxLines(max_lines),
+                )))
+            }
+            DispatchPrompt::FindChar {
+                direction,
+                inclusive,
+            } => {
+                let Some(target) = text.chars().next() else {
+                    return Ok(Dispatches::default());
+                };
+                Ok(Dispatches::one(Dispatch::ToEditor(
+                    DispatchEditor::FindChar(CharSearch {
+                        target,
+                        direction,
+                        inclusive,
+                    }),
+                )))
+            }
         }
     }
 }
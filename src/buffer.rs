@@ -1,30 +1,39 @@
 use crate::history::History;
 use crate::lsp::diagnostic::Diagnostic;
-use crate::quickfix_list::QuickfixListItem;
+use crate::quickfix_list::{DiagnosticSeverityRange, Location, QuickfixListItem};
 use crate::selection::Selection;
 use crate::selection_mode::naming_convention_agnostic::NamingConventionAgnostic;
 use crate::syntax_highlight::SyntaxHighlightRequestBatchId;
 use crate::{
     char_index_range::CharIndexRange,
-    components::suggestive_editor::Decoration,
+    components::{
+        editor::{Direction, INDENT_CHAR, INDENT_WIDTH},
+        suggestive_editor::{Decoration, Info},
+    },
     context::{LocalSearchConfig, LocalSearchConfigMode},
-    edit::{Action, ActionGroup, Edit, EditTransaction},
+    cursor_position_store::CursorPositionStore,
+    edit::{Action, ActionGroup, ApplyOffset, Edit, EditTransaction},
+    grid::StyleKey,
     position::Position,
     selection::{CharIndex, SelectionSet},
     selection_mode::{AstGrep, ByteRange},
+    style::Style,
+    surround::{get_surrounding_indices, EnclosureKind},
     syntax_highlight::{HighlightedSpan, HighlightedSpans},
-    utils::find_previous,
+    themes::Theme,
 };
 use itertools::Itertools;
+use nonempty::NonEmpty;
 use regex::Regex;
 use ropey::Rope;
 use shared::{
     canonicalized_path::CanonicalizedPath,
     language::{self, Language},
 };
-use std::{collections::HashSet, ops::Range};
+use std::{cell::RefCell, collections::HashSet, ops::Range, path::PathBuf, rc::Rc};
 use tree_sitter::{Node, Parser, Tree};
 use tree_sitter_traversal2::{traverse, Order};
+use unicode_width::UnicodeWidthChar;
 
 /// Determines the buffer's owner. Ki distinguishes buffer ownership during switches.
 /// System-owned buffers (e.g., from LSP diagnostics or quicklist functions) are
@@ -36,6 +45,13 @@ pub enum BufferOwner {
     System,
 }
 
+/// Errors of the editing APIs that the UI may want to distinguish from generic failures.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub(crate) enum BufferError {
+    #[error("Buffer is read-only")]
+    ReadOnly,
+}
+
 #[derive(Clone)]
 pub(crate) struct Buffer {
     rope: Rope,
@@ -44,8 +60,15 @@ pub(crate) struct Buffer {
     language: Option<Language>,
     path: Option<CanonicalizedPath>,
     highlighted_spans: HighlightedSpans,
+    /// Ordered by creation.
     marks: Vec<CharIndexRange>,
+    /// Each fold spans from the start of its first line
+    /// to the start of the line after its last line.
+    folds: Vec<CharIndexRange>,
     diagnostics: Vec<Diagnostic>,
+    /// True when the content was edited after the diagnostics were last set,
+    /// so their positions might be outdated until the LSP republishes them.
+    diagnostics_stale: bool,
     quickfix_list_items: Vec<QuickfixListItem>,
     decorations: Vec<Decoration>,
     selection_set_history: History<SelectionSet>,
@@ -54,6 +77,30 @@ pub(crate) struct Buffer {
     pub(crate) undo_stack: Vec<EditHistory>,
     redo_stack: Vec<EditHistory>,
     batch_id: SyntaxHighlightRequestBatchId,
+    /// Obtained from `textDocument/selectionRange`,
+    /// each chain is ordered from the innermost range to the outermost range.
+    selection_ranges: Vec<Vec<CharIndexRange>>,
+    /// Used by `repeat_char_search`, similar to Vim's `;` and `,`.
+    last_char_search: Option<CharSearch>,
+    /// Detected from the first line terminator of the content.
+    line_ending: LineEnding,
+    /// When true, user edits and saving are rejected with `BufferError::ReadOnly`,
+    /// but the system can still update the content using `update` or `reload`.
+    read_only: bool,
+    /// When set, `append` drops the oldest lines beyond this count, like a ring buffer.
+    max_lines: Option<usize>,
+    /// When set, `save` collapses the blank lines at the end of the content to at most this count.
+    max_trailing_blank_lines: Option<usize>,
+    /// True when `from_path` detected binary content, which is loaded read-only
+    /// without tree-sitter parsing nor formatting.
+    is_binary: bool,
+    /// True when the language was set explicitly, so that it is not replaced
+    /// by `maybe_redetect_language`.
+    language_overridden: bool,
+    /// Some = the user edits are being recorded for replaying them as a macro.
+    recorded_edit_transactions: Option<Vec<EditTransaction>>,
+    /// The cached `content_hash`, which is cleared whenever the content changes.
+    content_hash: std::cell::Cell<Option<u32>>,
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -64,6 +111,123 @@ pub(crate) struct Line {
     pub(crate) content: String,
 }
 
+/// A word-level change between the saved content and the current content.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct WordChange {
+    /// The range of the new words in the current content.
+    /// This is an empty range if the words were deleted.
+    pub(crate) range: CharIndexRange,
+    pub(crate) old: String,
+    pub(crate) new: String,
+}
+
+/// A line-level difference between two buffers, see `Buffer::diff_against`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct DiffHunk {
+    /// The range of the changed lines in this buffer.
+    /// This is an empty range if lines were only inserted.
+    pub(crate) range: CharIndexRange,
+    /// The range of the changed lines in the other buffer.
+    /// This is an empty range if lines were only deleted.
+    pub(crate) other_range: CharIndexRange,
+}
+
+/// The widest padding of a counter accepted by `Buffer::insert_counter`,
+/// as the width comes from the user and is allocated for every selection.
+const MAX_COUNTER_WIDTH: usize = 64;
+
+/// A file to be created by the caller, see `Buffer::extract_to_file`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct NewFile {
+    pub(crate) path: PathBuf,
+    pub(crate) content: String,
+}
+
+/// An entry of the outline built by `Buffer::indent_outline`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct OutlineEntry {
+    /// 0-based
+    pub(crate) line: usize,
+    /// The nesting depth, where 0 means unindented.
+    pub(crate) indent_level: usize,
+    /// The line content without its indentation.
+    pub(crate) text: String,
+}
+
+/// The kind of syntax that encloses a position, see `Buffer::syntax_context_at`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum SyntaxContext {
+    Code,
+    String,
+    Comment,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum QuoteStyle {
+    Single,
+    Double,
+    Backtick,
+}
+
+impl QuoteStyle {
+    fn from_delimiter(delimiter: &str) -> Option<Self> {
+        match delimiter {
+            "'" => Some(Self::Single),
+            "\"" => Some(Self::Double),
+            "`" => Some(Self::Backtick),
+            _ => None,
+        }
+    }
+
+    fn delimiter(&self) -> char {
+        match self {
+            QuoteStyle::Single => '\'',
+            QuoteStyle::Double => '"',
+            QuoteStyle::Backtick => '`',
+        }
+    }
+}
+
+/// A search for a character within the current line, like Vim's `f`, `t`, `F` and `T`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct CharSearch {
+    pub(crate) target: char,
+    pub(crate) direction: Direction,
+    pub(crate) inclusive: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub(crate) enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Returns the line ending of the first line terminator in `text`,
+    /// or `Lf` if `text` has no line terminator.
+    fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(index) if text[..index].ends_with('\r') => LineEnding::Crlf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum WhitespaceIssue {
+    TrailingWhitespace,
+    /// The leading indentation contains both tabs and spaces
+    MixedIndentation,
+}
+
 impl Buffer {
     pub(crate) fn new(language: Option<tree_sitter::Language>, text: &str) -> Self {
         Self {
@@ -88,8 +252,10 @@ impl Buffer {
             path: None,
             highlighted_spans: HighlightedSpans::default(),
             marks: Vec::new(),
+            folds: Vec::new(),
             decorations: Vec::new(),
             diagnostics: Vec::new(),
+            diagnostics_stale: false,
             quickfix_list_items: Vec::new(),
             selection_set_history: History::new(),
             dirty: false,
@@ -97,6 +263,16 @@ impl Buffer {
             undo_stack: Default::default(),
             redo_stack: Default::default(),
             batch_id: Default::default(),
+            selection_ranges: Default::default(),
+            last_char_search: None,
+            line_ending: LineEnding::detect(text),
+            read_only: false,
+            max_lines: None,
+            max_trailing_blank_lines: None,
+            is_binary: false,
+            language_overridden: false,
+            recorded_edit_transactions: None,
+            content_hash: Default::default(),
         }
     }
 
@@ -105,6 +281,22 @@ impl Buffer {
         self.owner = owner;
     }
 
+    pub(crate) fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only
+    }
+
+    pub(crate) fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub(crate) fn set_max_lines(&mut self, max_lines: Option<usize>) {
+        self.max_lines = max_lines
+    }
+
+    pub(crate) fn set_max_trailing_blank_lines(&mut self, max_trailing_blank_lines: Option<usize>) {
+        self.max_trailing_blank_lines = max_trailing_blank_lines
+    }
+
     /// Refer `BufferOwner`
     pub(crate) fn owner(&self) -> BufferOwner {
         self.owner
@@ -124,7 +316,19 @@ impl Buffer {
     pub(crate) fn reload(&mut self) -> anyhow::Result<()> {
         if let Some(path) = self.path() {
             let updated_content = path.read()?;
-            self.update_content(&updated_content, SelectionSet::default(), 0)?;
+            // Unchanged files are not diffed, which is far slower than comparing the contents
+            if self.rope != updated_content.as_str() {
+                let edit_transaction = self.get_edit_transaction(&updated_content)?;
+                // Following the file is not a user edit, so it is allowed in read-only buffers,
+                // where it is not undoable
+                self.apply_edit_transaction(
+                    &edit_transaction,
+                    SelectionSet::default(),
+                    true,
+                    !self.read_only,
+                    0,
+                )?;
+            }
             self.dirty = false;
         }
         Ok(())
@@ -143,38 +347,472 @@ impl Buffer {
     }
 
     pub(crate) fn save_marks(&mut self, new_ranges: Vec<CharIndexRange>) {
-        let old_ranges = std::mem::take(&mut self.marks)
-            .into_iter()
+        let new_ranges = new_ranges.into_iter().unique().collect_vec();
+        let unmarked_ranges = self
+            .marks
+            .iter()
+            .filter(|mark| new_ranges.contains(mark))
+            .cloned()
             .collect::<HashSet<_>>();
-        let new_ranges = new_ranges.into_iter().collect::<HashSet<_>>();
         // We take the symmetric difference between the old ranges and the new ranges
-        // so that user can unmark existing mark
-        self.marks = new_ranges
-            .symmetric_difference(&old_ranges)
-            .cloned()
-            .collect_vec();
+        // so that user can unmark existing mark,
+        // while keeping the remaining marks in the order they were created
+        self.marks.retain(|mark| !unmarked_ranges.contains(mark));
+        self.marks.extend(
+            new_ranges
+                .into_iter()
+                .filter(|range| !unmarked_ranges.contains(range)),
+        );
+    }
+
+    /// Returns the mark created after `current` (or the first mark if `current` is not a mark),
+    /// wrapping around to the first mark after the last one.
+    pub(crate) fn cycle_marks(&self, current: &CharIndexRange) -> Option<CharIndexRange> {
+        let next_index = self
+            .marks
+            .iter()
+            .position(|mark| mark == current)
+            .map_or(0, |index| (index + 1) % self.marks.len());
+        self.marks.get(next_index).cloned()
+    }
+
+    /// Sets the marks programmatically, for example to the regions of git conflict markers.
+    ///
+    /// Unlike `save_marks`, existing marks are never toggled off: if `replace` is true,
+    /// the marks become `ranges`, otherwise the ranges not yet marked are added.
+    pub(crate) fn set_marks_from_ranges(&mut self, ranges: Vec<CharIndexRange>, replace: bool) {
+        if replace {
+            self.marks.clear();
+        }
+        for range in ranges {
+            if !self.marks.contains(&range) {
+                self.marks.push(range);
+            }
+        }
+    }
+
+    /// Returns the text spanning from the start of the earlier mark
+    /// to the end of the later mark, regardless of the order of `a` and `b`.
+    pub(crate) fn text_between_marks(
+        &self,
+        a: CharIndexRange,
+        b: CharIndexRange,
+    ) -> anyhow::Result<Rope> {
+        self.slice(&(a.start.min(b.start)..a.end.max(b.end)).into())
     }
 
     pub(crate) fn path(&self) -> Option<CanonicalizedPath> {
         self.path.clone()
     }
 
+    /// The path to be displayed in the UI, which is relative to `relative_to` if the path is under it,
+    /// otherwise absolute.
+    pub(crate) fn display_path(&self, relative_to: &CanonicalizedPath) -> String {
+        match &self.path {
+            Some(path) => path
+                .display_relative_to(relative_to)
+                .unwrap_or_else(|_| path.display_absolute()),
+            None => "[scratch]".to_string(),
+        }
+    }
+
+    /// Returns the closest ancestor directory of `path` that contains either `.git`
+    /// or one of the root markers of the buffer language (e.g. `Cargo.toml` for Rust).
+    pub(crate) fn project_root(&self) -> Option<CanonicalizedPath> {
+        let language_root_markers = self
+            .language
+            .as_ref()
+            .map(|language| language.root_markers())
+            .unwrap_or_default();
+        let root_markers = language_root_markers.iter().chain(&[".git"]).collect_vec();
+        std::iter::successors(self.path()?.parent().ok().flatten(), |directory| {
+            directory.parent().ok().flatten()
+        })
+        .find(|directory| {
+            root_markers
+                .iter()
+                .any(|marker| directory.to_path_buf().join(marker).exists())
+        })
+    }
+
     #[cfg(test)]
     pub(crate) fn set_path(&mut self, path: CanonicalizedPath) {
         self.path = Some(path);
     }
 
+    /// The diagnostics are stored sorted by their range.
     pub(crate) fn set_diagnostics(&mut self, diagnostics: Vec<lsp_types::Diagnostic>) {
         self.diagnostics = diagnostics
             .into_iter()
             .filter_map(|diagnostic| Diagnostic::try_from(self, diagnostic).ok())
-            .collect()
+            .sorted_by_key(|diagnostic| diagnostic.range)
+            .collect();
+        self.set_diagnostics_stale(false);
     }
 
     pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
         self.diagnostics.clone()
     }
 
+    pub(crate) fn set_diagnostics_stale(&mut self, diagnostics_stale: bool) {
+        self.diagnostics_stale = diagnostics_stale
+    }
+
+    /// Consumers of `diagnostics` can use this to dim or hide diagnostics
+    /// that are awaiting a republish from the LSP.
+    pub(crate) fn diagnostics_stale(&self) -> bool {
+        self.diagnostics_stale
+    }
+
+    /// Returns the earliest-positioned diagnostic whose severity is at least `severity`,
+    /// for example, `DiagnosticSeverity::WARNING` matches both errors and warnings.
+    ///
+    /// Diagnostics without severity are never matched.
+    pub(crate) fn first_diagnostic_of_at_least(
+        &self,
+        severity: lsp_types::DiagnosticSeverity,
+    ) -> Option<Diagnostic> {
+        self.diagnostics
+            .iter()
+            .find(|diagnostic| {
+                diagnostic
+                    .severity
+                    .is_some_and(|diagnostic_severity| diagnostic_severity <= severity)
+            })
+            .cloned()
+    }
+
+    /// Returns the line nearest to `from` (searching both directions, preferring the later line
+    /// on ties) that has no error diagnostics.
+    /// Returns `from` if every line has errors.
+    pub(crate) fn nearest_clean_line(&self, from: usize) -> usize {
+        let error_lines = self
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic
+                    .severity
+                    .is_some_and(|severity| severity <= lsp_types::DiagnosticSeverity::ERROR)
+            })
+            .filter_map(|diagnostic| self.char_index_range_to_line_range(diagnostic.range).ok())
+            .flat_map(|range| range.start..=range.end)
+            .collect::<HashSet<_>>();
+        let last_line = self.len_lines().saturating_sub(1);
+        (0..=last_line.max(from))
+            .flat_map(|distance| {
+                [Some(from + distance), from.checked_sub(distance)]
+                    .into_iter()
+                    .flatten()
+            })
+            .find(|line| line <= &last_line && !error_lines.contains(line))
+            .unwrap_or(from)
+    }
+
+    /// Returns the index and content (without the line break) of every line matching `regex`,
+    /// like Vim's `:g/pattern/`.
+    pub(crate) fn matching_lines(&self, regex: &Regex) -> Vec<(usize, String)> {
+        self.rope
+            .lines()
+            .enumerate()
+            .filter_map(|(line_index, line)| {
+                let line = line.to_string();
+                let line = line.trim_end_matches(['\n', '\r']);
+                regex.is_match(line).then(|| (line_index, line.to_string()))
+            })
+            .collect()
+    }
+
+    /// Delete every line matching `regex`, or, if `invert` is true, every line not matching it.
+    ///
+    /// If the buffer does not end with a line break, the last remaining line
+    /// will not end with one either.
+    pub(crate) fn delete_matching_lines(&self, regex: &Regex, invert: bool) -> EditTransaction {
+        let len_chars = CharIndex(self.len_chars());
+        let ends_with_line_break =
+            self.rope.get_char(self.len_chars().saturating_sub(1)) == Some('\n');
+        let deleted_ranges = self
+            .rope
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.len_chars() > 0)
+            .filter(|(_, line)| {
+                let line = line.to_string();
+                regex.is_match(line.trim_end_matches(['\n', '\r'])) != invert
+            })
+            .filter_map(|(line_index, _)| self.line_to_char_range(line_index).ok())
+            // Merge consecutive lines, so that their deletions do not intersect
+            .coalesce(|previous, current| {
+                if previous.end == current.start {
+                    Ok((previous.start..current.end).into())
+                } else {
+                    Err((previous, current))
+                }
+            })
+            .map(|range| {
+                if range.end == len_chars && range.start > CharIndex(0) && !ends_with_line_break {
+                    // Remove the line break of the previous line instead
+                    (range.start - 1..range.end).into()
+                } else {
+                    range
+                }
+            })
+            .collect_vec();
+        EditTransaction::from_action_groups(
+            deleted_ranges
+                .into_iter()
+                .map(|range| {
+                    ActionGroup::new(
+                        [Action::Edit(Edit::new(&self.rope, range, Rope::new()))].to_vec(),
+                    )
+                })
+                .collect_vec(),
+        )
+    }
+
+    /// Start recording the user edits, discarding any unfinished recording.
+    pub(crate) fn start_recording(&mut self) {
+        self.recorded_edit_transactions = Some(Vec::new())
+    }
+
+    /// Stop recording, and returns the edit transactions applied since `start_recording`.
+    pub(crate) fn stop_recording(&mut self) -> Vec<EditTransaction> {
+        self.recorded_edit_transactions.take().unwrap_or_default()
+    }
+
+    /// Re-apply the `recorded` edit transactions, shifted by `offset` chars,
+    /// for example, the distance between the new cursor and the cursor when the recording started.
+    ///
+    /// Unlike undo history, the replayed edits keep their new text but are applied
+    /// to whatever text is at the shifted ranges.
+    pub(crate) fn replay(
+        &mut self,
+        recorded: &[EditTransaction],
+        offset: isize,
+        selection_set: SelectionSet,
+        last_visible_line: u16,
+    ) -> anyhow::Result<SelectionSet> {
+        recorded
+            .iter()
+            .try_fold(selection_set, |selection_set, edit_transaction| {
+                let edit_transaction = EditTransaction::from_action_groups(
+                    edit_transaction
+                        .unnormalized_edits()
+                        .into_iter()
+                        .map(|edit| {
+                            ActionGroup::new(
+                                [Action::Edit(Edit::new(
+                                    &self.rope,
+                                    edit.range().apply_offset(offset),
+                                    edit.new,
+                                ))]
+                                .to_vec(),
+                            )
+                        })
+                        .collect_vec(),
+                );
+                Ok(self
+                    .apply_edit_transaction(
+                        &edit_transaction,
+                        selection_set,
+                        true,
+                        true,
+                        last_visible_line,
+                    )?
+                    .0)
+            })
+    }
+
+    /// Apply the `recorded` edit transactions once at each selection, as one edit transaction.
+    /// The edits are shifted by the distance between each selection and the first recorded edit.
+    pub(crate) fn replay_edits(
+        &self,
+        recorded: &[EditTransaction],
+        selection_set: SelectionSet,
+    ) -> anyhow::Result<EditTransaction> {
+        let Some(origin) = recorded
+            .first()
+            .map(|edit_transaction| edit_transaction.min_char_index())
+        else {
+            return Ok(EditTransaction::from_action_groups(Vec::new()));
+        };
+        let action_groups = selection_set
+            .selections()
+            .iter()
+            .map(|selection| -> anyhow::Result<_> {
+                let offset = selection.extended_range().start.0 as isize - origin.0 as isize;
+                // Apply the edits sequentially on a copy, while tracking the affected window,
+                // so that they can be combined into a single edit
+                let mut rope = self.rope.clone();
+                let (mut window_start, mut window_end) = (None::<CharIndex>, CharIndex(0));
+                let mut net_offset = 0;
+                for edit in recorded
+                    .iter()
+                    .flat_map(|edit_transaction| edit_transaction.edits())
+                {
+                    let range = edit.range().apply_offset(offset);
+                    window_start =
+                        Some(window_start.map_or(range.start, |start| start.min(range.start)));
+                    window_end = window_end.max(range.end).apply_offset(edit.chars_offset());
+                    net_offset += edit.chars_offset();
+                    rope.try_remove(range.as_usize_range())?;
+                    rope.try_insert(range.start.0, &edit.new.to_string())?;
+                }
+                let window_start = window_start.unwrap_or(origin);
+                let new: Rope = rope
+                    .get_slice(window_start.0..window_end.0)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid range"))?
+                    .into();
+                let new_len_chars = new.len_chars();
+                Ok(ActionGroup::new(
+                    [
+                        Action::Edit(Edit::new(
+                            &self.rope,
+                            (window_start..window_end.apply_offset(-net_offset)).into(),
+                            new,
+                        )),
+                        Action::Select(
+                            selection
+                                .clone()
+                                .set_initial_range(None)
+                                .set_range((window_start..window_start + new_len_chars).into()),
+                        ),
+                    ]
+                    .to_vec(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(EditTransaction::from_action_groups(action_groups))
+    }
+
+    /// Render the content as a standalone HTML document, where each highlighted span
+    /// is wrapped in a `<span>` styled with the colors of `theme`.
+    pub(crate) fn to_html(&self, theme: &Theme) -> String {
+        let body = self
+            .styled_segments(theme)
+            .into_iter()
+            .map(|(text, style)| {
+                let text = html_escape(&text);
+                let Some(style) = style else {
+                    return text;
+                };
+                let css = [
+                    style
+                        .foreground_color
+                        .map(|color| format!("color:{}", color.to_hex())),
+                    style
+                        .background_color
+                        .map(|color| format!("background-color:{}", color.to_hex())),
+                    style.is_bold.then(|| "font-weight:bold".to_string()),
+                ]
+                .into_iter()
+                .flatten()
+                .join(";");
+                format!("<span style=\"{css}\">{text}</span>")
+            })
+            .join("");
+        format!(
+            "<!DOCTYPE html>\n<html>\n<body>\n<pre style=\"color:{};background-color:{}\">{body}</pre>\n</body>\n</html>\n",
+            theme.ui.text_foreground.to_hex(),
+            theme.ui.background_color.to_hex()
+        )
+    }
+
+    /// Render the content with ANSI escape codes for printing in a terminal,
+    /// where each highlighted span is colored by `theme`.
+    /// The style is reset at the end of every span and before every line break.
+    pub(crate) fn to_ansi(&self, theme: &Theme) -> String {
+        use crossterm::style::{Attribute, SetAttribute, SetBackgroundColor, SetForegroundColor};
+        self.styled_segments(theme)
+            .into_iter()
+            .map(|(text, style)| {
+                let Some(style) = style else {
+                    return text;
+                };
+                let codes = [
+                    style
+                        .foreground_color
+                        .map(|color| SetForegroundColor(color.into()).to_string()),
+                    style
+                        .background_color
+                        .map(|color| SetBackgroundColor(color.into()).to_string()),
+                    style
+                        .is_bold
+                        .then(|| SetAttribute(Attribute::Bold).to_string()),
+                ]
+                .into_iter()
+                .flatten()
+                .join("");
+                let reset = SetAttribute(Attribute::Reset);
+                text.split('\n')
+                    .map(|line| {
+                        if line.is_empty() {
+                            String::new()
+                        } else {
+                            format!("{codes}{line}{reset}")
+                        }
+                    })
+                    .join("\n")
+            })
+            .join("")
+    }
+
+    /// Split the content into segments, each with the style of its highlighted span (if any).
+    fn styled_segments(&self, theme: &Theme) -> Vec<(String, Option<Style>)> {
+        let content = self.content();
+        let mut segments = Vec::new();
+        let mut byte = 0;
+        for span in self.highlighted_spans() {
+            // Skip the part of the span that overlaps with the previous span
+            let start = span.byte_range.start.max(byte);
+            let (Some(before), Some(text)) = (
+                content.get(byte..start),
+                content.get(start..span.byte_range.end),
+            ) else {
+                continue;
+            };
+            if !before.is_empty() {
+                segments.push((before.to_string(), None));
+            }
+            if !text.is_empty() {
+                segments.push((text.to_string(), Some(theme.get_style(&span.style_key))));
+            }
+            byte = span.byte_range.end;
+        }
+        if let Some(rest) = content.get(byte..).filter(|rest| !rest.is_empty()) {
+            segments.push((rest.to_string(), None));
+        }
+        segments
+    }
+
+    /// Returns the first blank line after `from`, or the last line if there is none.
+    /// If `whitespace_only_is_blank` is true, lines with only whitespaces are also blank.
+    pub(crate) fn next_blank_line(&self, from: usize, whitespace_only_is_blank: bool) -> usize {
+        let last_line = self.len_lines().saturating_sub(1);
+        (from + 1..=last_line)
+            .find(|line| self.is_blank_line(*line, whitespace_only_is_blank))
+            .unwrap_or(last_line)
+    }
+
+    /// Returns the last blank line before `from`, or the first line if there is none.
+    /// If `whitespace_only_is_blank` is true, lines with only whitespaces are also blank.
+    pub(crate) fn previous_blank_line(&self, from: usize, whitespace_only_is_blank: bool) -> usize {
+        (0..from)
+            .rev()
+            .find(|line| self.is_blank_line(*line, whitespace_only_is_blank))
+            .unwrap_or(0)
+    }
+
+    fn is_blank_line(&self, line: usize, whitespace_only_is_blank: bool) -> bool {
+        self.get_line_by_line_index(line).is_some_and(|slice| {
+            let mut chars = slice.chars().filter(|char| *char != '\n' && *char != '\r');
+            if whitespace_only_is_blank {
+                chars.all(char::is_whitespace)
+            } else {
+                chars.next().is_none()
+            }
+        })
+    }
+
     pub(crate) fn words(&self) -> Vec<String> {
         let regex = regex::Regex::new(r"\b\w+").unwrap();
         let str = self.rope.to_string();
@@ -185,6 +823,51 @@ impl Buffer {
             .collect()
     }
 
+    /// Returns the names visible at `char_index`, that is, the names defined
+    /// in the scopes enclosing it according to the locals query of the language,
+    /// so that a fresh name can be picked for a new variable.
+    ///
+    /// Falls back to every word of the buffer if there is no locals query.
+    pub(crate) fn names_in_scope(&self, char_index: CharIndex) -> HashSet<String> {
+        let (Some(locals), Ok(byte)) = (self.locals(), self.char_to_byte(char_index)) else {
+            return self.words().into_iter().collect();
+        };
+        locals
+            .definitions
+            .iter()
+            .filter(|(definition, _)| {
+                locals
+                    .scope_of(definition)
+                    .is_none_or(|scope| scope.contains(&byte))
+            })
+            .map(|(_, name)| name.clone())
+            .collect()
+    }
+
+    /// Returns the range of the first occurrence of `name` that follows a definition
+    /// keyword of the language (e.g. `fn foo`), as a best-effort go-to-definition
+    /// for when neither a language server nor a locals query is available.
+    pub(crate) fn heuristic_definition(&self, name: &str) -> Option<CharIndexRange> {
+        let keywords = self
+            .language
+            .as_ref()
+            .map(|language| language.definition_keywords())
+            .unwrap_or_else(|| Language::new().definition_keywords());
+        let regex = Regex::new(&format!(
+            r"\b(?:{})\s+(?:mut\s+)?({})\b",
+            keywords
+                .iter()
+                .map(|keyword| regex::escape(keyword))
+                .join("|"),
+            regex::escape(name)
+        ))
+        .ok()?;
+        let content = self.content();
+        let name_match = regex.captures(&content)?.get(1)?;
+        self.byte_range_to_char_index_range(&name_match.range())
+            .ok()
+    }
+
     pub(crate) fn get_parent_lines(&self, line_number: usize) -> anyhow::Result<Vec<Line>> {
         let char_index = self.line_to_char(line_number)?;
         let node = self.get_nearest_node_after_char(char_index);
@@ -307,8 +990,97 @@ impl Buffer {
 
     pub(crate) fn update(&mut self, text: &str) {
         (self.rope, self.tree) = Self::get_rope_and_tree(self.treesitter_language.clone(), text);
+        self.line_ending = LineEnding::detect(text);
         self.dirty = true;
         self.owner = BufferOwner::User;
+        self.last_char_search = None;
+        self.content_hash.set(None);
+    }
+
+    /// Returns the CRC32 checksum of the content, as used by the host application
+    /// to detect an unsynchronized buffer.
+    ///
+    /// The checksum is computed once per change of the content, instead of on every call.
+    pub(crate) fn content_hash(&self) -> u32 {
+        if let Some(hash) = self.content_hash.get() {
+            return hash;
+        }
+        let mut hasher = crc32fast::Hasher::new();
+        for chunk in self.rope.chunks() {
+            hasher.update(chunk.as_bytes());
+        }
+        let hash = hasher.finalize();
+        self.content_hash.set(Some(hash));
+        hash
+    }
+
+    pub(crate) fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Returns a transaction that rewrites every line terminator to `line_ending`.
+    /// The stored line ending is re-detected once the transaction is applied.
+    ///
+    /// The selections of `selection_set` are carried over to the same characters,
+    /// although CRLF to LF and vice versa changes the char count before them.
+    pub(crate) fn normalize_line_endings(
+        &self,
+        line_ending: LineEnding,
+        selection_set: &SelectionSet,
+    ) -> EditTransaction {
+        // Only the `\r` is removed or inserted, so that the `\n` stays intact
+        let chars = self.rope.chars().collect_vec();
+        let edits = chars
+            .iter()
+            .enumerate()
+            .filter_map(|(index, char)| {
+                let previous = index.checked_sub(1).map(|index| chars[index]);
+                match (line_ending, previous, *char) {
+                    (LineEnding::Lf, Some('\r'), '\n') => Some(Edit::new(
+                        &self.rope,
+                        (CharIndex(index - 1)..CharIndex(index)).into(),
+                        Rope::new(),
+                    )),
+                    (LineEnding::Crlf, previous, '\n') if previous != Some('\r') => {
+                        Some(Edit::new(
+                            &self.rope,
+                            (CharIndex(index)..CharIndex(index)).into(),
+                            Rope::from_str("\r"),
+                        ))
+                    }
+                    _ => None,
+                }
+            })
+            .collect_vec();
+
+        // The edits are disjoint, so applying them from the last to the first
+        // keeps each of them valid in the original coordinates
+        let max_char_index = CharIndex(self.len_chars());
+        let selection_set = edits
+            .iter()
+            .rev()
+            .fold(selection_set.clone(), |selection_set, edit| {
+                selection_set.apply_edit(edit, max_char_index)
+            });
+        let selections = selection_set
+            .selections()
+            .iter()
+            .cloned()
+            .map(Action::Select)
+            .collect_vec();
+
+        // Each edit is in its own group, because the edits are in the original coordinates
+        let mut action_groups = edits
+            .into_iter()
+            .map(|edit| ActionGroup::new([Action::Edit(edit)].to_vec()))
+            .collect_vec();
+        // The selections are already in the resulting coordinates, so they join the group
+        // of the first edit, which comes first and therefore is not offset by the others
+        match action_groups.first_mut() {
+            Some(action_group) => action_group.actions.extend(selections),
+            None => action_groups.push(ActionGroup::new(selections)),
+        }
+        EditTransaction::from_action_groups(action_groups)
     }
 
     pub(crate) fn get_line_by_char_index(&self, char_index: CharIndex) -> anyhow::Result<Rope> {
@@ -335,22 +1107,38 @@ impl Buffer {
         Ok((line_start..line_end).into())
     }
 
+    /// Returns the text of `completion_replace_range`,
+    /// so that the completion items are filtered by the exact text that they will replace.
     pub(crate) fn get_word_before_char_index(
         &self,
         char_index: CharIndex,
     ) -> anyhow::Result<String> {
-        let cursor_byte = self.char_to_byte(char_index)?;
-        let regex = Regex::new(r"\b\w+").unwrap();
-        let string = self.rope.to_string();
-        let mut iter = regex.find_iter(&string);
-
-        Ok(find_previous(
-            &mut iter,
-            |_, _| true,
-            |match_| match_.start() >= cursor_byte,
-        )
-        .map(|match_| match_.as_str().to_string())
-        .unwrap_or_default())
+        Ok(self
+            .slice(&self.completion_replace_range(char_index))?
+            .to_string())
+    }
+
+    /// Returns the range of the identifier that ends at `char_index`,
+    /// which is replaced when a completion item without an edit is accepted.
+    ///
+    /// The identifier consists of alphanumeric characters, underscores and
+    /// the identifier characters of the buffer language, e.g. dashes in CSS.
+    pub(crate) fn completion_replace_range(&self, char_index: CharIndex) -> CharIndexRange {
+        let char_index = char_index.min(CharIndex(self.len_chars()));
+        let identifier_chars = self
+            .language
+            .as_ref()
+            .map(|language| language.identifier_chars())
+            .unwrap_or_default();
+        let word_start = self
+            .rope
+            .chars_at(char_index.0)
+            .reversed()
+            .take_while(|char| {
+                char.is_alphanumeric() || char == &'_' || identifier_chars.contains(char)
+            })
+            .count();
+        (char_index - word_start..char_index).into()
     }
 
     pub(crate) fn len_lines(&self) -> usize {
@@ -374,6 +1162,46 @@ impl Buffer {
         Ok(CharIndex(self.rope.try_line_to_char(line_index)?))
     }
 
+    /// Extend `selection` by moving its active end by `count` chars towards `direction`,
+    /// clamped to `0..=len_chars()`.
+    ///
+    /// Like the extension of selection modes, `initial_range` is kept as the anchor
+    /// (the current range if `selection` is not extended yet), and `range` is moved,
+    /// so the extended range is their union. Hence, after `Selection::swap_selection_ends`,
+    /// the other end of the extended range is moved instead.
+    pub(crate) fn extend_selection(
+        &self,
+        selection: &Selection,
+        direction: Direction,
+        count: usize,
+    ) -> Selection {
+        let range = selection.range();
+        let max_char_index = CharIndex(self.len_chars());
+        let moved_range = match direction {
+            Direction::Start => range.start - count..range.end - count,
+            Direction::End => {
+                (range.start + count).min(max_char_index)..(range.end + count).min(max_char_index)
+            }
+        };
+        selection
+            .clone()
+            .set_initial_range(Some(selection.initial_range.unwrap_or(range)))
+            .set_range(moved_range.into())
+    }
+
+    /// Returns the start of the given 0-based `line`, clamped to the last line.
+    pub(crate) fn char_index_at_line(&self, line: usize) -> CharIndex {
+        self.line_to_char(line.min(self.len_lines().saturating_sub(1)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the start of the line at `percent` (0 to 100) of the total lines,
+    /// for example, 50% of a 10-line buffer is the start of line 5 (0-based).
+    pub(crate) fn char_index_at_percent(&self, percent: f32) -> CharIndex {
+        let ratio = (percent / 100.0).clamp(0.0, 1.0);
+        self.char_index_at_line((ratio * self.len_lines() as f32) as usize)
+    }
+
     pub(crate) fn char_to_byte(&self, char_index: CharIndex) -> anyhow::Result<usize> {
         Ok(self.rope.try_char_to_byte(char_index.0)?)
     }
@@ -411,6 +1239,57 @@ impl Buffer {
         })
     }
 
+    /// Convert a `position` received from VS Code, whose character is measured
+    /// in UTF-16 code units, into a position of this buffer.
+    ///
+    /// The position is clamped into the content of this buffer, so that positions sent
+    /// before the buffer got out of sync do not cause invalid conversions:
+    /// the line is clamped to the last line (which is empty if the content ends with a newline),
+    /// and the character is clamped to the end of the line, excluding its line break.
+    pub(crate) fn vscode_position_to_position(
+        &self,
+        position: &ki_protocol_types::Position,
+    ) -> Position {
+        let line = (position.line as usize).min(self.rope.len_lines().saturating_sub(1));
+        let content = self
+            .rope
+            .get_line(line)
+            .map(|slice| slice.to_string())
+            .unwrap_or_default();
+        let content = content.trim_end_matches(['\n', '\r']);
+        let character = (position.character as usize).min(content.encode_utf16().count());
+        if line != position.line as usize || character != position.character as usize {
+            log::warn!(
+                "Buffer::vscode_position_to_position: clamped {position:?} to line {line}, character {character}"
+            );
+        }
+        let mut code_units = 0;
+        let column = content
+            .chars()
+            .take_while(|char| {
+                code_units += char.len_utf16();
+                code_units <= character
+            })
+            .count();
+        Position { line, column }
+    }
+
+    /// Convert the extended range of `selection` into an LSP range,
+    /// for requests such as range formatting and code action.
+    ///
+    /// Like `char_to_vscode_position`, a range ending right after a newline
+    /// ends at the start of the next line.
+    pub(crate) fn selection_to_lsp_range(
+        &self,
+        selection: &Selection,
+    ) -> anyhow::Result<lsp_types::Range> {
+        let range = selection.extended_range();
+        Ok(lsp_types::Range::new(
+            self.char_to_position(range.start)?.into(),
+            self.char_to_position(range.end)?.into(),
+        ))
+    }
+
     pub(crate) fn position_to_char(&self, position: Position) -> anyhow::Result<CharIndex> {
         let line = position.line.clamp(0, self.len_lines());
         let column = position.column.clamp(
@@ -422,31 +1301,229 @@ impl Buffer {
         Ok(CharIndex(self.rope.try_line_to_char(line)? + column))
     }
 
-    pub(crate) fn byte_to_char(&self, byte_index: usize) -> anyhow::Result<CharIndex> {
-        Ok(CharIndex(self.rope.try_byte_to_char(byte_index)?))
+    /// Records the cursor position of the primary selection in `store`, keyed by this buffer's path.
+    pub(crate) fn save_cursor_position(
+        &self,
+        store: &CursorPositionStore,
+        selection_set: &SelectionSet,
+    ) -> anyhow::Result<()> {
+        let Some(path) = self.path() else {
+            return Ok(());
+        };
+        let position = self.char_to_position(
+            selection_set
+                .primary_selection()
+                .to_char_index(&Direction::Start),
+        )?;
+        store.set(&path, position)
     }
 
-    pub(crate) fn rope(&self) -> &Rope {
-        &self.rope
+    /// Returns a selection set placed at the cursor position previously saved in `store`,
+    /// clamped to the current content in case the file shrank since.
+    pub(crate) fn restored_selection_set(
+        &self,
+        store: &CursorPositionStore,
+    ) -> Option<SelectionSet> {
+        let position = store.get(&self.path()?)?;
+        let char_index = self
+            .position_to_char(position)
+            .ok()?
+            .min(CharIndex(self.len_chars()));
+        Some(SelectionSet::new(NonEmpty::new(Selection::new(
+            (char_index..char_index).into(),
+        ))))
     }
 
-    pub(crate) fn len_chars(&self) -> usize {
-        self.rope.len_chars()
+    /// Returns the char index rendered at the cell (`row`, `column`) of the viewport,
+    /// where `row` is relative to the `scroll_top` line, and tabs span up to the next tab stop.
+    ///
+    /// Cells beyond the end of a line are clamped to the end of that line.
+    pub(crate) fn char_index_from_cell(
+        &self,
+        row: usize,
+        column: usize,
+        scroll_top: usize,
+        tab_width: usize,
+    ) -> CharIndex {
+        let tab_width = tab_width.max(1);
+        let line_index = (scroll_top + row).min(self.len_lines().saturating_sub(1));
+        let (Ok(line_start), Some(line)) = (
+            self.line_to_char(line_index),
+            self.get_line_by_line_index(line_index),
+        ) else {
+            return CharIndex(self.len_chars());
+        };
+        let mut tab_stops = self.tab_stops_for_line(line_index, tab_width).into_iter();
+        let mut cell = 0;
+        for (offset, char) in line.chars().enumerate() {
+            if char == '\n' || char == '\r' {
+                return line_start + offset;
+            }
+            let next_cell = if char == '\t' {
+                tab_stops.next().unwrap_or(cell)
+            } else {
+                cell + cell_width(char, cell, tab_width)
+            };
+            if column < next_cell {
+                return line_start + offset;
+            }
+            cell = next_cell;
+        }
+        line_start + line.len_chars()
     }
 
-    pub(crate) fn slice(&self, range: &CharIndexRange) -> anyhow::Result<Rope> {
-        let slice = self.rope.get_slice(range.start.0..range.end.0);
-        match slice {
-            Some(slice) => Ok(slice.into()),
-            None => Err(anyhow::anyhow!(
-                "Unable to obtain slice for range: {:#?}",
-                range
-            )),
+    /// Returns the display column that each tab of `line` expands to, that is,
+    /// the tab stop following the column where the tab is rendered,
+    /// so that rendering and mapping clicks to characters agree on tab widths.
+    pub(crate) fn tab_stops_for_line(&self, line: usize, tab_width: usize) -> Vec<usize> {
+        let tab_width = tab_width.max(1);
+        let Some(content) = self.get_line_by_line_index(line) else {
+            return Vec::new();
+        };
+        let mut cell = 0;
+        let mut tab_stops = Vec::new();
+        for char in content.chars() {
+            cell += cell_width(char, cell, tab_width);
+            if char == '\t' {
+                tab_stops.push(cell);
+            }
         }
+        tab_stops
     }
 
-    pub(crate) fn get_nearest_node_after_char(&self, char_index: CharIndex) -> Option<Node> {
-        let byte = self.char_to_byte(char_index).ok()?;
+    /// Returns the number of columns that the text of `range` occupies when rendered,
+    /// where tabs span up to the next tab stop, and wide characters span two columns.
+    /// For a range spanning multiple lines, the widest line is returned.
+    pub(crate) fn display_width(&self, range: CharIndexRange, tab_width: usize) -> usize {
+        let tab_width = tab_width.max(1);
+        let (Ok(line), Ok(text)) = (self.char_to_line(range.start), self.slice(&range)) else {
+            return 0;
+        };
+        let Ok(line_start) = self.line_to_char(line) else {
+            return 0;
+        };
+        // Tab stops are relative to the start of the line
+        let start_cell = self
+            .slice(&(line_start..range.start).into())
+            .map(|prefix| {
+                prefix
+                    .chars()
+                    .fold(0, |cell, char| cell + cell_width(char, cell, tab_width))
+            })
+            .unwrap_or(0);
+        let mut widths = vec![0];
+        let mut cell = start_cell;
+        for char in text.chars() {
+            if char == '\n' {
+                widths.push(0);
+                cell = 0;
+            } else {
+                let width = cell_width(char, cell, tab_width);
+                cell += width;
+                *widths.last_mut().unwrap() += width;
+            }
+        }
+        widths.into_iter().max().unwrap_or(0)
+    }
+
+    /// Returns a transaction that replaces each tab within `range` with the spaces
+    /// it renders as, i.e. up to the next tab stop, such that the alignment of the
+    /// text following the tab is preserved.
+    pub(crate) fn expand_tabs_in_range(
+        &self,
+        range: CharIndexRange,
+        tab_width: usize,
+    ) -> anyhow::Result<EditTransaction> {
+        let tab_width = tab_width.max(1);
+        // Tab stops are relative to the start of the line
+        let line_start = self.line_to_char(self.char_to_line(range.start)?)?;
+        let text = self.slice(&(line_start..range.end).into())?;
+        let mut cell = 0;
+        let mut action_groups = Vec::new();
+        for (char_index, char) in (line_start.0..).map(CharIndex).zip(text.chars()) {
+            let width = cell_width(char, cell, tab_width);
+            if char == '\t' && char_index >= range.start {
+                action_groups.push(ActionGroup::new(vec![Action::Edit(Edit::new(
+                    &self.rope,
+                    (char_index..char_index + 1).into(),
+                    Rope::from_str(&" ".repeat(width)),
+                ))]));
+            }
+            cell = if char == '\n' { 0 } else { cell + width };
+        }
+        Ok(EditTransaction::from_action_groups(action_groups))
+    }
+
+    /// Returns the on-screen (row, column) of `char_index` within its soft-wrapped line,
+    /// where row 0 is the first row of the line, unlike `char_to_position`, which
+    /// returns the logical column.
+    ///
+    /// `wrap_width` is the width of the content area passed to `soft_wrap`,
+    /// and tabs are expanded up to the next tab stop.
+    pub(crate) fn display_cursor_position(
+        &self,
+        char_index: CharIndex,
+        wrap_width: usize,
+        tab_width: usize,
+    ) -> anyhow::Result<(usize, usize)> {
+        let tab_width = tab_width.max(1);
+        let position = self.char_to_position(char_index)?;
+        let line = self
+            .get_line_by_line_index(position.line)
+            .map(|slice| slice.to_string())
+            .unwrap_or_default();
+        // Tab stops depend on the display columns, whereas `calibrate` expects a char column
+        let mut expanded_line = String::new();
+        let mut expanded_column = None;
+        let (mut cell, mut expanded_chars) = (0, 0);
+        for (column, char) in line.trim_end_matches(['\r', '\n']).chars().enumerate() {
+            if column == position.column {
+                expanded_column = Some(expanded_chars);
+            }
+            let width = cell_width(char, cell, tab_width);
+            if char == '\t' {
+                expanded_line.extend(std::iter::repeat_n(' ', width));
+                expanded_chars += width;
+            } else {
+                expanded_line.push(char);
+                expanded_chars += 1;
+            }
+            cell += width;
+        }
+        let expanded_column = expanded_column.unwrap_or(expanded_chars);
+        let calibrated = crate::soft_wrap::soft_wrap(&expanded_line, wrap_width)
+            .calibrate(Position::new(0, expanded_column))
+            .ok()
+            .and_then(|mut positions| positions.first())
+            .unwrap_or_default();
+        Ok((calibrated.line, calibrated.column))
+    }
+
+    pub(crate) fn byte_to_char(&self, byte_index: usize) -> anyhow::Result<CharIndex> {
+        Ok(CharIndex(self.rope.try_byte_to_char(byte_index)?))
+    }
+
+    pub(crate) fn rope(&self) -> &Rope {
+        &self.rope
+    }
+
+    pub(crate) fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub(crate) fn slice(&self, range: &CharIndexRange) -> anyhow::Result<Rope> {
+        let slice = self.rope.get_slice(range.start.0..range.end.0);
+        match slice {
+            Some(slice) => Ok(slice.into()),
+            None => Err(anyhow::anyhow!(
+                "Unable to obtain slice for range: {:#?}",
+                range
+            )),
+        }
+    }
+
+    pub(crate) fn get_nearest_node_after_char(&self, char_index: CharIndex) -> Option<Node> {
+        let byte = self.char_to_byte(char_index).ok()?;
         // Preorder is the main key here,
         // because preorder traversal walks the parent first
         self.tree.as_ref().and_then(|tree| {
@@ -521,6 +1598,9 @@ impl Buffer {
         update_undo_stack: bool,
         last_visible_line: u16,
     ) -> Result<(SelectionSet, Vec<ki_protocol_types::DiffEdit>), anyhow::Error> {
+        if self.read_only && update_undo_stack {
+            return Err(BufferError::ReadOnly.into());
+        }
         let new_selection_set = edit_transaction
             .non_empty_selections()
             .map(|selections| current_selection_set.clone().set_selections(selections))
@@ -531,6 +1611,8 @@ impl Buffer {
         };
 
         let inverted_edit_transaction = edit_transaction.inverse();
+        // A transaction without edits affects no lines, not even the first
+        let first_line_edited = self.affected_lines(edit_transaction).contains(&0);
 
         // NOTE: the VS Code edits should be computed BEFORE applying the edits
         let applied_vscode_edits = edit_transaction
@@ -557,6 +1639,9 @@ impl Buffer {
         };
 
         if update_undo_stack {
+            if let Some(recorded_edit_transactions) = self.recorded_edit_transactions.as_mut() {
+                recorded_edit_transactions.push(edit_transaction.clone());
+            }
             self.undo_stack.push(EditHistory {
                 edit_transaction: inverted_edit_transaction,
                 unnormalized_edits: inverted_vscode_edits,
@@ -569,7 +1654,9 @@ impl Buffer {
             self.redo_stack.clear();
         }
 
-        if reparse_tree {
+        // The tree is already reparsed when the language is redetected
+        let language_redetected = first_line_edited && self.maybe_redetect_language();
+        if reparse_tree && !language_redetected {
             self.reparse_tree()?;
         }
 
@@ -598,6 +1685,7 @@ impl Buffer {
                 &affected_range,
                 edit.new.len_bytes() as isize - byte_range.len() as isize,
             );
+            self.highlighted_spans.sort_spans();
         }
 
         let quickfix_list_items_with_char_index_range =
@@ -612,10 +1700,21 @@ impl Buffer {
                 })
                 .collect_vec();
 
+        let (start, end) = (edit.range.start.0, edit.end().0);
+
+        // The line ending follows the first line terminator, which only edits on the first line change
+        let first_line_edited = self
+            .rope
+            .try_char_to_line(start)
+            .is_ok_and(|line| line == 0);
+
         // Update the content
-        self.rope.try_remove(edit.range.start.0..edit.end().0)?;
-        self.rope
-            .try_insert(edit.range.start.0, edit.new.to_string().as_str())?;
+        self.rope.try_remove(start..end)?;
+        self.rope.try_insert(start, edit.new.to_string().as_str())?;
+        self.content_hash.set(None);
+        if first_line_edited {
+            self.line_ending = LineEnding::detect(&self.rope.line(0).to_string());
+        }
         self.dirty = true;
 
         self.owner = BufferOwner::User;
@@ -640,6 +1739,14 @@ impl Buffer {
                 false
             }
         });
+        self.folds.retain_mut(|fold| {
+            if let Some(range) = fold.apply_edit(edit) {
+                *fold = range;
+                true
+            } else {
+                false
+            }
+        });
         self.diagnostics.retain_mut(|diagnostic| {
             if let Some(range) = diagnostic.range.apply_edit(edit) {
                 diagnostic.range = range;
@@ -648,10 +1755,14 @@ impl Buffer {
                 false
             }
         });
+        self.set_diagnostics_stale(true);
         let max_char_index = CharIndex(self.len_chars());
         self.selection_set_history = std::mem::take(&mut self.selection_set_history)
             .apply(|selection_set| selection_set.apply_edit(edit, max_char_index));
 
+        // The selection ranges are outdated once the content changes
+        self.selection_ranges.clear();
+
         Ok(())
     }
 
@@ -659,6 +1770,14 @@ impl Buffer {
         &self.batch_id
     }
 
+    /// Returns true if the syntax tree has no error.
+    /// Buffers without a syntax tree are always valid.
+    pub(crate) fn is_syntactically_valid(&self) -> bool {
+        self.tree
+            .as_ref()
+            .is_none_or(|tree| !tree.root_node().has_error())
+    }
+
     pub(crate) fn has_syntax_error_at(&self, range: CharIndexRange) -> bool {
         let rope = &self.rope;
         if let Some(node) = self.tree.as_ref().and_then(|tree| {
@@ -677,8 +1796,14 @@ impl Buffer {
         path: &CanonicalizedPath,
         enable_tree_sitter: bool,
     ) -> anyhow::Result<Buffer> {
-        let content = path.read()?;
-        let language = if enable_tree_sitter {
+        let bytes = std::fs::read(path.to_path_buf())?;
+        let is_binary = is_binary_content(&bytes);
+        let content = if is_binary {
+            String::from_utf8_lossy(&bytes).into_owned()
+        } else {
+            String::from_utf8(bytes)?
+        };
+        let language = if enable_tree_sitter && !is_binary {
             language::from_path(path).or_else(|| language::from_content_directive(&content))
         } else {
             None
@@ -693,6 +1818,8 @@ impl Buffer {
 
         buffer.path = Some(path.clone());
         buffer.language = language;
+        buffer.is_binary = is_binary;
+        buffer.read_only = is_binary;
 
         Ok(buffer)
     }
@@ -706,11 +1833,20 @@ impl Buffer {
         Ok(())
     }
 
+    pub(crate) fn is_binary(&self) -> bool {
+        self.is_binary
+    }
+
     pub(crate) fn get_formatted_content(&self) -> Option<String> {
+        if self.is_binary {
+            return None;
+        }
         if let Some(content) = self.language.as_ref().and_then(|language| {
             language.formatter().map(|formatter| {
                 log::info!("[FORMAT]: {}", formatter.command_string());
-                formatter.format(&self.rope.to_string())
+                formatter
+                    .set_current_dir(self.project_root().map(|root| root.to_path_buf().clone()))
+                    .format(&self.rope.to_string())
             })
         }) {
             match content {
@@ -732,6 +1868,9 @@ impl Buffer {
         if !force && !self.dirty {
             return Ok(None);
         }
+        if self.read_only {
+            return Err(BufferError::ReadOnly.into());
+        }
 
         if let Some(path) = &self.path {
             path.write(&self.content())?;
@@ -749,15 +1888,80 @@ impl Buffer {
         force: bool,
         last_visible_line: u16,
     ) -> anyhow::Result<Option<CanonicalizedPath>> {
-        if force || self.dirty {
+        // Formatting malformed code is pointless, as formatters reject it
+        if (force || self.dirty) && self.is_syntactically_valid() {
             if let Some(formatted_content) = self.get_formatted_content() {
-                self.update_content(&formatted_content, current_selection_set, last_visible_line)?;
+                self.update_content(
+                    &formatted_content,
+                    current_selection_set.clone(),
+                    last_visible_line,
+                )?;
             }
         }
+        if let Some(edit_transaction) = self
+            .max_trailing_blank_lines
+            .filter(|_| (force || self.dirty) && !self.read_only)
+            .and_then(|max| self.trim_trailing_blank_lines(max))
+        {
+            self.apply_edit_transaction(
+                &edit_transaction,
+                current_selection_set,
+                true,
+                true,
+                last_visible_line,
+            )?;
+        }
 
         self.save_without_formatting(force)
     }
 
+    /// Returns a transaction that collapses the blank lines at the end of the content
+    /// to at most `max` lines, or `None` if there are not more than `max` of them.
+    ///
+    /// The line terminator of the last non-blank line is kept, so that the file
+    /// still ends with a newline, and blank lines elsewhere are untouched.
+    pub(crate) fn trim_trailing_blank_lines(&self, max: usize) -> Option<EditTransaction> {
+        let content = self.content();
+        let last_non_blank = content.trim_end().len();
+        // The trailing blank lines start after the terminator of the last non-blank line
+        let tail_start = last_non_blank + content[last_non_blank..].find('\n')? + 1;
+        let tail = &content[tail_start..];
+        let blank_lines = tail
+            .split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+            .count();
+        if tail.is_empty() || (blank_lines <= max && tail.ends_with('\n')) {
+            return None;
+        }
+        let range = self
+            .byte_range_to_char_index_range(&(tail_start..content.len()))
+            .ok()?;
+        let replacement = self.line_ending.as_str().repeat(blank_lines.min(max));
+        Some(EditTransaction::from_action_groups(vec![ActionGroup::new(
+            vec![Action::Edit(Edit::new(
+                &self.rope,
+                range,
+                Rope::from_str(&replacement),
+            ))],
+        )]))
+    }
+
+    /// Returns the changes that the formatter would make on `save`, without applying them,
+    /// as the replaced ranges (measured against the current content) paired with their new text.
+    ///
+    /// Returns `None` if this buffer has no formatter, or if formatting failed.
+    pub(crate) fn format_preview(&self) -> Option<Vec<(CharIndexRange, String)>> {
+        let formatted_content = self.get_formatted_content()?;
+        let edit_transaction = self.get_edit_transaction(&formatted_content).ok()?;
+        Some(
+            edit_transaction
+                .unnormalized_edits()
+                .into_iter()
+                .map(|edit| (edit.range(), edit.new.to_string()))
+                .collect(),
+        )
+    }
+
     pub(crate) fn update_content(
         &mut self,
         new_content: &str,
@@ -775,12 +1979,52 @@ impl Buffer {
         Ok(())
     }
 
+    /// Insert `text` at the end of the buffer without diffing the whole content,
+    /// which is meant for output panels that are appended frequently.
+    ///
+    /// If `max_lines` is set, the oldest lines beyond it are dropped,
+    /// along with the marks and diagnostics on them.
+    pub(crate) fn append(&mut self, text: &str, last_visible_line: u16) -> anyhow::Result<()> {
+        // Appended output is not an edit of the user, so `apply_edit` must not claim the buffer
+        let (owner, dirty) = (self.owner, self.dirty);
+        let end = CharIndex(self.len_chars());
+        self.apply_edit(
+            &Edit::new(&self.rope, (end..end).into(), text.into()),
+            last_visible_line,
+        )?;
+        if let Some(max_lines) = self.max_lines {
+            let excess_lines = self.len_lines().saturating_sub(max_lines);
+            if excess_lines > 0 {
+                let end = self.line_to_char(excess_lines)?;
+                self.apply_edit(
+                    &Edit::new(&self.rope, (CharIndex(0)..end).into(), Rope::new()),
+                    last_visible_line,
+                )?;
+            }
+        }
+        (self.owner, self.dirty) = (owner, dirty);
+        self.reparse_tree()?;
+        self.batch_id.increment();
+        Ok(())
+    }
+
     /// The resulting spans must be sorted by range
     pub(crate) fn highlighted_spans(&self) -> &Vec<HighlightedSpan> {
+        debug_assert!(self.highlighted_spans.is_sorted());
         let spans = self.highlighted_spans.0.as_ref(); // Don't clone this thing man
         spans
     }
 
+    /// Returns the ranges of the highlighted spans styled with `style_key`,
+    /// to find out which text a highlight group applies to when debugging a theme.
+    pub(crate) fn spans_of_style(&self, style_key: &StyleKey) -> Vec<CharIndexRange> {
+        self.highlighted_spans()
+            .iter()
+            .filter(|span| &span.style_key == style_key)
+            .filter_map(|span| self.byte_range_to_char_index_range(&span.byte_range).ok())
+            .collect()
+    }
+
     pub(crate) fn language(&self) -> Option<Language> {
         self.language.clone()
     }
@@ -788,9 +2032,39 @@ impl Buffer {
     #[cfg(test)]
     pub(crate) fn set_language(&mut self, language: Language) -> anyhow::Result<()> {
         self.language = Some(language);
+        self.language_overridden = true;
         self.reparse_tree()
     }
 
+    /// Re-detects the language from the content directive of the first line,
+    /// such as a shebang typed into a new file, and reparses the content if it changed.
+    ///
+    /// The language is kept if it was set explicitly, or if it is determined by the path.
+    /// Returns true if the language changed.
+    fn maybe_redetect_language(&mut self) -> bool {
+        if self.language_overridden
+            || self.is_binary
+            || self.path.as_ref().and_then(language::from_path).is_some()
+        {
+            return false;
+        }
+        let Some(language) = self
+            .rope
+            .get_line(0)
+            .and_then(|line| language::from_content_directive(&line.to_string()))
+        else {
+            return false;
+        };
+        if self.language.as_ref() == Some(&language) {
+            return false;
+        }
+        self.treesitter_language = language.tree_sitter_language();
+        self.language = Some(language);
+        (_, self.tree) =
+            Self::get_rope_and_tree(self.treesitter_language.clone(), &self.rope.to_string());
+        true
+    }
+
     pub(crate) fn treesitter_language(&self) -> Option<tree_sitter::Language> {
         self.treesitter_language.clone()
     }
@@ -819,15 +2093,112 @@ impl Buffer {
         Ok(ByteRange::new(start..end))
     }
 
+    /// Returns the byte ranges (excluding the newline character) of the lines in `lines`,
+    /// which is equivalent to calling `line_to_byte_range` for each line,
+    /// but only needs one pass over the rope.
+    pub(crate) fn line_byte_ranges(&self, lines: Range<usize>) -> Vec<Range<usize>> {
+        let Ok(mut start) = self.line_to_byte(lines.start) else {
+            return Vec::new();
+        };
+        self.rope
+            .lines_at(lines.start)
+            .take(lines.len())
+            .filter(|line| line.len_chars() > 0)
+            .map(|line| {
+                let len_bytes = line.len_bytes();
+                let range = start..start + len_bytes.saturating_sub(1);
+                start += len_bytes;
+                range
+            })
+            .collect()
+    }
+
     pub(crate) fn marks(&self) -> Vec<CharIndexRange> {
         self.marks.clone()
     }
 
+    /// Folds the given 0-based line range, which hides every line of it except the first.
+    pub(crate) fn fold(&mut self, lines: Range<usize>) -> anyhow::Result<()> {
+        if lines.len() < 2 {
+            return Err(anyhow::anyhow!(
+                "A fold must span at least 2 lines, but got {lines:?}"
+            ));
+        }
+        let range = self.line_range_to_char_index_range(lines)?;
+        if !self.folds.contains(&range) {
+            self.folds.push(range)
+        }
+        Ok(())
+    }
+
+    /// Removes every fold that overlaps with the given 0-based line range.
+    pub(crate) fn unfold(&mut self, lines: Range<usize>) {
+        let folds = std::mem::take(&mut self.folds);
+        self.folds = folds
+            .into_iter()
+            .filter(|fold| {
+                self.fold_line_range(fold).is_none_or(|folded_lines| {
+                    folded_lines.end <= lines.start || lines.end <= folded_lines.start
+                })
+            })
+            .collect()
+    }
+
+    /// The 0-based line range of `fold`.
+    fn fold_line_range(&self, fold: &CharIndexRange) -> Option<Range<usize>> {
+        Some(self.char_to_line(fold.start).ok()?..self.char_to_line(fold.end).ok()?)
+    }
+
+    /// The 0-based line ranges of the folds.
+    pub(crate) fn folded_line_ranges(&self) -> Vec<Range<usize>> {
+        self.folds
+            .iter()
+            .filter_map(|fold| self.fold_line_range(fold))
+            .collect()
+    }
+
+    /// Returns true if `line` is hidden by a fold,
+    /// note that the first line of a fold remains visible.
+    #[cfg(test)]
+    pub(crate) fn is_folded(&self, line: usize) -> bool {
+        self.folded_line_ranges()
+            .iter()
+            .any(|lines| hides_line(lines, line))
+    }
+
+    /// The 0-based indices of the lines that are not hidden by any fold.
+    pub(crate) fn visible_lines(&self) -> Vec<usize> {
+        let folded_line_ranges = self.folded_line_ranges();
+        (0..self.len_lines())
+            .filter(|line| {
+                !folded_line_ranges
+                    .iter()
+                    .any(|lines| hides_line(lines, *line))
+            })
+            .collect()
+    }
+
     /// Has the buffer changed since its last save?
     pub(crate) fn dirty(&self) -> bool {
         self.dirty
     }
 
+    /// Returns the paths of the user-owned `buffers` that have unsaved changes,
+    /// so that the user can be warned before quitting.
+    pub(crate) fn unsaved_user_buffer_paths(
+        buffers: &[Rc<RefCell<Buffer>>],
+    ) -> Vec<CanonicalizedPath> {
+        buffers
+            .iter()
+            .filter_map(|buffer| {
+                let buffer = buffer.borrow();
+                (buffer.dirty() && buffer.owner() == BufferOwner::User)
+                    .then(|| buffer.path())
+                    .flatten()
+            })
+            .collect()
+    }
+
     pub(crate) fn byte_to_position(&self, byte_index: usize) -> anyhow::Result<Position> {
         let char_index = self.byte_to_char(byte_index)?;
         self.char_to_position(char_index)
@@ -869,9 +2240,80 @@ impl Buffer {
         Ok(self.char_to_position(range.start)?..self.char_to_position(range.end)?)
     }
 
+    /// Converts every selection of `selection_set` into a quickfix item located in this buffer,
+    /// so that a multi-cursor session can be navigated as a list.
+    /// The message of each item is derived from its selection by `message`.
+    ///
+    /// Returns an empty list if this buffer has no path.
+    pub(crate) fn selections_to_quickfix(
+        &self,
+        selection_set: &SelectionSet,
+        message: impl Fn(&Selection) -> String,
+    ) -> anyhow::Result<Vec<QuickfixListItem>> {
+        let Some(path) = self.path() else {
+            return Ok(Vec::new());
+        };
+        selection_set
+            .selections()
+            .iter()
+            .map(|selection| {
+                let location = Location {
+                    path: path.clone(),
+                    range: self.char_index_range_to_position_range(selection.extended_range())?,
+                };
+                let info = Info::new("Selection".to_string(), message(selection));
+                Ok(QuickfixListItem::new(location, Some(info)))
+            })
+            .collect()
+    }
+
+    /// Converts the diagnostics of this buffer within `severity_range` into quickfix items
+    /// located in this buffer, ordered by position.
+    ///
+    /// Diagnostics whose range no longer fits this buffer are skipped,
+    /// and an empty list is returned if this buffer has no path.
+    pub(crate) fn diagnostics_to_quickfix(
+        &self,
+        severity_range: &DiagnosticSeverityRange,
+    ) -> Vec<QuickfixListItem> {
+        let Some(path) = self.path() else {
+            return Vec::new();
+        };
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| severity_range.contains(diagnostic.severity))
+            .filter_map(|diagnostic| {
+                let location = Location {
+                    path: path.clone(),
+                    range: self
+                        .char_index_range_to_position_range(diagnostic.range)
+                        .ok()?,
+                };
+                let info = Info::new("Diagnostics".to_string(), diagnostic.message.clone());
+                Some(QuickfixListItem::new(location, Some(info)))
+            })
+            .collect()
+    }
+
     /// Get an `EditTransaction` by getting the line diffs between the content of this buffer and the given `new` string
     fn get_edit_transaction(&self, new: &str) -> anyhow::Result<EditTransaction> {
         let old = self.rope.to_string();
+        // Typing or deleting a character should not re-diff the whole content
+        if let Some(edit) = self.small_contiguous_edit(&old, new) {
+            return Ok(EditTransaction::from_action_groups(vec![ActionGroup::new(
+                vec![Action::Edit(edit)],
+            )]));
+        }
+        self.line_diff_edit_transaction(old, new)
+    }
+
+    /// Get an `EditTransaction` with one edit per group of changed lines between `old`
+    /// (the current content) and `new`.
+    fn line_diff_edit_transaction(
+        &self,
+        old: String,
+        new: &str,
+    ) -> anyhow::Result<EditTransaction> {
         let new = new.to_string();
         let edits = {
             let diff_from_lines = similar::TextDiff::from_lines(&old, &new);
@@ -952,6 +2394,146 @@ impl Buffer {
         ))
     }
 
+    /// Returns the single edit that turns `old` (the current content) into `new`,
+    /// if they only differ in one contiguous region of at most `SMALL_EDIT_MAX_CHARS` chars,
+    /// which is the case of a typed or deleted character.
+    fn small_contiguous_edit(&self, old: &str, new: &str) -> Option<Edit> {
+        const SMALL_EDIT_MAX_CHARS: usize = 32;
+        let old_len = self.rope.len_chars();
+        let new_len = new.chars().count();
+        let prefix = old
+            .chars()
+            .zip(new.chars())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let suffix = old
+            .chars()
+            .rev()
+            .zip(new.chars().rev())
+            .take_while(|(old, new)| old == new)
+            .count()
+            .min(old_len - prefix)
+            .min(new_len - prefix);
+        let removed = old_len - prefix - suffix;
+        let inserted = new_len - prefix - suffix;
+        if (removed == 0 && inserted == 0)
+            || removed > SMALL_EDIT_MAX_CHARS
+            || inserted > SMALL_EDIT_MAX_CHARS
+        {
+            return None;
+        }
+        Some(Edit::new(
+            &self.rope,
+            (CharIndex(prefix)..CharIndex(prefix + removed)).into(),
+            Rope::from_str(&new.chars().skip(prefix).take(inserted).collect::<String>()),
+        ))
+    }
+
+    /// Applies the hunks of the unified diff `patch` as a single edit transaction,
+    /// and selects the new content of each hunk.
+    ///
+    /// Nothing is applied if any hunk does not apply cleanly,
+    /// that is, if its context and deleted lines do not match the content at its starting line.
+    pub(crate) fn apply_unified_diff(
+        &mut self,
+        patch: &str,
+        current_selection_set: SelectionSet,
+        last_visible_line: u16,
+    ) -> anyhow::Result<(SelectionSet, Vec<ki_protocol_types::DiffEdit>)> {
+        let hunk_header = lazy_regex::regex!(r"^@@ -(\d+)(?:,(\d+))? \+\d+(?:,\d+)? @@");
+        // Each hunk is the 0-based starting line, the old lines and the new lines
+        let mut hunks: Vec<(usize, Vec<String>, Vec<String>)> = Vec::new();
+        // Whether the previous hunk line belongs to the old lines and to the new lines
+        let mut previous_line_kind = (false, false);
+        for line in patch.split_inclusive('\n') {
+            if let Some(captures) = hunk_header.captures(line) {
+                let start: usize = captures[1].parse()?;
+                let old_lines_count = match captures.get(2) {
+                    Some(count) => count.as_str().parse()?,
+                    None => 1,
+                };
+                // The start of a hunk without old lines refers to the line before the insertion
+                let start = if old_lines_count == 0 {
+                    start
+                } else {
+                    start.saturating_sub(1)
+                };
+                hunks.push((start, Vec::new(), Vec::new()));
+                continue;
+            }
+            let Some((_, old_lines, new_lines)) = hunks.last_mut() else {
+                // Skip the file headers
+                continue;
+            };
+            let mut chars = line.chars();
+            let kind = chars.next();
+            let content = chars.as_str().to_string();
+            match kind {
+                Some(' ') => {
+                    old_lines.push(content.clone());
+                    new_lines.push(content);
+                    previous_line_kind = (true, true);
+                }
+                Some('-') => {
+                    old_lines.push(content);
+                    previous_line_kind = (true, false);
+                }
+                Some('+') => {
+                    new_lines.push(content);
+                    previous_line_kind = (false, true);
+                }
+                // "\ No newline at end of file"
+                Some('\\') => {
+                    let (old, new) = previous_line_kind;
+                    for (is_previous, lines) in [(old, old_lines), (new, new_lines)] {
+                        if let Some(last_line) = lines.last_mut().filter(|_| is_previous) {
+                            *last_line = last_line.trim_end_matches('\n').to_string();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let action_groups = hunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, (start, old_lines, new_lines))| {
+                let (old, new) = (old_lines.concat(), new_lines.concat());
+                let range_start = self.line_to_char(start)?;
+                let range: CharIndexRange =
+                    (range_start..range_start + old.chars().count()).into();
+                let actual = self
+                    .slice(&range)
+                    .map(|rope| rope.to_string())
+                    .unwrap_or_default();
+                if actual != old {
+                    return Err(anyhow::anyhow!(
+                        "Hunk {} does not apply cleanly at line {}, expected:\n{old}\nbut found:\n{actual}",
+                        index + 1,
+                        start + 1
+                    ));
+                }
+                let new_char_count = new.chars().count();
+                Ok(ActionGroup::new(
+                    [
+                        Action::Edit(Edit::new(&self.rope, range, new.into())),
+                        Action::Select(Selection::new(
+                            (range_start..range_start + new_char_count).into(),
+                        )),
+                    ]
+                    .to_vec(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.apply_edit_transaction(
+            &EditTransaction::from_action_groups(action_groups),
+            current_selection_set,
+            true,
+            true,
+            last_visible_line,
+        )
+    }
+
     /// The boolean returned indicates whether the replacement causes any modification
     pub(crate) fn replace(
         &mut self,
@@ -1021,48 +2603,930 @@ impl Buffer {
         Ok(self.char_to_byte(range.start)?..self.char_to_byte(range.end)?)
     }
 
-    pub(crate) fn quickfix_list_items(&self) -> Vec<QuickfixListItem> {
-        self.quickfix_list_items.clone()
+    /// Returns the number of bytes and chars of the (extended) range of `selection`,
+    /// or `(0, 0)` if the range is empty or out of bounds.
+    pub(crate) fn selection_size(&self, selection: &Selection) -> (usize, usize) {
+        let range = selection.extended_range();
+        self.char_index_range_to_byte_range(range)
+            .map(|byte_range| (byte_range.len(), range.len()))
+            .unwrap_or_default()
     }
 
-    pub(crate) fn line_range_to_char_index_range(
-        &self,
-        range: Range<usize>,
-    ) -> anyhow::Result<CharIndexRange> {
-        Ok((self.line_to_char(range.start)?..self.line_to_char(range.end)?).into())
+    /// Builds a nesting outline purely from the leading indentation of every non-blank line,
+    /// for languages without a tree-sitter grammar.
+    ///
+    /// A line is nested under the closest preceding line with less indentation.
+    pub(crate) fn indent_outline(&self) -> Vec<OutlineEntry> {
+        let mut indentation_stack: Vec<usize> = Vec::new();
+        self.rope
+            .lines()
+            .enumerate()
+            .filter_map(|(line, slice)| {
+                let content = slice.to_string();
+                let text = content.trim();
+                if text.is_empty() {
+                    return None;
+                }
+                let indentation = content.len() - content.trim_start().len();
+                while indentation_stack
+                    .last()
+                    .is_some_and(|&parent_indentation| parent_indentation >= indentation)
+                {
+                    indentation_stack.pop();
+                }
+                let indent_level = indentation_stack.len();
+                indentation_stack.push(indentation);
+                Some(OutlineEntry {
+                    line,
+                    indent_level,
+                    text: text.to_string(),
+                })
+            })
+            .collect()
     }
 
-    pub(crate) fn line_range_to_full_char_index_range(
-        &self,
-        range: Range<usize>,
-    ) -> anyhow::Result<CharIndexRange> {
-        let end_line_char_start = self.line_to_char(range.end)?;
-        let line = self.get_line_by_line_index(range.end).ok_or_else(|| {
-            anyhow::anyhow!(
-                "Buffer::line_range_to_char_index_range: Unable to get line at index {}",
-                range.end
-            )
+    /// Returns a transaction that re-indents the lines of the just-pasted `range`
+    /// to the indentation expected at the paste location, while preserving
+    /// the relative indentation among the pasted lines.
+    ///
+    /// The expected indentation is that of the nearest sibling node of the paste
+    /// (in the innermost node enclosing it), or one level deeper than
+    /// the enclosing node if the paste has no sibling.
+    /// If `range` starts in the middle of a line, that first line is left untouched.
+    pub(crate) fn reindent_pasted(&self, range: CharIndexRange) -> anyhow::Result<EditTransaction> {
+        let leading_whitespace = |line: usize| -> usize {
+            self.get_line_by_line_index(line)
+                .map(|slice| {
+                    slice
+                        .chars()
+                        .take_while(|char| char.is_whitespace() && *char != '\n')
+                        .count()
+                })
+                .unwrap_or_default()
+        };
+        let is_blank = |line: usize| {
+            self.get_line_by_line_index(line)
+                .is_none_or(|slice| slice.chars().all(char::is_whitespace))
+        };
+
+        let first_line = self.char_to_line(range.start)?;
+        let last_line = self.char_to_line(if range.end > range.start {
+            range.end - 1
+        } else {
+            range.end
         })?;
-        Ok((self.line_to_char(range.start)?..end_line_char_start + line.len_chars()).into())
-    }
+        let first_line = if range.start == self.line_to_char(first_line)? {
+            first_line
+        } else {
+            first_line + 1
+        };
+        let lines = (first_line..last_line + 1)
+            .filter(|line| !is_blank(*line))
+            .collect_vec();
+        let Some(pasted_indentation) = lines.iter().map(|line| leading_whitespace(*line)).min()
+        else {
+            return Ok(EditTransaction::from_action_groups(Vec::new()));
+        };
 
-    pub(crate) fn char_index_range_to_line_range(
-        &self,
-        range: CharIndexRange,
-    ) -> anyhow::Result<Range<usize>> {
-        Ok(self.char_to_line(range.start)?..self.char_to_line(range.end)?)
+        let base_indentation = self.paste_base_indentation(range)?;
+        Ok(EditTransaction::from_action_groups(
+            lines
+                .into_iter()
+                .map(|line| -> anyhow::Result<_> {
+                    let start = self.line_to_char(line)?;
+                    let old_indentation =
+                        self.slice(&(start..start + leading_whitespace(line)).into())?;
+                    let new_indentation = format!(
+                        "{}{}",
+                        base_indentation,
+                        old_indentation
+                            .chars()
+                            .skip(pasted_indentation)
+                            .collect::<String>()
+                    );
+                    Ok(ActionGroup::new(vec![Action::Edit(Edit::new(
+                        &self.rope,
+                        (start..start + old_indentation.len_chars()).into(),
+                        Rope::from_str(&new_indentation),
+                    ))]))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        ))
     }
 
-    pub(crate) fn push_selection_set_history(&mut self, selection_set: SelectionSet) {
-        self.selection_set_history.push(selection_set.clone());
+    /// Returns true if only whitespaces precede `byte` on its line.
+    fn begins_line(&self, byte: usize) -> bool {
+        self.byte_to_char(byte)
+            .and_then(|char_index| {
+                let line_start = self.line_to_char(self.char_to_line(char_index)?)?;
+                self.slice(&(line_start..char_index).into())
+            })
+            .is_ok_and(|prefix| prefix.chars().all(char::is_whitespace))
     }
 
-    pub(crate) fn previous_selection_set(&mut self) -> Option<SelectionSet> {
-        self.selection_set_history.undo()
-    }
+    /// Returns the indentation expected for code pasted at `range`, see `reindent_pasted`.
+    fn paste_base_indentation(&self, range: CharIndexRange) -> anyhow::Result<String> {
+        let line_indentation = |byte: usize| -> anyhow::Result<String> {
+            Ok(self
+                .line_indentation(self.byte_to_char(byte)?)
+                .unwrap_or_default())
+        };
+        let byte_range = self.char_index_range_to_byte_range(range)?;
+        let Some(root) = self.tree.as_ref().map(|tree| tree.root_node()) else {
+            return Ok(String::new());
+        };
+        let Some(enclosing) = std::iter::successors(
+            root.descendant_for_byte_range(byte_range.start, byte_range.end),
+            |node| node.parent(),
+        )
+        .find(|node| node.start_byte() < byte_range.start || node.end_byte() > byte_range.end) else {
+            return Ok(String::new());
+        };
 
-    pub(crate) fn next_selection_set(&mut self) -> Option<SelectionSet> {
-        self.selection_set_history.redo()
+        // Only siblings that begin their line tell the indentation of the paste
+        let mut cursor = enclosing.walk();
+        let siblings = enclosing
+            .named_children(&mut cursor)
+            .filter(|child| {
+                child.end_byte() <= byte_range.start || child.start_byte() >= byte_range.end
+            })
+            .filter(|child| self.begins_line(child.start_byte()))
+            .collect_vec();
+        let nearest_sibling = siblings
+            .iter()
+            .rev()
+            .find(|child| child.end_byte() <= byte_range.start)
+            .or_else(|| siblings.first());
+        if let Some(sibling) = nearest_sibling {
+            return line_indentation(sibling.start_byte());
+        }
+        if enclosing.id() == root.id() {
+            return Ok(String::new());
+        }
+        Ok(format!(
+            "{}{}",
+            line_indentation(enclosing.start_byte())?,
+            INDENT_CHAR.to_string().repeat(INDENT_WIDTH)
+        ))
+    }
+
+    /// Returns the indentation for a new sibling line in the block enclosing `char_index`,
+    /// such as a new field below the current one in a struct body.
+    ///
+    /// The indentation is taken from the nearest sibling that begins its own line,
+    /// or is one level deeper than the enclosing node if the block is empty.
+    pub(crate) fn enclosing_block_indent(&self, char_index: CharIndex) -> String {
+        let line_indentation = |byte: usize| {
+            self.byte_to_char(byte)
+                .ok()
+                .and_then(|char_index| self.line_indentation(char_index))
+                .unwrap_or_default()
+        };
+        let Ok(byte) = self.char_to_byte(char_index) else {
+            return String::new();
+        };
+        if self.tree.is_none() {
+            return self.line_indentation(char_index).unwrap_or_default();
+        }
+        let Some((block, siblings)) = self.enclosing_block(byte) else {
+            return String::new();
+        };
+        match siblings
+            .iter()
+            .rev()
+            .find(|child| child.start_byte() <= byte)
+            .or_else(|| siblings.first())
+        {
+            Some(sibling) => line_indentation(sibling.start_byte()),
+            None => format!(
+                "{}{}",
+                line_indentation(block.start_byte()),
+                INDENT_CHAR.to_string().repeat(INDENT_WIDTH)
+            ),
+        }
+    }
+
+    /// Returns the innermost multiline node around `byte` whose children begin lines
+    /// below the line it starts on, or which has no children at all, paired with
+    /// those children.
+    fn enclosing_block(&self, byte: usize) -> Option<(Node, Vec<Node>)> {
+        let line = |byte: usize| {
+            self.byte_to_char(byte)
+                .and_then(|char_index| self.char_to_line(char_index))
+                .ok()
+        };
+        std::iter::successors(
+            self.tree
+                .as_ref()?
+                .root_node()
+                .descendant_for_byte_range(byte, byte),
+            |node| node.parent(),
+        )
+        .filter(|node| node.start_byte() < byte && line(node.start_byte()) < line(node.end_byte()))
+        .find_map(|node| {
+            let mut cursor = node.walk();
+            let siblings = node
+                .named_children(&mut cursor)
+                .filter(|child| {
+                    self.begins_line(child.start_byte())
+                        && line(child.start_byte()) > line(node.start_byte())
+                })
+                .collect_vec();
+            (!siblings.is_empty() || node.named_child_count() == 0).then_some((node, siblings))
+        })
+    }
+
+    /// Returns the range covering every statement of the block enclosing `char_index`,
+    /// excluding the braces of the block, e.g. for selecting the contents of a function body.
+    ///
+    /// Returns `None` if there is no enclosing block, or if it is empty.
+    pub(crate) fn block_statements_range(&self, char_index: CharIndex) -> Option<CharIndexRange> {
+        let byte = self.char_to_byte(char_index).ok()?;
+        let block = std::iter::successors(
+            self.tree
+                .as_ref()?
+                .root_node()
+                .descendant_for_byte_range(byte, byte),
+            |node| node.parent(),
+        )
+        .find(|node| {
+            let braced = node.child(0).is_some_and(|child| child.kind() == "{")
+                && node
+                    .child(node.child_count().saturating_sub(1))
+                    .is_some_and(|child| child.kind() == "}");
+            // Indentation-based blocks, such as Python's, have no braces
+            braced || node.kind() == "block"
+        })?;
+        let mut cursor = block.walk();
+        let statements = block.named_children(&mut cursor).collect_vec();
+        let (first, last) = (statements.first()?, statements.last()?);
+        self.byte_range_to_char_index_range(&(first.start_byte()..last.end_byte()))
+            .ok()
+    }
+
+    /// Returns a transaction that inserts `text` on a new line above (`Direction::Start`)
+    /// or below (`Direction::End`) the statement at `char_index`, indented like its siblings,
+    /// paired with a cursor at the end of the inserted text.
+    ///
+    /// This is a structure-aware version of Vim's `o` and `O`.
+    pub(crate) fn insert_sibling_line(
+        &self,
+        char_index: CharIndex,
+        text: &str,
+        direction: Direction,
+    ) -> anyhow::Result<(EditTransaction, SelectionSet)> {
+        let byte = self.char_to_byte(char_index)?;
+        let indentation = self.enclosing_block_indent(char_index);
+        // The statement is the child of the enclosing block that contains `char_index`
+        let statement = self.enclosing_block(byte).and_then(|(_, siblings)| {
+            siblings
+                .into_iter()
+                .find(|child| child.start_byte() <= byte && byte < child.end_byte())
+        });
+        let lines = match statement {
+            Some(statement) => {
+                self.char_to_line(self.byte_to_char(statement.start_byte())?)?
+                    ..=self.char_to_line(self.byte_to_char(statement.end_byte())?)?
+            }
+            None => {
+                let line = self.char_to_line(char_index)?;
+                line..=line
+            }
+        };
+        let new_line = format!("{indentation}{text}");
+        let (position, inserted, cursor) = match direction {
+            Direction::Start => {
+                let position = self.line_to_char(*lines.start())?;
+                (
+                    position,
+                    format!("{new_line}{}", self.line_ending.as_str()),
+                    position + new_line.chars().count(),
+                )
+            }
+            Direction::End => {
+                let last_line = *lines.end();
+                let line_content = self
+                    .get_line_by_line_index(last_line)
+                    .map(|line| line.to_string())
+                    .unwrap_or_default();
+                let position = self.line_to_char(last_line)?
+                    + line_content.trim_end_matches(['\r', '\n']).chars().count();
+                let line_ending = self.line_ending.as_str();
+                (
+                    position,
+                    format!("{line_ending}{new_line}"),
+                    position + line_ending.chars().count() + new_line.chars().count(),
+                )
+            }
+        };
+        let edit_transaction =
+            EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(
+                    &self.rope,
+                    (position..position).into(),
+                    Rope::from_str(&inserted),
+                ),
+            )])]);
+        let selection_set =
+            SelectionSet::new(NonEmpty::new(Selection::new((cursor..cursor).into())));
+        Ok((edit_transaction, selection_set))
+    }
+
+    /// Returns the range of lines touched by `edit_transaction`, measured
+    /// against the content before the transaction is applied.
+    pub(crate) fn affected_lines(&self, edit_transaction: &EditTransaction) -> Range<usize> {
+        let edits = edit_transaction.unnormalized_edits();
+        let line = |char_index: CharIndex| {
+            self.char_to_line(char_index)
+                .unwrap_or_else(|_| self.len_lines())
+        };
+        let Some(start) = edits.iter().map(|edit| line(edit.range().start)).min() else {
+            return 0..0;
+        };
+        let end = edits
+            .iter()
+            .map(|edit| line(edit.range().end))
+            .max()
+            .unwrap_or(start);
+        start..end + 1
+    }
+
+    /// Returns true if the selected text is entirely whitespace,
+    /// which includes empty selections.
+    ///
+    /// Used for guarding operations that should not run on whitespace, such as formatting.
+    pub(crate) fn selection_is_whitespace(&self, selection: &Selection) -> bool {
+        self.slice(&selection.extended_range())
+            .map(|slice| slice.chars().all(char::is_whitespace))
+            .unwrap_or(true)
+    }
+
+    pub(crate) fn quickfix_list_items(&self) -> Vec<QuickfixListItem> {
+        self.quickfix_list_items.clone()
+    }
+
+    pub(crate) fn line_range_to_char_index_range(
+        &self,
+        range: Range<usize>,
+    ) -> anyhow::Result<CharIndexRange> {
+        Ok((self.line_to_char(range.start)?..self.line_to_char(range.end)?).into())
+    }
+
+    pub(crate) fn line_range_to_full_char_index_range(
+        &self,
+        range: Range<usize>,
+    ) -> anyhow::Result<CharIndexRange> {
+        let end_line_char_start = self.line_to_char(range.end)?;
+        let line = self.get_line_by_line_index(range.end).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Buffer::line_range_to_char_index_range: Unable to get line at index {}",
+                range.end
+            )
+        })?;
+        Ok((self.line_to_char(range.start)?..end_line_char_start + line.len_chars()).into())
+    }
+
+    pub(crate) fn char_index_range_to_line_range(
+        &self,
+        range: CharIndexRange,
+    ) -> anyhow::Result<Range<usize>> {
+        Ok(self.char_to_line(range.start)?..self.char_to_line(range.end)?)
+    }
+
+    /// Returns a transaction that deletes all text outside the selections of `selection_set`,
+    /// so that only the selected fragments remain, concatenated in order
+    /// (separated by line breaks if `separate_with_newlines` is true).
+    ///
+    /// Overlapping selections are merged, and each remaining fragment is selected.
+    pub(crate) fn keep_only_selections(
+        &self,
+        selection_set: &SelectionSet,
+        separate_with_newlines: bool,
+    ) -> EditTransaction {
+        let selections = selection_set.clone().merge_selections();
+        let selections = selections.selections();
+        let separator = if separate_with_newlines { "\n" } else { "" };
+        let boundaries = std::iter::once(CharIndex(0))
+            .chain(
+                selections
+                    .iter()
+                    .flat_map(|selection| [selection.range.start, selection.range.end]),
+            )
+            .chain(std::iter::once(CharIndex(self.len_chars())))
+            .collect_vec();
+        let last_gap_index = selections.len();
+        // The gaps go first, so that a selection is offset by the gap right before it
+        let gaps = boundaries
+            .chunks(2)
+            .enumerate()
+            .filter_map(|(index, gap)| {
+                let new = if index == 0 || index == last_gap_index {
+                    ""
+                } else {
+                    separator
+                };
+                (gap[0] < gap[1] || !new.is_empty()).then(|| {
+                    ActionGroup::new(vec![Action::Edit(Edit::new(
+                        &self.rope,
+                        (gap[0]..gap[1]).into(),
+                        Rope::from_str(new),
+                    ))])
+                })
+            })
+            .collect_vec();
+        EditTransaction::from_action_groups(
+            gaps.into_iter()
+                .chain(
+                    selections
+                        .iter()
+                        .map(|selection| ActionGroup::new(vec![Action::Select(selection.clone())])),
+                )
+                .collect(),
+        )
+    }
+
+    /// Returns a transaction that shifts the lines of `range` right by `levels` of `indent_unit`,
+    /// or left if `levels` is negative, like Vim's `>` and `<`.
+    ///
+    /// Shifting left never removes more than the existing leading whitespace of a line.
+    /// Blank lines, and lines that start within a string that began on an earlier line,
+    /// are left untouched.
+    pub(crate) fn shift_indent(
+        &self,
+        range: CharIndexRange,
+        levels: i32,
+        indent_unit: &str,
+    ) -> anyhow::Result<EditTransaction> {
+        let last_char = if range.end > range.start {
+            range.end - 1
+        } else {
+            range.end
+        };
+        let action_groups = (self.char_to_line(range.start)?..self.char_to_line(last_char)? + 1)
+            .filter_map(|line| {
+                let start = self.line_to_char(line).ok()?;
+                let content = self.get_line_by_line_index(line)?;
+                let within_earlier_string =
+                    std::iter::successors(self.node_at_char(start), |node| node.parent())
+                        .find(|node| node.kind().contains("string"))
+                        .is_some_and(|node| node.start_position().row < line);
+                if content.chars().all(char::is_whitespace) || within_earlier_string {
+                    return None;
+                }
+                let edit = if levels >= 0 {
+                    Edit::new(
+                        &self.rope,
+                        (start..start).into(),
+                        Rope::from_str(&indent_unit.repeat(levels as usize)),
+                    )
+                } else {
+                    let leading_whitespace = content
+                        .chars()
+                        .take_while(|char| char.is_whitespace())
+                        .count();
+                    let removed = leading_whitespace
+                        .min(indent_unit.chars().count() * levels.unsigned_abs() as usize);
+                    Edit::new(&self.rope, (start..start + removed).into(), Rope::new())
+                };
+                Some(ActionGroup::new(vec![Action::Edit(edit)]))
+            })
+            .collect_vec();
+        Ok(EditTransaction::from_action_groups(action_groups))
+    }
+
+    /// Returns a transaction that pads the cells of the pipe-delimited (Markdown)
+    /// or comma-delimited (CSV) lines of `range`, so that their columns align.
+    ///
+    /// The header separator row of a Markdown table (e.g. `|---|:-:|`) is kept,
+    /// with its dashes extended to the column width.
+    /// Returns `None` if the lines are not delimited consistently.
+    pub(crate) fn format_table(&self, range: CharIndexRange) -> Option<EditTransaction> {
+        let last_char = if range.end > range.start {
+            range.end - 1
+        } else {
+            range.end
+        };
+        let last_line = self.char_to_line(last_char).ok()?;
+        let start = self
+            .line_to_char(self.char_to_line(range.start).ok()?)
+            .ok()?;
+        let end_line_content = self.get_line_by_line_index(last_line)?.to_string();
+        let end = self.line_to_char(last_line).ok()?
+            + end_line_content
+                .trim_end_matches(['\r', '\n'])
+                .chars()
+                .count();
+        let text = self.slice(&(start..end).into()).ok()?.to_string();
+        let lines = text.lines().collect_vec();
+        let indentation: String = lines
+            .first()?
+            .chars()
+            .take_while(|char| char.is_whitespace())
+            .collect();
+        let delimiter = ['|', ',']
+            .into_iter()
+            .find(|delimiter| lines.iter().all(|line| line.contains(*delimiter)))?;
+        let is_markdown = delimiter == '|';
+        let rows = lines
+            .iter()
+            .map(|line| {
+                let line = line.trim();
+                let line = if is_markdown {
+                    let line = line.strip_prefix('|').unwrap_or(line);
+                    line.strip_suffix('|').unwrap_or(line)
+                } else {
+                    line
+                };
+                line.split(delimiter)
+                    .map(|cell| cell.trim().to_string())
+                    .collect_vec()
+            })
+            .collect_vec();
+        let is_separator_row = |row: &Vec<String>| {
+            is_markdown
+                && row
+                    .iter()
+                    .all(|cell| lazy_regex::regex_is_match!(r"^:?-+:?$", cell))
+        };
+        let width = |cell: &str| unicode_width::UnicodeWidthStr::width(cell);
+        let column_widths = (0..rows.iter().map(|row| row.len()).max()?)
+            .map(|column| {
+                rows.iter()
+                    .filter(|row| !is_separator_row(row))
+                    .filter_map(|row| row.get(column))
+                    .map(|cell| width(cell))
+                    .max()
+                    .unwrap_or_default()
+                    // A separator cell needs at least three dashes
+                    .max(if is_markdown { 3 } else { 0 })
+            })
+            .collect_vec();
+        let formatted = rows
+            .iter()
+            .map(|row| {
+                let cells = column_widths
+                    .iter()
+                    .enumerate()
+                    .map(|(column, column_width)| {
+                        let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                        if is_separator_row(row) {
+                            let dashes = "-".repeat(
+                                column_width
+                                    - cell.starts_with(':') as usize
+                                    - cell.ends_with(':') as usize,
+                            );
+                            format!(
+                                "{}{dashes}{}",
+                                if cell.starts_with(':') { ":" } else { "" },
+                                if cell.ends_with(':') { ":" } else { "" }
+                            )
+                        } else if is_markdown {
+                            format!("{cell}{}", " ".repeat(column_width - width(cell)))
+                        } else {
+                            // The delimiter hugs the cell, the padding follows it
+                            let is_last = column + 1 == column_widths.len();
+                            let delimiter = if is_last { "" } else { "," };
+                            format!(
+                                "{cell}{delimiter}{}",
+                                " ".repeat(column_width - width(cell))
+                            )
+                        }
+                    });
+                if is_markdown {
+                    format!("{indentation}| {} |", cells.collect_vec().join(" | "))
+                } else {
+                    let line = format!("{indentation}{}", cells.collect_vec().join(" "));
+                    line.trim_end().to_string()
+                }
+            })
+            .join("\n");
+        Some(EditTransaction::from_action_groups(vec![ActionGroup::new(
+            vec![Action::Edit(Edit::new(
+                &self.rope,
+                (start..end).into(),
+                Rope::from_str(&formatted),
+            ))],
+        )]))
+    }
+
+    /// Returns the range of the word under `char_index`, like Vim's `iw` (`around` is false)
+    /// and `aw` (`around` is true), without crossing line boundaries.
+    ///
+    /// A word is a run of alphanumeric characters and underscores, or a run of other
+    /// non-blank characters. On whitespace, "inside" selects the whitespace run, and
+    /// "around" also selects the word that follows it. Otherwise, "around" also selects
+    /// the whitespace that follows the word, or precedes it if there is none after.
+    pub(crate) fn word_object(
+        &self,
+        char_index: CharIndex,
+        around: bool,
+    ) -> anyhow::Result<CharIndexRange> {
+        #[derive(PartialEq)]
+        enum Class {
+            Whitespace,
+            Word,
+            Punctuation,
+        }
+        let class = |char: char| {
+            if char.is_whitespace() {
+                Class::Whitespace
+            } else if char.is_alphanumeric() || char == '_' {
+                Class::Word
+            } else {
+                Class::Punctuation
+            }
+        };
+        let line_start = self.line_to_char(self.char_to_line(char_index)?)?;
+        let chars = self
+            .get_line_by_char_index(char_index)?
+            .chars()
+            .take_while(|char| *char != '\r' && *char != '\n')
+            .collect_vec();
+        let offset = char_index.0 - line_start.0;
+        let Some(current) = chars.get(offset).map(|char| class(*char)) else {
+            return Ok((char_index..char_index).into());
+        };
+        // Returns the end of the run of `class` that starts at `start`
+        let run_end = |start: usize, run_class: &Class| {
+            start
+                + chars[start..]
+                    .iter()
+                    .take_while(|char| class(**char) == *run_class)
+                    .count()
+        };
+        let start = offset
+            - chars[..offset]
+                .iter()
+                .rev()
+                .take_while(|char| class(**char) == current)
+                .count();
+        let end = run_end(offset, &current);
+        let (start, end) = if !around {
+            (start, end)
+        } else if current == Class::Whitespace {
+            match chars.get(end) {
+                Some(next) => (start, run_end(end, &class(*next))),
+                None => (start, end),
+            }
+        } else {
+            let trailing_end = run_end(end, &Class::Whitespace);
+            if trailing_end > end {
+                (start, trailing_end)
+            } else {
+                let leading = chars[..start]
+                    .iter()
+                    .rev()
+                    .take_while(|char| class(**char) == Class::Whitespace)
+                    .count();
+                (start - leading, end)
+            }
+        };
+        Ok((line_start + start..line_start + end).into())
+    }
+
+    /// Returns a selection on each whole-word occurrence of the word under `char_index`,
+    /// where the primary selection is the occurrence under `char_index`,
+    /// so that every use of a name can be edited at once.
+    ///
+    /// Returns `None` if `char_index` is not on a word.
+    pub(crate) fn cursors_at_word_occurrences(
+        &self,
+        char_index: CharIndex,
+    ) -> Option<SelectionSet> {
+        let word_range = self.word_object(char_index, false).ok()?;
+        let word = self.slice(&word_range).ok()?.to_string();
+        if !word
+            .chars()
+            .all(|char| char.is_alphanumeric() || char == '_')
+            || word.is_empty()
+        {
+            return None;
+        }
+        let regex = Regex::new(&format!(r"\b{}\b", regex::escape(&word))).ok()?;
+        let content = self.content();
+        let ranges = regex
+            .find_iter(&content)
+            .filter_map(|found| self.byte_range_to_char_index_range(&found.range()).ok())
+            .collect_vec();
+        let cursor_index = ranges.iter().position(|range| range == &word_range)?;
+        let mut selection_set = SelectionSet::new(NonEmpty::from_vec(
+            ranges.into_iter().map(Selection::new).collect(),
+        )?);
+        selection_set.cursor_index = cursor_index;
+        Some(selection_set)
+    }
+
+    /// Returns a transaction that replaces the typographic characters of `range`,
+    /// which are common in text pasted from the web, with their ASCII counterparts:
+    /// curly quotes become straight quotes, em and en dashes become hyphens,
+    /// and the ellipsis becomes three dots.
+    ///
+    /// If the buffer has a syntax tree, only strings and comments are normalized,
+    /// as typographic characters in code are deliberate.
+    pub(crate) fn normalize_typography(
+        &self,
+        range: CharIndexRange,
+    ) -> anyhow::Result<EditTransaction> {
+        let replacement = |char: char| match char {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => Some("'"),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => Some("\""),
+            '\u{2013}' | '\u{2014}' => Some("-"),
+            '\u{2026}' => Some("..."),
+            _ => None,
+        };
+        let action_groups = (range.start.0..)
+            .map(CharIndex)
+            .zip(self.slice(&range)?.chars())
+            .filter_map(|(char_index, char)| Some((char_index, replacement(char)?)))
+            .filter(|(char_index, _)| {
+                self.tree.is_none() || self.syntax_context_at(*char_index) != SyntaxContext::Code
+            })
+            .map(|(char_index, replacement)| {
+                ActionGroup::new(vec![Action::Edit(Edit::new(
+                    &self.rope,
+                    (char_index..char_index + 1).into(),
+                    Rope::from_str(replacement),
+                ))])
+            })
+            .collect();
+        Ok(EditTransaction::from_action_groups(action_groups))
+    }
+
+    /// Returns a transaction that flips the case of the character at the start of
+    /// each selection of `selection_set`, and moves the selection to the next character,
+    /// like Vim's `~`, as one undo step.
+    ///
+    /// Non-alphabetic characters are left unchanged, but the selection still advances.
+    /// Selections beyond the end of the buffer are left untouched.
+    pub(crate) fn toggle_char_case_at_selections(
+        &self,
+        selection_set: &SelectionSet,
+    ) -> EditTransaction {
+        EditTransaction::from_action_groups(
+            selection_set
+                .selections()
+                .iter()
+                .filter_map(|selection| self.toggle_char_case_action_group(selection))
+                .collect(),
+        )
+    }
+
+    fn toggle_char_case_action_group(&self, selection: &Selection) -> Option<ActionGroup> {
+        let char_index = selection.range().start;
+        let char = self.rope.get_char(char_index.0)?;
+        let toggled: String = if char.is_lowercase() {
+            char.to_uppercase().collect()
+        } else if char.is_uppercase() {
+            char.to_lowercase().collect()
+        } else {
+            char.to_string()
+        };
+        // The cursor does not advance past the end of the line
+        let next = if char == '\n' {
+            char_index
+        } else {
+            char_index + toggled.chars().count()
+        };
+        let edit = (toggled != char.to_string()).then(|| {
+            Action::Edit(Edit::new(
+                &self.rope,
+                (char_index..char_index + 1).into(),
+                Rope::from_str(&toggled),
+            ))
+        });
+        let select = Action::Select(
+            selection
+                .clone()
+                .set_initial_range(None)
+                .set_range((next..next + 1).into()),
+        );
+        Some(ActionGroup::new(edit.into_iter().chain([select]).collect()))
+    }
+
+    /// Returns the content where each selection of `selection_set` is enclosed in `[` and `]`,
+    /// and each empty selection is marked by `|`, e.g. `[hello] |world`,
+    /// so that selection states can be asserted at a glance.
+    #[cfg(test)]
+    pub(crate) fn debug_render_with_selections(&self, selection_set: &SelectionSet) -> String {
+        let markers = selection_set
+            .selections()
+            .iter()
+            .flat_map(|selection| {
+                let range = selection.extended_range();
+                if range.start == range.end {
+                    vec![(range.start, '|')]
+                } else {
+                    vec![(range.start, '['), (range.end, ']')]
+                }
+            })
+            // At the same position, a closing marker precedes an opening one
+            .sorted_by_key(|(char_index, marker)| (*char_index, *marker != ']'))
+            .collect_vec();
+        let mut markers = markers.into_iter().peekable();
+        let mut result = String::new();
+        for (char_index, char) in (0..).map(CharIndex).zip(self.rope.chars()) {
+            while let Some((_, marker)) = markers.next_if(|(index, _)| *index <= char_index) {
+                result.push(marker);
+            }
+            result.push(char);
+        }
+        result.extend(markers.map(|(_, marker)| marker));
+        result
+    }
+
+    /// Moves the lines of `range` into a new file at `path`, returning the file
+    /// for the caller to write, and a transaction that removes those lines from this buffer.
+    ///
+    /// The common indentation of the lines is removed from the new file.
+    /// If `reference` is set, such as an import of the new file, it takes the place
+    /// of the removed lines, with the indentation of the first line.
+    pub(crate) fn extract_to_file(
+        &self,
+        range: CharIndexRange,
+        path: PathBuf,
+        reference: Option<&str>,
+    ) -> anyhow::Result<(NewFile, EditTransaction)> {
+        let last_char = if range.end > range.start {
+            range.end - 1
+        } else {
+            range.end
+        };
+        let start = self.line_to_char(self.char_to_line(range.start)?)?;
+        let last_line = self.char_to_line(last_char)?;
+        let end = if last_line + 1 < self.len_lines() {
+            self.line_to_char(last_line + 1)?
+        } else {
+            CharIndex(self.len_chars())
+        };
+        let lines_range = (start..end).into();
+        let text = self.slice(&lines_range)?.to_string();
+        let common_indentation = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let content = text
+            .lines()
+            .map(|line| line.get(common_indentation..).unwrap_or(line.trim_start()))
+            .map(|line| format!("{line}{}", self.line_ending.as_str()))
+            .collect::<String>();
+        let replacement = reference
+            .map(|reference| {
+                let indentation = self.line_indentation(start).unwrap_or_default();
+                format!("{indentation}{reference}{}", self.line_ending.as_str())
+            })
+            .unwrap_or_default();
+        let edit_transaction =
+            EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(&self.rope, lines_range, Rope::from_str(&replacement)),
+            )])]);
+        Ok((NewFile { path, content }, edit_transaction))
+    }
+
+    /// Returns the lines covered by each selection of `selection_set`, as sorted
+    /// half-open line ranges, where overlapping or adjacent ranges are merged,
+    /// so that batch line operations process every line exactly once.
+    pub(crate) fn selections_to_line_ranges(
+        &self,
+        selection_set: &SelectionSet,
+    ) -> anyhow::Result<Vec<Range<usize>>> {
+        let ranges = selection_set
+            .selections()
+            .iter()
+            .map(|selection| {
+                let range = selection.extended_range();
+                let last_char = if range.end > range.start {
+                    range.end - 1
+                } else {
+                    range.end
+                };
+                Ok(self.char_to_line(range.start)?..self.char_to_line(last_char)? + 1)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(ranges
+            .into_iter()
+            .sorted_by_key(|range| range.start)
+            .coalesce(|previous, current| {
+                if current.start <= previous.end {
+                    Ok(previous.start..previous.end.max(current.end))
+                } else {
+                    Err((previous, current))
+                }
+            })
+            .collect())
+    }
+
+    pub(crate) fn push_selection_set_history(&mut self, selection_set: SelectionSet) {
+        self.selection_set_history.push(selection_set.clone());
+    }
+
+    pub(crate) fn previous_selection_set(&mut self) -> Option<SelectionSet> {
+        self.selection_set_history.undo()
+    }
+
+    pub(crate) fn next_selection_set(&mut self) -> Option<SelectionSet> {
+        self.selection_set_history.redo()
     }
 
     pub(crate) fn line_range_to_byte_range(
@@ -1085,6 +3549,9 @@ impl Buffer {
         &mut self,
         last_visible_line: u16,
     ) -> Result<Option<(SelectionSet, Vec<ki_protocol_types::DiffEdit>)>, anyhow::Error> {
+        if self.read_only {
+            return Err(BufferError::ReadOnly.into());
+        }
         if let Some(history) = self.redo_stack.pop() {
             let edits = history.unnormalized_edits.clone();
 
@@ -1110,6 +3577,9 @@ impl Buffer {
         &mut self,
         last_visible_line: u16,
     ) -> Result<Option<(SelectionSet, Vec<ki_protocol_types::DiffEdit>)>, anyhow::Error> {
+        if self.read_only {
+            return Err(BufferError::ReadOnly.into());
+        }
         if let Some(history) = self.undo_stack.pop() {
             let edits = history.unnormalized_edits.clone();
 
@@ -1142,302 +3612,4231 @@ impl Buffer {
             .get_char(cursor_char_index.0)
             .ok_or_else(|| anyhow::anyhow!("Unable to get char at {cursor_char_index:?}"))
     }
-}
 
-#[cfg(test)]
-mod test_buffer {
-    use std::fs::File;
+    fn node_text(&self, node: &Node) -> anyhow::Result<String> {
+        Ok(self
+            .slice(&self.byte_range_to_char_index_range(&node.byte_range())?)?
+            .to_string())
+    }
 
-    use itertools::Itertools;
-    use shared::canonicalized_path::CanonicalizedPath;
-    use tempfile::tempdir;
+    /// Returns the smallest node that covers the given `char_index`.
+    fn node_at_char(&self, char_index: CharIndex) -> Option<Node> {
+        let byte = self.char_to_byte(char_index).ok()?;
+        self.tree
+            .as_ref()?
+            .root_node()
+            .descendant_for_byte_range(byte, byte)
+    }
 
-    use crate::{
-        grid::{IndexedHighlightGroup, StyleKey},
-        selection::SelectionSet,
-        syntax_highlight::{HighlightedSpan, HighlightedSpans},
-    };
+    /// Expand `range` to cover its whole line(s) if the text surrounding it on
+    /// those lines is whitespace only, so that deleting it does not leave a blank line behind.
+    fn expand_to_whole_lines_if_alone(&self, range: CharIndexRange) -> CharIndexRange {
+        let (Ok(start_line), Ok(end_line)) =
+            (self.char_to_line(range.start), self.char_to_line(range.end))
+        else {
+            return range;
+        };
+        let Ok(line_start) = self.line_to_char(start_line) else {
+            return range;
+        };
+        let line_end = self
+            .line_to_char(end_line + 1)
+            .unwrap_or(CharIndex(self.len_chars()));
+        let is_blank = |range: CharIndexRange| {
+            self.slice(&range)
+                .map(|rope| rope.chars().all(char::is_whitespace))
+                .unwrap_or(false)
+        };
+        if is_blank((line_start..range.start).into()) && is_blank((range.end..line_end).into()) {
+            (line_start..line_end).into()
+        } else {
+            range
+        }
+    }
 
-    use super::Buffer;
+    /// Inline the variable bound by the `let` declaration at (or referred by the identifier at)
+    /// `char_index`, replacing every usage that the locals query of the language
+    /// resolves to it with the bound expression, and removing the declaration.
+    ///
+    /// Returns `None` if the binding is not a simple `let name = expr;`,
+    /// or if any of its usages is reassigned.
+    pub(crate) fn inline_variable(&self, char_index: CharIndex) -> Option<EditTransaction> {
+        let locals = self.locals()?;
+        let node = self.node_at_char(char_index)?;
+        let definition = match locals.definition_of(&node) {
+            Some(definition) => definition,
+            None => std::iter::successors(Some(node), |node| node.parent())
+                .find(|node| node.kind() == "let_declaration")?
+                .child_by_field_name("pattern")?,
+        };
+        let declaration = definition
+            .parent()
+            .filter(|parent| parent.kind() == "let_declaration")?;
+        if declaration.child_by_field_name("pattern")?.id() != definition.id() {
+            return None;
+        }
+        let value = declaration.child_by_field_name("value")?;
+        let name = self.node_text(&definition).ok()?;
+        let expression = self.node_text(&value).ok()?;
+        let usages = locals
+            .references
+            .iter()
+            .map(|(reference, _)| *reference)
+            .filter(|reference| {
+                locals
+                    .definition_of(reference)
+                    .is_some_and(|resolved| resolved.id() == definition.id())
+            })
+            .collect_vec();
 
-    #[test]
-    fn get_parent_lines_1() {
-        let buffer = Buffer::new(
-            shared::language::from_extension("yaml")
-                .unwrap()
-                .tree_sitter_language(),
-            "
-- spongebob
-- who:
-  - lives
-  - in:
-    - a
-    - pineapple
-  - under
-",
+        let is_reassigned = usages.iter().any(|usage| {
+            usage.parent().is_some_and(|parent| {
+                matches!(
+                    parent.kind(),
+                    "assignment_expression" | "compound_assignment_expr"
+                ) && parent
+                    .child_by_field_name("left")
+                    .is_some_and(|left| left.id() == usage.id())
+            })
+        });
+        if is_reassigned {
+            return None;
+        }
+
+        let value_needs_parentheses = matches!(
+            value.kind(),
+            "binary_expression"
+                | "range_expression"
+                | "type_cast_expression"
+                | "closure_expression"
         );
-        let actual = buffer
-            .get_parent_lines(6)
-            .unwrap()
-            .into_iter()
-            .map(|line| line.content)
+        let replacements = usages
+            .iter()
+            .map(|usage| -> anyhow::Result<ActionGroup> {
+                let usage_needs_parentheses = value_needs_parentheses
+                    && usage.parent().is_some_and(|parent| {
+                        matches!(
+                            parent.kind(),
+                            "binary_expression"
+                                | "unary_expression"
+                                | "field_expression"
+                                | "call_expression"
+                                | "index_expression"
+                                | "reference_expression"
+                                | "type_cast_expression"
+                                | "try_expression"
+                        )
+                    });
+                let is_shorthand = usage
+                    .parent()
+                    .is_some_and(|parent| parent.kind() == "shorthand_field_initializer");
+                let new = if is_shorthand {
+                    // `Foo { x }` becomes `Foo { x: expression }`
+                    format!("{name}: {expression}")
+                } else if usage_needs_parentheses {
+                    format!("({expression})")
+                } else {
+                    expression.clone()
+                };
+                let range = self.byte_range_to_char_index_range(&usage.byte_range())?;
+                Ok(ActionGroup::new(
+                    [Action::Edit(Edit::new(&self.rope, range, new.into()))].to_vec(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .ok()?;
+
+        let declaration_range = self.expand_to_whole_lines_if_alone(
+            self.byte_range_to_char_index_range(&declaration.byte_range())
+                .ok()?,
+        );
+        let removal = ActionGroup::new(
+            [Action::Edit(Edit::new(
+                &self.rope,
+                declaration_range,
+                Rope::new(),
+            ))]
+            .to_vec(),
+        );
+
+        Some(EditTransaction::from_action_groups(
+            std::iter::once(removal).chain(replacements).collect_vec(),
+        ))
+    }
+
+    /// Surround `range` with `function_name(` and `)`, for example, `x` becomes `Some(x)`.
+    /// The cursor is placed after the closing parenthesis.
+    pub(crate) fn wrap_in_call(
+        &self,
+        range: CharIndexRange,
+        function_name: &str,
+    ) -> anyhow::Result<EditTransaction> {
+        let new: Rope = format!("{function_name}({})", self.slice(&range)?).into();
+        let cursor = range.start + new.len_chars();
+        Ok(EditTransaction::from_action_groups(
+            [ActionGroup::new(
+                [
+                    Action::Edit(Edit::new(&self.rope, range, new)),
+                    Action::Select(Selection::new((cursor..cursor).into())),
+                ]
+                .to_vec(),
+            )]
+            .to_vec(),
+        ))
+    }
+
+    /// Generate a function stub from the call expression at `char_index`,
+    /// for example, `foo(a, b)` produces `fn foo(a: TODO, b: TODO) {..}`.
+    ///
+    /// Argument names are taken from the call site if they are plain identifiers,
+    /// otherwise they are named by their position.
+    pub(crate) fn stub_from_call(&self, char_index: CharIndex) -> Option<String> {
+        const TYPE_PLACEHOLDER: &str = "TODO";
+        let call = self.nearest_ancestor_of_kind(char_index, &["call_expression"])?;
+        let function = call.child_by_field_name("function")?;
+        let name_node = match function.kind() {
+            "scoped_identifier" => function.child_by_field_name("name")?,
+            "field_expression" => function.child_by_field_name("field")?,
+            _ => function,
+        };
+        let name = self.node_text(&name_node).ok()?;
+        let arguments = call.child_by_field_name("arguments")?;
+        let mut cursor = arguments.walk();
+        let parameters = arguments
+            .named_children(&mut cursor)
+            .filter(|argument| !argument.is_extra())
+            .enumerate()
+            .map(|(index, argument)| {
+                let name = match argument.kind() {
+                    "identifier" => self.node_text(&argument).ok(),
+                    "field_expression" => argument
+                        .child_by_field_name("field")
+                        .and_then(|field| self.node_text(&field).ok()),
+                    _ => None,
+                }
+                .unwrap_or_else(|| format!("arg{index}"));
+                format!("{name}: {TYPE_PLACEHOLDER}")
+            })
+            .join(", ");
+        Some(format!("fn {name}({parameters}) {{\n    todo!()\n}}"))
+    }
+
+    /// Returns a transaction that inserts the stub generated by `stub_from_call`
+    /// after the top-level item enclosing `char_index`, separated by a blank line.
+    pub(crate) fn insert_function_stub(&self, char_index: CharIndex) -> Option<EditTransaction> {
+        let stub = self.stub_from_call(char_index)?;
+        let item = std::iter::successors(self.node_at_char(char_index), |node| node.parent())
+            .take_while(|node| node.parent().is_some())
+            .last()?;
+        let item_end = self.byte_to_char(item.end_byte()).ok()?;
+        Some(EditTransaction::from_action_groups(
+            [ActionGroup::new(
+                [Action::Edit(Edit::new(
+                    &self.rope,
+                    (item_end..item_end).into(),
+                    format!("\n\n{stub}").into(),
+                ))]
+                .to_vec(),
+            )]
+            .to_vec(),
+        ))
+    }
+
+    /// Store the response of `textDocument/selectionRange`,
+    /// to be walked by `expand_selection_semantic` and `shrink_selection_semantic`.
+    pub(crate) fn set_selection_ranges(
+        &mut self,
+        selection_ranges: Vec<lsp_types::SelectionRange>,
+    ) {
+        self.selection_ranges = selection_ranges
+            .iter()
+            .filter_map(|selection_range| {
+                std::iter::successors(Some(selection_range), |selection_range| {
+                    selection_range.parent.as_deref()
+                })
+                .map(|selection_range| {
+                    self.position_range_to_char_index_range(
+                        &(Position::from(selection_range.range.start)
+                            ..Position::from(selection_range.range.end)),
+                    )
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                .ok()
+            })
+            .collect_vec();
+    }
+
+    pub(crate) fn has_selection_ranges(&self) -> bool {
+        !self.selection_ranges.is_empty()
+    }
+
+    /// Returns the smallest selection range that strictly contains `range`.
+    ///
+    /// Falls back to the syntax tree if no selection ranges were provided by the LSP.
+    pub(crate) fn expand_selection_semantic(
+        &self,
+        range: CharIndexRange,
+    ) -> Option<CharIndexRange> {
+        if self.selection_ranges.is_empty() {
+            return self.grow_selection(range);
+        }
+        self.selection_ranges
+            .iter()
+            .flatten()
+            .filter(|candidate| candidate.is_supserset_of(&range) && **candidate != range)
+            .min_by_key(|candidate| candidate.len())
+            .copied()
+    }
+
+    /// Returns the largest selection range that is strictly contained by `range`.
+    ///
+    /// Falls back to the syntax tree if no selection ranges were provided by the LSP.
+    pub(crate) fn shrink_selection_semantic(
+        &self,
+        range: CharIndexRange,
+    ) -> Option<CharIndexRange> {
+        if self.selection_ranges.is_empty() {
+            return self.shrink_selection(range);
+        }
+        self.selection_ranges
+            .iter()
+            .flatten()
+            .filter(|candidate| range.is_supserset_of(candidate) && **candidate != range)
+            .max_by_key(|candidate| candidate.len())
+            .copied()
+    }
+
+    /// Returns the range of the smallest syntax node that strictly contains `range`.
+    fn grow_selection(&self, range: CharIndexRange) -> Option<CharIndexRange> {
+        let byte_range = self.char_index_range_to_byte_range(range).ok()?;
+        let node = self
+            .tree
+            .as_ref()?
+            .root_node()
+            .descendant_for_byte_range(byte_range.start, byte_range.end)?;
+        std::iter::successors(Some(node), |node| node.parent())
+            .map(|node| node.byte_range())
+            .find(|node_range| node_range != &byte_range)
+            .and_then(|node_range| self.byte_range_to_char_index_range(&node_range).ok())
+    }
+
+    /// Returns the range of the largest syntax node that is strictly contained by `range`.
+    fn shrink_selection(&self, range: CharIndexRange) -> Option<CharIndexRange> {
+        let byte_range = self.char_index_range_to_byte_range(range).ok()?;
+        let node = self
+            .tree
+            .as_ref()?
+            .root_node()
+            .descendant_for_byte_range(byte_range.start, byte_range.end)?;
+        traverse(node.walk(), Order::Pre)
+            .find(|node| {
+                node.byte_range() != byte_range
+                    && byte_range.start <= node.start_byte()
+                    && node.end_byte() <= byte_range.end
+            })
+            .and_then(|node| self.byte_range_to_char_index_range(&node.byte_range()).ok())
+    }
+
+    /// Returns the ranges of all syntax nodes whose kind is one of `kinds`,
+    /// for example, `&["string_literal"]` for selecting all strings.
+    pub(crate) fn ranges_of_kind(&self, kinds: &[&str]) -> Vec<CharIndexRange> {
+        let Some(tree) = self.tree.as_ref() else {
+            return Vec::new();
+        };
+        traverse(tree.walk(), Order::Pre)
+            .filter(|node| kinds.contains(&node.kind()))
+            .filter_map(|node| self.byte_range_to_char_index_range(&node.byte_range()).ok())
+            .collect_vec()
+    }
+
+    /// Exchange the text of the node of `selection` with its next named sibling,
+    /// for example, swapping the arguments of `f(a, b)`.
+    /// The separators between the siblings are left untouched.
+    ///
+    /// The returned transaction selects the node at its new position.
+    pub(crate) fn swap_with_next_sibling(&self, selection: &Selection) -> Option<EditTransaction> {
+        let node = self.get_current_node(selection, false).ok()??;
+        let next = node.next_named_sibling()?;
+        let node_range = self
+            .byte_range_to_char_index_range(&node.byte_range())
+            .ok()?;
+        let next_range = self
+            .byte_range_to_char_index_range(&next.byte_range())
+            .ok()?;
+        let node_text = self.slice(&node_range).ok()?;
+        let next_text = self.slice(&next_range).ok()?;
+        let node_len_chars = node_text.len_chars();
+        Some(EditTransaction::from_action_groups(
+            [
+                ActionGroup::new(
+                    [Action::Edit(Edit::new(&self.rope, node_range, next_text))].to_vec(),
+                ),
+                ActionGroup::new(
+                    [
+                        Action::Edit(Edit::new(&self.rope, next_range, node_text)),
+                        Action::Select(selection.clone().set_range(
+                            (next_range.start..next_range.start + node_len_chars).into(),
+                        )),
+                    ]
+                    .to_vec(),
+                ),
+            ]
+            .to_vec(),
+        ))
+    }
+
+    /// Swap the contents of two selections, or rotate the contents forward
+    /// (in document order) if there are more than two selections.
+    ///
+    /// Returns `None` if there are fewer than two selections.
+    pub(crate) fn transpose_selections(
+        &self,
+        selection_set: SelectionSet,
+    ) -> Option<EditTransaction> {
+        if selection_set.len() < 2 {
+            return None;
+        }
+        self.rotate_selection_contents(selection_set, Direction::End)
+            .ok()
+    }
+
+    /// Cyclically shift the contents of the selections (in document order) as one edit.
+    /// Towards `Direction::End`, the text of each selection moves to the next selection,
+    /// and the text of the last selection moves to the first selection.
+    pub(crate) fn rotate_selection_contents(
+        &self,
+        selection_set: SelectionSet,
+        direction: Direction,
+    ) -> anyhow::Result<EditTransaction> {
+        let selections = selection_set
+            .selections()
+            .iter()
+            .sorted_by_key(|selection| selection.extended_range())
+            .collect_vec();
+        let texts = selections
+            .iter()
+            .map(|selection| self.slice(&selection.extended_range()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let count = texts.len();
+        Ok(EditTransaction::from_action_groups(
+            selections
+                .iter()
+                .enumerate()
+                .map(|(index, selection)| {
+                    let range = selection.extended_range();
+                    let source_index = match direction {
+                        Direction::End => (index + count - 1) % count,
+                        Direction::Start => (index + 1) % count,
+                    };
+                    let new = texts[source_index].clone();
+                    let new_len_chars = new.len_chars();
+                    ActionGroup::new(
+                        [
+                            Action::Edit(Edit::new(&self.rope, range, new)),
+                            Action::Select(
+                                (*selection)
+                                    .clone()
+                                    .set_initial_range(None)
+                                    .set_range((range.start..range.start + new_len_chars).into()),
+                            ),
+                        ]
+                        .to_vec(),
+                    )
+                })
+                .collect_vec(),
+        ))
+    }
+
+    /// Insert incrementing counters (`start`, `start + step`, ...) at the start of each selection
+    /// in document order.
+    ///
+    /// Every `{}` in `format` is replaced by the counter, `{:N}` pads it with spaces
+    /// to a width of `N`, and `{:0N}` pads it with zeros, for example, `"{:02}. "`.
+    ///
+    /// Returns an error if a width exceeds `MAX_COUNTER_WIDTH`, or if a counter overflows.
+    pub(crate) fn insert_counter(
+        &self,
+        selection_set: SelectionSet,
+        start: i64,
+        step: i64,
+        format: &str,
+    ) -> anyhow::Result<EditTransaction> {
+        let placeholder = lazy_regex::regex!(r"\{(?::(0)?(\d+))?\}");
+        if let Some(width) = placeholder
+            .captures_iter(format)
+            .filter_map(|captures| captures.get(2))
+            .find(|width| {
+                width
+                    .as_str()
+                    .parse::<usize>()
+                    .map_or(true, |width| width > MAX_COUNTER_WIDTH)
+            })
+        {
+            return Err(anyhow::anyhow!(
+                "The counter width {} exceeds the maximum of {MAX_COUNTER_WIDTH}",
+                width.as_str()
+            ));
+        }
+        let action_groups = selection_set
+            .selections()
+            .iter()
+            .sorted_by_key(|selection| selection.extended_range())
+            .enumerate()
+            .map(|(index, selection)| {
+                let value = i64::try_from(index)
+                    .ok()
+                    .and_then(|index| step.checked_mul(index))
+                    .and_then(|offset| start.checked_add(offset))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "The counter overflows when counting from {start} by {step}"
+                        )
+                    })?;
+                let counter = placeholder.replace_all(format, |captures: &regex::Captures| {
+                    let width = captures
+                        .get(2)
+                        .and_then(|width| width.as_str().parse().ok())
+                        .unwrap_or(0);
+                    if captures.get(1).is_some() {
+                        format!("{value:0width$}")
+                    } else {
+                        format!("{value:width$}")
+                    }
+                });
+                let new: Rope = counter.as_ref().into();
+                let start = selection.extended_range().start;
+                let end = start + new.len_chars();
+                Ok(ActionGroup::new(
+                    [
+                        Action::Edit(Edit::new(&self.rope, (start..start).into(), new)),
+                        Action::Select(
+                            selection
+                                .clone()
+                                .set_initial_range(None)
+                                .set_range((start..end).into()),
+                        ),
+                    ]
+                    .to_vec(),
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(EditTransaction::from_action_groups(action_groups))
+    }
+
+    /// Keep only the selections whose text matches `regex`,
+    /// or, if `invert` is true, only those that do not match, like Vim's `:g` and `:v`.
+    ///
+    /// Returns `None` if no selection remains.
+    pub(crate) fn retain_selections_matching(
+        &self,
+        selection_set: SelectionSet,
+        regex: &Regex,
+        invert: bool,
+    ) -> Option<SelectionSet> {
+        let selections = selection_set
+            .selections()
+            .iter()
+            .filter(|selection| {
+                self.slice(&selection.extended_range())
+                    .map(|text| regex.is_match(&text.to_string()) != invert)
+                    .unwrap_or(false)
+            })
+            .cloned()
+            .collect_vec();
+        Some(selection_set.set_selections(NonEmpty::from_vec(selections)?))
+    }
+
+    /// Split each selection into the fields separated by the matches of `separator`,
+    /// for example, the cells of a CSV line.
+    ///
+    /// Empty fields are dropped unless `keep_empty` is true.
+    /// Returns `None` if no selection remains.
+    pub(crate) fn split_selections(
+        &self,
+        selection_set: SelectionSet,
+        separator: &Regex,
+        keep_empty: bool,
+    ) -> Option<SelectionSet> {
+        let selections = selection_set
+            .selections()
+            .iter()
+            .flat_map(|selection| {
+                let range = selection.extended_range();
+                let text = self
+                    .slice(&range)
+                    .map(|text| text.to_string())
+                    .unwrap_or_default();
+                let char_index = |byte: usize| range.start + text[..byte].chars().count();
+                let mut field_start = 0;
+                let mut fields = Vec::new();
+                for separator in separator.find_iter(&text) {
+                    fields.push(field_start..separator.start());
+                    field_start = separator.end();
+                }
+                fields.push(field_start..text.len());
+                fields
+                    .into_iter()
+                    .filter(|field| keep_empty || !field.is_empty())
+                    .map(|field| {
+                        selection
+                            .clone()
+                            .set_initial_range(None)
+                            .set_range((char_index(field.start)..char_index(field.end)).into())
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+        Some(selection_set.set_selections(NonEmpty::from_vec(selections)?))
+    }
+
+    /// Returns the line-level differences between this buffer and `other`,
+    /// where adjacent deletions and insertions are merged into one hunk.
+    pub(crate) fn diff_against(&self, other: &Buffer) -> Vec<DiffHunk> {
+        let (content, other_content) = (self.content(), other.content());
+        similar::TextDiff::from_lines(&content, &other_content)
+            .ops()
+            .iter()
+            .filter(|op| op.tag() != similar::DiffTag::Equal)
+            .map(|op| (op.old_range(), op.new_range()))
+            .coalesce(|(lines, other_lines), (next_lines, next_other_lines)| {
+                if lines.end == next_lines.start && other_lines.end == next_other_lines.start {
+                    Ok((
+                        lines.start..next_lines.end,
+                        other_lines.start..next_other_lines.end,
+                    ))
+                } else {
+                    Err(((lines, other_lines), (next_lines, next_other_lines)))
+                }
+            })
+            .filter_map(|(lines, other_lines)| {
+                Some(DiffHunk {
+                    range: self.line_range_to_char_index_range(lines).ok()?,
+                    other_range: other.line_range_to_char_index_range(other_lines).ok()?,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the line-level differences between this buffer and `base`
+    /// (e.g. the content of the file at git HEAD), where `DiffHunk::other_range` refers to `base`.
+    pub(crate) fn diff_against_content(&self, base: &str) -> Vec<DiffHunk> {
+        self.diff_against(&Buffer::new(None, base))
+    }
+
+    /// Returns a transaction that replaces the lines of `hunk` with their
+    /// counterpart in `base`, leaving the other hunks untouched.
+    ///
+    /// `hunk` should be obtained from `diff_against_content` with the same `base`.
+    pub(crate) fn revert_hunk(
+        &self,
+        hunk: &DiffHunk,
+        base: &str,
+    ) -> anyhow::Result<EditTransaction> {
+        let base = Rope::from_str(base);
+        let other_range = hunk.other_range;
+        let replacement = base
+            .get_slice(other_range.start.0..other_range.end.0)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Buffer::revert_hunk: {other_range:?} is out of bounds of the base content"
+                )
+            })?;
+        Ok(EditTransaction::from_action_groups(vec![ActionGroup::new(
+            vec![Action::Edit(Edit::new(
+                &self.rope,
+                hunk.range,
+                replacement.into(),
+            ))],
+        )]))
+    }
+
+    /// Returns the word-level changes between the content saved at `path` and the current content.
+    /// This is more precise than a line-level diff, as only the changed words within a modified line are reported.
+    pub(crate) fn word_diff(&self) -> anyhow::Result<Vec<WordChange>> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let saved = path.read()?;
+        let current = self.content();
+        let diff = similar::TextDiff::from_unicode_words(saved.as_str(), current.as_str());
+        let mut word_changes = Vec::new();
+        let mut pending: Option<WordChange> = None;
+        let mut char_index = CharIndex(0);
+        for change in diff.iter_all_changes() {
+            let value = change.value();
+            match change.tag() {
+                similar::ChangeTag::Equal => {
+                    word_changes.extend(pending.take());
+                    char_index = char_index + value.chars().count();
+                }
+                similar::ChangeTag::Delete => pending
+                    .get_or_insert_with(|| WordChange {
+                        range: (char_index..char_index).into(),
+                        old: String::new(),
+                        new: String::new(),
+                    })
+                    .old
+                    .push_str(value),
+                similar::ChangeTag::Insert => {
+                    let word_change = pending.get_or_insert_with(|| WordChange {
+                        range: (char_index..char_index).into(),
+                        old: String::new(),
+                        new: String::new(),
+                    });
+                    word_change.new.push_str(value);
+                    char_index = char_index + value.chars().count();
+                    word_change.range.end = char_index;
+                }
+            }
+        }
+        word_changes.extend(pending);
+        Ok(word_changes)
+    }
+
+    /// Determine whether `char_index` is within a string, a comment, or code,
+    /// based on the kinds of its enclosing syntax nodes.
+    ///
+    /// Always returns `SyntaxContext::Code` if there's no syntax tree.
+    pub(crate) fn syntax_context_at(&self, char_index: CharIndex) -> SyntaxContext {
+        std::iter::successors(self.node_at_char(char_index), |node| node.parent())
+            .find_map(|node| {
+                let kind = node.kind();
+                if kind.contains("comment") {
+                    Some(SyntaxContext::Comment)
+                } else if kind.contains("string") {
+                    Some(SyntaxContext::String)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(SyntaxContext::Code)
+    }
+
+    /// Returns a transaction that removes `terminator` (e.g. `;` or `,`) from the end
+    /// of the code of `line`, that is, before any trailing comment, or appends it if absent.
+    ///
+    /// A terminator is not appended after an opening bracket or another terminator,
+    /// within a multiline string, or, for `;`, within parentheses or brackets spanning
+    /// multiple lines, such as the arguments of a call.
+    pub(crate) fn toggle_line_terminator(
+        &self,
+        line: usize,
+        terminator: char,
+    ) -> anyhow::Result<EditTransaction> {
+        let line_start = self.line_to_char(line)?;
+        let content = self
+            .get_line_by_line_index(line)
+            .ok_or_else(|| anyhow::anyhow!("Buffer::toggle_line_terminator: no line {line}"))?
+            .to_string();
+        let content = content.trim_end_matches(['\r', '\n']);
+        let Some((offset, last_char)) = content
+            .chars()
+            .enumerate()
+            .collect_vec()
+            .into_iter()
+            .rev()
+            .find(|(offset, char)| {
+                !char.is_whitespace()
+                    && self.syntax_context_at(line_start + *offset) != SyntaxContext::Comment
+            })
+        else {
+            return Ok(EditTransaction::from_action_groups(Vec::new()));
+        };
+        let last_char_index = line_start + offset;
+        if last_char == terminator {
+            return Ok(EditTransaction::from_action_groups(vec![ActionGroup::new(
+                vec![Action::Edit(Edit::new(
+                    &self.rope,
+                    (last_char_index..last_char_index + 1).into(),
+                    Rope::new(),
+                ))],
+            )]));
+        }
+        // The innermost construct that continues past this line
+        let line_end_byte = self.char_to_byte(line_start + content.chars().count())?;
+        let enclosing =
+            std::iter::successors(self.node_at_char(last_char_index), |node| node.parent())
+                .find(|node| node.end_byte() > line_end_byte);
+        let is_inappropriate = enclosing.is_some_and(|node| {
+            node.kind().contains("string")
+                || (terminator == ';'
+                    && node
+                        .child(0)
+                        .is_some_and(|child| matches!(child.kind(), "(" | "[")))
+        });
+        if matches!(last_char, '(' | '[' | '{' | ',' | ';') || is_inappropriate {
+            return Ok(EditTransaction::from_action_groups(Vec::new()));
+        }
+        Ok(EditTransaction::from_action_groups(vec![ActionGroup::new(
+            vec![Action::Edit(Edit::new(
+                &self.rope,
+                (last_char_index + 1..last_char_index + 1).into(),
+                Rope::from_str(&terminator.to_string()),
+            ))],
+        )]))
+    }
+
+    /// Returns the comment leader of `line` including its indentation, e.g. `    // `,
+    /// and whether the line holds nothing but that leader.
+    fn comment_leader(&self, line: usize) -> Option<(String, bool)> {
+        let content = self.get_line_by_line_index(line)?.to_string();
+        let leader = lazy_regex::regex!(r"^[ \t]*(?://[/!]?|#|--|;+)[ \t]?")
+            .find(&content)?
+            .as_str()
+            .to_string();
+        // Without this check, a Rust attribute would be mistaken for a `#` comment
+        let leader_start = self.line_to_char(line).ok()? + leader.trim_end().chars().count() - 1;
+        if self.tree.is_some() && self.syntax_context_at(leader_start) == SyntaxContext::Code {
+            return None;
+        }
+        let is_blank = content[leader.len()..].trim().is_empty();
+        Some((leader.trim_end().to_string(), is_blank))
+    }
+
+    /// Returns the range of the comment paragraph at `char_index`, which spans
+    /// the consecutive line comments sharing the same leader (indentation included),
+    /// so that reflowing or joining operates on the whole comment.
+    ///
+    /// A blank comment line, made of only the leader, terminates the paragraph.
+    /// The range starts at the first line and ends before the line break of the last line.
+    pub(crate) fn comment_paragraph_range(&self, char_index: CharIndex) -> Option<CharIndexRange> {
+        let line = self.char_to_line(char_index).ok()?;
+        let (leader, is_blank) = self.comment_leader(line)?;
+        if is_blank {
+            return None;
+        }
+        let is_paragraph_line = |line: usize| {
+            self.comment_leader(line)
+                .is_some_and(|(other_leader, is_blank)| other_leader == leader && !is_blank)
+        };
+        let first_line = (0..line)
+            .rev()
+            .take_while(|line| is_paragraph_line(*line))
+            .last()
+            .unwrap_or(line);
+        let last_line = (line + 1..self.len_lines())
+            .take_while(|line| is_paragraph_line(*line))
+            .last()
+            .unwrap_or(line);
+        let start = self.line_to_char(first_line).ok()?;
+        let last_line_content = self.get_line_by_line_index(last_line)?.to_string();
+        let end = self.line_to_char(last_line).ok()?
+            + last_line_content
+                .trim_end_matches(['\r', '\n'])
+                .chars()
+                .count();
+        Some((start..end).into())
+    }
+
+    /// Returns the spans of trailing whitespaces and of leading indentations that mix tabs and spaces.
+    /// Whitespaces within strings are not reported.
+    pub(crate) fn whitespace_issue_spans(&self) -> Vec<(CharIndexRange, WhitespaceIssue)> {
+        self.rope
+            .lines()
+            .enumerate()
+            .filter_map(|(line_index, line)| {
+                let line_start = self.line_to_char(line_index).ok()?;
+                let content = line.to_string();
+                let content = content.trim_end_matches(['\n', '\r']);
+                let len_chars = content.chars().count();
+                let leading = content
+                    .chars()
+                    .take_while(|char| char.is_whitespace())
+                    .collect::<String>();
+                let trailing_len_chars = content
+                    .chars()
+                    .rev()
+                    .take_while(|char| char.is_whitespace())
+                    .count();
+                let mixed_indentation: Option<(CharIndexRange, _)> =
+                    (leading.contains(' ') && leading.contains('\t')).then(|| {
+                        (
+                            (line_start..line_start + leading.chars().count()).into(),
+                            WhitespaceIssue::MixedIndentation,
+                        )
+                    });
+                let trailing_whitespace: Option<(CharIndexRange, _)> = (trailing_len_chars > 0)
+                    .then(|| {
+                        (
+                            (line_start + (len_chars - trailing_len_chars)..line_start + len_chars)
+                                .into(),
+                            WhitespaceIssue::TrailingWhitespace,
+                        )
+                    });
+                Some([mixed_indentation, trailing_whitespace])
+            })
+            .flatten()
+            .flatten()
+            .filter(|(range, _)| self.syntax_context_at(range.start) != SyntaxContext::String)
             .collect_vec()
+    }
+
+    /// Like Vim's `f` (`inclusive`) and `t` (not `inclusive`),
+    /// returns the index of the next `target` after `char_index` within the same line,
+    /// or the index right before it if not `inclusive`.
+    pub(crate) fn find_char_forward(
+        &self,
+        char_index: CharIndex,
+        target: char,
+        inclusive: bool,
+    ) -> Option<CharIndex> {
+        let line_range = self.get_line_range_by_char_index(char_index).ok()?;
+        (char_index.0 + 1..line_range.end.0)
+            .map(CharIndex)
+            .take_while(|index| !matches!(self.rope.get_char(index.0), Some('\n' | '\r')))
+            .find(|index| self.rope.get_char(index.0) == Some(target))
+            .map(|index| if inclusive { index } else { index - 1 })
+    }
+
+    /// Like Vim's `F` (`inclusive`) and `T` (not `inclusive`),
+    /// returns the index of the previous `target` before `char_index` within the same line,
+    /// or the index right after it if not `inclusive`.
+    pub(crate) fn find_char_backward(
+        &self,
+        char_index: CharIndex,
+        target: char,
+        inclusive: bool,
+    ) -> Option<CharIndex> {
+        let line_range = self.get_line_range_by_char_index(char_index).ok()?;
+        (line_range.start.0..char_index.0)
+            .rev()
+            .map(CharIndex)
+            .find(|index| self.rope.get_char(index.0) == Some(target))
+            .map(|index| if inclusive { index } else { index + 1 })
+    }
+
+    /// Returns the range between the quotes of the string enclosing `char_index`.
+    pub(crate) fn string_content_range(&self, char_index: CharIndex) -> Option<CharIndexRange> {
+        let node = self.enclosing_string_node(char_index)?;
+        let opening = node.child(0)?;
+        let closing = node.child(node.child_count().checked_sub(1)?)?;
+        let is_delimiter =
+            |node: &Node| !node.kind().contains("content") && !node.kind().contains("escape");
+        if opening == closing || !is_delimiter(&opening) || !is_delimiter(&closing) {
+            return None;
+        }
+        self.byte_range_to_char_index_range(&(opening.end_byte()..closing.start_byte()))
+            .ok()
+    }
+
+    /// If `char_index` is within a string whose content before `char_index` looks like a path
+    /// (i.e. it contains a slash), returns the directory of that path and the partially typed name.
+    ///
+    /// Relative paths are resolved against the directory of this buffer.
+    pub(crate) fn path_completion_query(
+        &self,
+        char_index: CharIndex,
+    ) -> Option<(std::path::PathBuf, String)> {
+        if self.syntax_context_at(char_index) != SyntaxContext::String {
+            return None;
+        }
+        let typed = self
+            .string_content_range(char_index)
+            .filter(|range| range.start <= char_index)
+            .and_then(|range| self.slice(&(range.start..char_index).into()).ok())?
+            .to_string();
+        let (directory, partial_name) = typed.rsplit_once('/')?;
+        let directory = std::path::Path::new(if directory.is_empty() { "/" } else { directory });
+        let directory = match self
+            .path
+            .as_ref()
+            .and_then(|path| path.parent().ok().flatten())
+        {
+            Some(parent) if directory.is_relative() => parent.to_path_buf().join(directory),
+            _ => directory.to_path_buf(),
+        };
+        Some((directory, partial_name.to_string()))
+    }
+
+    /// Returns the value of the string enclosing `char_index`, with its escape sequences resolved.
+    pub(crate) fn unescaped_string_at(&self, char_index: CharIndex) -> Option<String> {
+        let range = self.string_content_range(char_index)?;
+        Some(unescape(&self.slice(&range).ok()?.to_string()))
+    }
+
+    /// Change the quotes of the string literal enclosing `char_index` to `style`,
+    /// escaping or unescaping the interior quotes accordingly.
+    ///
+    /// Returns `None` if the content cannot be represented in `style`,
+    /// for example, a template string with interpolations.
+    pub(crate) fn convert_quotes(
+        &self,
+        char_index: CharIndex,
+        style: QuoteStyle,
+    ) -> Option<EditTransaction> {
+        let node = self.enclosing_string_node(char_index)?;
+        let opening = node.child(0)?;
+        let current_style = QuoteStyle::from_delimiter(&self.node_text(&opening).ok()?)?;
+        let has_interpolation = traverse(node.walk(), Order::Pre).any(|node| {
+            node.kind().contains("substitution") || node.kind().contains("interpolation")
+        });
+        if has_interpolation {
+            return None;
+        }
+        let content = self
+            .slice(&self.string_content_range(char_index)?)
+            .ok()?
+            .to_string();
+        if (style == QuoteStyle::Backtick && content.contains("${"))
+            || (style != QuoteStyle::Backtick && content.contains('\n'))
+        {
+            return None;
+        }
+        let (old_quote, new_quote) = (current_style.delimiter(), style.delimiter());
+        let mut new = String::from(new_quote);
+        let mut chars = content.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => match chars.next() {
+                    Some(escaped) if escaped == old_quote && escaped != new_quote => {
+                        new.push(escaped)
+                    }
+                    Some(escaped) => {
+                        new.push('\\');
+                        new.push(escaped)
+                    }
+                    None => new.push('\\'),
+                },
+                char if char == new_quote => {
+                    new.push('\\');
+                    new.push(char)
+                }
+                char => new.push(char),
+            }
+        }
+        new.push(new_quote);
+        let range = self
+            .byte_range_to_char_index_range(&node.byte_range())
+            .ok()?;
+        let new: Rope = new.into();
+        let selection = Selection::new((range.start..range.start + new.len_chars()).into());
+        Some(EditTransaction::from_action_groups(
+            [ActionGroup::new(
+                [
+                    Action::Edit(Edit::new(&self.rope, range, new)),
+                    Action::Select(selection),
+                ]
+                .to_vec(),
+            )]
+            .to_vec(),
+        ))
+    }
+
+    /// Returns the range of the smallest statement enclosing `char_index`,
+    /// for example, a `let` declaration, or an `if` block.
+    pub(crate) fn statement_range(&self, char_index: CharIndex) -> Option<CharIndexRange> {
+        let statement = self.enclosing_statement(char_index)?;
+        self.byte_range_to_char_index_range(&statement.byte_range())
+            .ok()
+    }
+
+    /// Move the statement enclosing `selection` to just after its containing block,
+    /// re-indenting it to the indentation of the block's statement.
+    pub(crate) fn move_node_out_of_block(&self, selection: &Selection) -> Option<EditTransaction> {
+        let statement = self.enclosing_statement(selection.range().start)?;
+        let block = statement
+            .parent()
+            .filter(|parent| parent.kind().contains("block") || parent.kind().contains("body"))?;
+        let outer = std::iter::successors(block.parent(), |node| node.parent())
+            .find(is_statement)
+            .unwrap_or(block);
+        let statement_range = self
+            .byte_range_to_char_index_range(&statement.byte_range())
+            .ok()?;
+        let outer_end = self.byte_to_char(outer.end_byte()).ok()?;
+        let statement_indent = self.line_indentation(statement_range.start)?;
+        let outer_indent = self.line_indentation(self.byte_to_char(outer.start_byte()).ok()?)?;
+        let text = self
+            .node_text(&statement)
+            .ok()?
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                if index == 0 {
+                    line.to_string()
+                } else {
+                    format!(
+                        "{outer_indent}{}",
+                        line.strip_prefix(statement_indent.as_str()).unwrap_or(line)
+                    )
+                }
+            })
             .join("\n");
-        let expected = "
-- who:
-  - in:
-"
-        .trim()
-        .to_string();
-        pretty_assertions::assert_eq!(actual, expected)
+        let moved_start = outer_end + 1 + outer_indent.chars().count();
+        let moved: Rope = format!("\n{outer_indent}{text}").into();
+        let moved_end = outer_end + moved.len_chars();
+        Some(EditTransaction::from_action_groups(
+            [
+                ActionGroup::new(
+                    [Action::Edit(Edit::new(
+                        &self.rope,
+                        self.expand_to_whole_lines_if_alone(statement_range),
+                        Rope::new(),
+                    ))]
+                    .to_vec(),
+                ),
+                ActionGroup::new(
+                    [
+                        Action::Edit(Edit::new(&self.rope, (outer_end..outer_end).into(), moved)),
+                        Action::Select(Selection::new((moved_start..moved_end).into())),
+                    ]
+                    .to_vec(),
+                ),
+            ]
+            .to_vec(),
+        ))
+    }
+
+    /// Reorders the entries of the innermost object/map node (JSON object or YAML mapping)
+    /// enclosing `selection` alphabetically by their keys.
+    ///
+    /// Only the entries are moved, so the separators, comments and whitespaces between them
+    /// stay in place.
+    ///
+    /// Returns `None` if `selection` is not in an object/map node.
+    pub(crate) fn sort_object_keys(&self, selection: &Selection) -> Option<EditTransaction> {
+        let object = self.nearest_ancestor_of_kind(
+            selection.range().start,
+            &["object", "block_mapping", "flow_mapping"],
+        )?;
+        let entries = object
+            .named_children(&mut object.walk())
+            .filter(|node| ["pair", "block_mapping_pair", "flow_pair"].contains(&node.kind()))
+            .map(|entry| {
+                let key = entry
+                    .child_by_field_name("key")
+                    .and_then(|key| self.node_text(&key).ok())
+                    .unwrap_or_default();
+                Some((
+                    self.byte_range_to_char_index_range(&entry.byte_range())
+                        .ok()?,
+                    key.trim_matches(['"', '\'']).to_string(),
+                    self.node_text(&entry).ok()?,
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let sorted_entries = entries
+            .iter()
+            .map(|(_, key, text)| (key, text))
+            .sorted()
+            .collect_vec();
+        let object_range = self
+            .byte_range_to_char_index_range(&object.byte_range())
+            .ok()?;
+        Some(EditTransaction::from_action_groups(
+            entries
+                .iter()
+                .zip(sorted_entries)
+                .enumerate()
+                .map(|(index, ((range, _, _), (_, text)))| {
+                    let edit = Action::Edit(Edit::new(&self.rope, *range, Rope::from_str(text)));
+                    ActionGroup::new(if index == 0 {
+                        [edit, Action::Select(Selection::new(object_range))].to_vec()
+                    } else {
+                        [edit].to_vec()
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    /// Returns the ranges of the open and close brackets of the innermost bracket pair
+    /// (parentheses, square brackets or curly braces) enclosing `char_index`.
+    pub(crate) fn surrounding_brackets(
+        &self,
+        char_index: CharIndex,
+    ) -> Option<(CharIndexRange, CharIndexRange)> {
+        if char_index.0 >= self.len_chars() {
+            return None;
+        }
+        let content = self.content();
+        [
+            EnclosureKind::Parentheses,
+            EnclosureKind::SquareBrackets,
+            EnclosureKind::CurlyBraces,
+        ]
+        .into_iter()
+        .filter_map(|kind| get_surrounding_indices(&content, kind, char_index, true))
+        .max_by_key(|(open, _)| *open)
+        .map(|(open, close)| ((open..open + 1).into(), (close..close + 1).into()))
+    }
+
+    /// Perform `search` from `char_index`, and remember it for `repeat_char_search`.
+    pub(crate) fn search_char(
+        &mut self,
+        char_index: CharIndex,
+        search: CharSearch,
+    ) -> Option<CharIndex> {
+        let result = self.apply_char_search(char_index, &search, false);
+        self.last_char_search = Some(search);
+        result
+    }
+
+    /// Repeat the last `search_char`, like Vim's `;`.
+    pub(crate) fn repeat_char_search(&self, char_index: CharIndex) -> Option<CharIndex> {
+        self.apply_char_search(char_index, self.last_char_search.as_ref()?, true)
+    }
+
+    /// Repeat the last `search_char` in the opposite direction, like Vim's `,`.
+    pub(crate) fn repeat_char_search_reversed(&self, char_index: CharIndex) -> Option<CharIndex> {
+        let search = self.last_char_search.as_ref()?;
+        self.apply_char_search(
+            char_index,
+            &CharSearch {
+                direction: search.direction.reverse(),
+                ..search.clone()
+            },
+            true,
+        )
+    }
+
+    /// When `is_repeat` is true, a non-inclusive search skips the adjacent target,
+    /// otherwise repeating it would never move the cursor.
+    fn apply_char_search(
+        &self,
+        char_index: CharIndex,
+        search: &CharSearch,
+        is_repeat: bool,
+    ) -> Option<CharIndex> {
+        let skip = if is_repeat && !search.inclusive { 1 } else { 0 };
+        match search.direction {
+            Direction::Start => {
+                self.find_char_backward(char_index - skip, search.target, search.inclusive)
+            }
+            Direction::End => {
+                self.find_char_forward(char_index + skip, search.target, search.inclusive)
+            }
+        }
+    }
+
+    /// Returns the outermost node of the string that covers `char_index`,
+    /// so that string contents and escape sequences resolve to the literal itself.
+    fn enclosing_string_node(&self, char_index: CharIndex) -> Option<Node> {
+        std::iter::successors(self.node_at_char(char_index), |node| node.parent())
+            .skip_while(|node| !node.kind().contains("string"))
+            .take_while(|node| node.kind().contains("string"))
+            .last()
+    }
+
+    /// Returns the leading whitespaces of the line of `char_index`.
+    fn line_indentation(&self, char_index: CharIndex) -> Option<String> {
+        Some(
+            self.get_line_by_char_index(char_index)
+                .ok()?
+                .chars()
+                .take_while(|char| char.is_whitespace() && *char != '\n')
+                .collect(),
+        )
+    }
+
+    /// Returns the nearest node covering `char_index` (the node itself included)
+    /// whose kind is one of `kinds`, e.g. the `impl_item` around a method.
+    pub(crate) fn nearest_ancestor_of_kind(
+        &self,
+        char_index: CharIndex,
+        kinds: &[&str],
+    ) -> Option<Node> {
+        std::iter::successors(self.node_at_char(char_index), |node| node.parent())
+            .find(|node| kinds.contains(&node.kind()))
+    }
+
+    fn enclosing_statement(&self, char_index: CharIndex) -> Option<Node> {
+        std::iter::successors(self.node_at_char(char_index), |node| node.parent())
+            .find(is_statement)
+    }
+
+    /// Runs the locals query of the language over the whole tree.
+    fn locals(&self) -> Option<Locals> {
+        use tree_sitter::StreamingIterator;
+
+        let query = self
+            .language
+            .as_ref()?
+            .locals_query()
+            .zip(self.treesitter_language.as_ref())
+            .and_then(|(source, language)| tree_sitter::Query::new(language, source).ok())?;
+        let tree = self.tree.as_ref()?;
+        let content = self.rope.to_string();
+        let capture_names = query.capture_names();
+        let mut locals = Locals::default();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let mut captures = cursor.captures(&query, tree.root_node(), content.as_bytes());
+        while let Some((query_match, index)) = captures.next() {
+            let capture = query_match.captures[*index];
+            let name = || {
+                capture
+                    .node
+                    .utf8_text(content.as_bytes())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            match capture_names[capture.index as usize] {
+                "local.scope" => locals.scopes.push(capture.node.byte_range()),
+                "local.reference" => locals.references.push((capture.node, name())),
+                capture_name if capture_name.starts_with("local.definition") => {
+                    locals.definitions.push((capture.node, name()))
+                }
+                _ => {}
+            }
+        }
+        // Definitions are also captured as references
+        let Locals {
+            definitions,
+            references,
+            ..
+        } = &mut locals;
+        references.retain(|(reference, _)| {
+            definitions
+                .iter()
+                .all(|(definition, _)| definition.id() != reference.id())
+        });
+        Some(locals)
+    }
+}
+
+/// The captures of a locals query, each definition and reference is paired with its name.
+#[derive(Default)]
+struct Locals<'tree> {
+    scopes: Vec<Range<usize>>,
+    definitions: Vec<(Node<'tree>, String)>,
+    references: Vec<(Node<'tree>, String)>,
+}
+
+impl<'tree> Locals<'tree> {
+    /// The innermost scope that encloses `node`, `None` means the whole file.
+    fn scope_of(&self, node: &Node) -> Option<&Range<usize>> {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.start <= node.start_byte() && node.end_byte() <= scope.end)
+            .min_by_key(|scope| scope.len())
+    }
+
+    /// Returns the definition that `node` refers to, or `node` itself if it is a definition.
+    ///
+    /// Among the definitions of the same name that are visible at `node`,
+    /// the latest one is picked, so that shadowing is respected.
+    fn definition_of(&self, node: &Node) -> Option<Node<'tree>> {
+        if let Some((definition, _)) = self
+            .definitions
+            .iter()
+            .find(|(definition, _)| definition.id() == node.id())
+        {
+            return Some(*definition);
+        }
+        let (_, name) = self
+            .references
+            .iter()
+            .find(|(reference, _)| reference.id() == node.id())?;
+        self.definitions
+            .iter()
+            .filter(|(definition, definition_name)| {
+                definition_name == name
+                    && visible_from(definition) <= node.start_byte()
+                    && self
+                        .scope_of(definition)
+                        .is_none_or(|scope| scope.contains(&node.start_byte()))
+            })
+            .max_by_key(|(definition, _)| visible_from(definition))
+            .map(|(definition, _)| *definition)
+    }
+}
+
+/// The byte from which `definition` can be referred to.
+/// A binding is not visible in its own value, for example, the second `x` of `let x = x + 1`.
+fn visible_from(definition: &Node) -> usize {
+    definition
+        .parent()
+        .and_then(|parent| parent.child_by_field_name("value"))
+        .filter(|value| value.start_byte() >= definition.end_byte())
+        .map_or(definition.end_byte(), |value| value.end_byte())
+}
+
+/// Returns true if the fold spanning `lines` hides `line`, which is every line but the first.
+fn hides_line(lines: &Range<usize>, line: usize) -> bool {
+    lines.start < line && line < lines.end
+}
+
+/// Returns the number of columns that `char` occupies when rendered at `cell`.
+fn cell_width(char: char, cell: usize, tab_width: usize) -> usize {
+    match char {
+        '\t' => tab_width - cell % tab_width,
+        '\r' | '\n' => 0,
+        _ => UnicodeWidthChar::width(char).unwrap_or(0),
+    }
+}
+
+/// Guesses whether `bytes` is binary rather than text, like Git does,
+/// by looking for NUL bytes or a high ratio of control bytes near the start.
+fn is_binary_content(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(8000)];
+    let control_bytes = sample
+        .iter()
+        .filter(|byte| byte.is_ascii_control() && !matches!(byte, b'\n' | b'\r' | b'\t' | 0x0c))
+        .count();
+    sample.contains(&0) || control_bytes * 10 > sample.len() * 3
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn is_statement(node: &Node) -> bool {
+    let kind = node.kind();
+    kind.ends_with("statement") || kind.ends_with("declaration")
+}
+
+/// Resolve the common escape sequences of string literals (Rust, JSON, etc.).
+/// Unknown escape sequences are kept as they are.
+fn unescape(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '\\' {
+            result.push(char);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some(escaped @ ('\\' | '"' | '\'' | '/')) => result.push(escaped),
+            Some('u') => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let hex = if braced {
+                    chars
+                        .by_ref()
+                        .take_while(|char| *char != '}')
+                        .collect::<String>()
+                } else {
+                    chars.by_ref().take(4).collect::<String>()
+                };
+                match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    Some(unicode) => result.push(unicode),
+                    None if braced => result.push_str(&format!("\\u{{{hex}}}")),
+                    None => result.push_str(&format!("\\u{hex}")),
+                }
+            }
+            Some(other) => {
+                result.push('\\');
+                result.push(other)
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// Returns the names of the entries of `directory`, sorted, with a trailing slash for directories.
+pub(crate) fn directory_entries(directory: &std::path::Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            Some(if entry.file_type().ok()?.is_dir() {
+                format!("{name}/")
+            } else {
+                name
+            })
+        })
+        .sorted()
+        .collect()
+}
+
+/// Returns the entries of `entries` which start with `partial_name`.
+///
+/// Hidden entries are only returned when `partial_name` starts with a dot.
+pub(crate) fn matching_path_entries(entries: &[String], partial_name: &str) -> Vec<String> {
+    entries
+        .iter()
+        .filter(|name| {
+            name.starts_with(partial_name)
+                && (!name.starts_with('.') || partial_name.starts_with('.'))
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod test_buffer {
+    use std::fs::File;
+
+    use itertools::Itertools;
+    use shared::canonicalized_path::CanonicalizedPath;
+    use tempfile::tempdir;
+
+    use crate::{
+        grid::{IndexedHighlightGroup, StyleKey},
+        selection::SelectionSet,
+        syntax_highlight::{HighlightedSpan, HighlightedSpans},
+    };
+
+    use super::{Buffer, BufferError};
+
+    fn rust_buffer(content: &str) -> Buffer {
+        Buffer::new(
+            shared::language::from_extension("rs")
+                .unwrap()
+                .tree_sitter_language(),
+            content,
+        )
+    }
+
+    /// Like `rust_buffer`, but the language is also set, for features that need its queries.
+    fn rust_buffer_with_language(content: &str) -> Buffer {
+        let mut buffer = rust_buffer(content);
+        buffer
+            .set_language(shared::language::from_extension("rs").unwrap())
+            .unwrap();
+        buffer
+    }
+
+    #[test]
+    fn get_parent_lines_1() {
+        let buffer = Buffer::new(
+            shared::language::from_extension("yaml")
+                .unwrap()
+                .tree_sitter_language(),
+            "
+- spongebob
+- who:
+  - lives
+  - in:
+    - a
+    - pineapple
+  - under
+",
+        );
+        let actual = buffer
+            .get_parent_lines(6)
+            .unwrap()
+            .into_iter()
+            .map(|line| line.content)
+            .collect_vec()
+            .join("\n");
+        let expected = "
+- who:
+  - in:
+"
+        .trim()
+        .to_string();
+        pretty_assertions::assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn get_parent_lines_2() {
+        let buffer = Buffer::new(
+            shared::language::from_extension("rs")
+                .unwrap()
+                .tree_sitter_language(),
+            "
+fn f(
+  x: X
+) -> Result<
+  A,
+  B
+> {
+  hello
+}",
+        );
+        let actual = buffer
+            .get_parent_lines(5)
+            .unwrap()
+            .into_iter()
+            .map(|line| line.content)
+            .collect_vec()
+            .join("\n");
+        let expected = "
+fn f(
+) -> Result<"
+            .trim()
+            .to_string();
+        pretty_assertions::assert_eq!(actual, expected)
+    }
+
+    mod replace {
+
+        use crate::{
+            context::{
+                LocalSearchConfig,
+                LocalSearchConfigMode::{AstGrep, Regex},
+            },
+            list::grep::RegexConfig,
+        };
+
+        use super::*;
+        fn test(input: &str, config: LocalSearchConfig, expected: &str) -> anyhow::Result<()> {
+            let mut buffer = Buffer::new(
+                shared::language::from_extension("rs")
+                    .unwrap()
+                    .tree_sitter_language(),
+                input,
+            );
+            buffer.replace(config, SelectionSet::default(), 0)?;
+            assert_eq!(buffer.content(), expected);
+            Ok(())
+        }
+
+        #[test]
+        fn literal_1() -> anyhow::Result<()> {
+            test(
+                "hel. help hel.o",
+                LocalSearchConfig::new(Regex(RegexConfig {
+                    escaped: true,
+                    case_sensitive: false,
+                    match_whole_word: false,
+                }))
+                .set_search("hel.".to_string())
+                .set_replacment("wow".to_string())
+                .to_owned(),
+                "wow help wowo",
+            )
+        }
+
+        #[test]
+        fn regex_capture_group() -> anyhow::Result<()> {
+            test(
+                "123x456",
+                LocalSearchConfig::new(Regex(RegexConfig {
+                    escaped: false,
+                    case_sensitive: false,
+                    match_whole_word: false,
+                }))
+                .set_search(r"(\d+)".to_string())
+                .set_replacment(r"($1)".to_string())
+                .to_owned(),
+                "(123)x(456)",
+            )
+        }
+
+        #[test]
+        fn ast_group_1() -> anyhow::Result<()> {
+            test(
+                "fn main() { replace(x + 1, f(2)); replace(a,b) }",
+                LocalSearchConfig::new(AstGrep)
+                    .set_search(r"replace($X,$Y)".to_string())
+                    .set_replacment(r"replace($Y,$X)".to_string())
+                    .to_owned(),
+                "fn main() { replace(f(2),x + 1); replace(b,a) }",
+            )
+        }
+    }
+
+    /// The TempDir is returned so that the directory is not deleted
+    /// when the TempDir object is dropped
+    fn run_test(f: impl Fn(CanonicalizedPath, Buffer)) {
+        let dir = tempdir().unwrap();
+
+        let file_path = dir.path().join("main.rs");
+        File::create(&file_path).unwrap();
+        let path = CanonicalizedPath::try_from(file_path).unwrap();
+        path.write("").unwrap();
+
+        let buffer = Buffer::from_path(&path, true).unwrap();
+
+        f(path, buffer)
+    }
+
+    mod auto_format {
+
+        use crate::selection::{CharIndex, SelectionSet};
+
+        use super::run_test;
+
+        #[test]
+        fn should_format_code() {
+            run_test(|path, mut buffer| {
+                // Update the buffer with unformatted code
+                buffer.update(" fn main\n() {}");
+
+                // Save the buffer
+                buffer.save(SelectionSet::default(), false, 0).unwrap();
+
+                // Expect the output is formatted
+                let saved_content = path.read().unwrap();
+                let buffer_content = buffer.rope.to_string();
+
+                assert_eq!(saved_content, "fn main() {}\n");
+                assert_eq!(buffer_content, "fn main() {}\n");
+
+                // Expect the syntax node is also updated
+                assert_eq!(
+                    buffer
+                        .get_next_token(CharIndex::default(), false)
+                        .unwrap()
+                        .byte_range(),
+                    0..2
+                );
+            })
+        }
+
+        #[test]
+        fn format_preview_matches_save() {
+            run_test(|_, mut buffer| {
+                buffer.update(" fn main\n() {}");
+                let preview = buffer.format_preview().unwrap();
+                assert!(!preview.is_empty());
+
+                // Nothing is applied by the preview
+                assert_eq!(buffer.rope.to_string(), " fn main\n() {}");
+
+                let mut previewed = buffer.rope.clone();
+                for (range, new) in preview.iter().rev() {
+                    previewed.remove(range.as_usize_range());
+                    previewed.insert(range.start.0, new);
+                }
+
+                buffer.save(SelectionSet::default(), false, 0).unwrap();
+                assert_eq!(previewed.to_string(), buffer.rope.to_string());
+            })
+        }
+
+        #[test]
+        /// The formatted output should be undoable,
+        /// in case the formatter messed up the code.
+        fn should_be_undoable() {
+            run_test(|_, mut buffer| {
+                let original = " fn main\n() {}";
+                buffer.update(original);
+
+                buffer.save(SelectionSet::default(), false, 0).unwrap();
+
+                // Expect the buffer is formatted
+                assert_ne!(buffer.rope.to_string(), original);
+
+                // Undo the buffer
+                buffer.undo(0).unwrap();
+
+                let content = buffer.rope.to_string();
+
+                // Expect the content is reverted to the original
+                assert_eq!(content, " fn main\n() {}");
+            })
+        }
+
+        #[test]
+        fn should_not_run_when_syntax_node_is_malformed() {
+            run_test(|_, mut buffer| {
+                // Update the buffer to be invalid Rust code
+                buffer.update("fn main() {");
+
+                // Save the buffer
+                buffer.save(SelectionSet::default(), false, 0).unwrap();
+
+                // Expect the buffer remain unchanged,
+                // because the syntax node is invalid
+                assert_eq!(buffer.rope.to_string(), "fn main() {");
+            })
+        }
+
+        #[test]
+        fn should_not_update_buffer_if_formatter_returns_error() {
+            let code = r#"
+            let x = "1";
+                "#;
+
+            run_test(|_, mut buffer| {
+                // Update the buffer to be valid Rust code
+                // but unformatable
+                buffer.update(code);
+
+                // The code should be deemed as valid by Tree-sitter,
+                // but not to the formatter
+                assert!(buffer.is_syntactically_valid());
+
+                buffer.save(SelectionSet::default(), false, 0).unwrap();
+
+                // Expect the buffer remain unchanged
+                assert_eq!(buffer.rope.to_string(), code);
+            })
+        }
+    }
+
+    #[test]
+    fn only_update_syntax_highlight_spans_of_same_batch_id() {
+        run_test(|_, mut buffer| {
+            let initial_batch_id = buffer.batch_id().clone();
+
+            let initial_spans = buffer.highlighted_spans().clone();
+
+            // Update the buffer, this should cause the batch ID to be changed
+            buffer
+                .update_content("testing", Default::default(), 1)
+                .unwrap();
+
+            // Update the buffer with new highlight spans using the initial batch ID
+            let new_highlighted_spans = HighlightedSpans(
+                [HighlightedSpan {
+                    byte_range: Default::default(),
+                    style_key: StyleKey::Syntax(IndexedHighlightGroup::new(0)),
+                }]
+                .to_vec(),
+            );
+            buffer.update_highlighted_spans(initial_batch_id, new_highlighted_spans.clone());
+
+            // Expect the highlight spans remain the same, because the batch ID is changed
+            assert_eq!(&initial_spans, buffer.highlighted_spans());
+            assert_ne!(&new_highlighted_spans.0, buffer.highlighted_spans())
+        })
+    }
+
+    mod patch_edit {
+        use crate::edit::EditTransaction;
+
+        use super::*;
+        fn run_test(old: &str, new: &str) -> anyhow::Result<EditTransaction> {
+            let mut buffer = Buffer::new(Some(tree_sitter_md::LANGUAGE.into()), old);
+
+            let edit_transaction = buffer.get_edit_transaction(new)?;
+
+            // Apply the edit transaction
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+
+            // Expect the content to be the same as the 2nd files
+            pretty_assertions::assert_eq!(buffer.content(), new);
+
+            Ok(edit_transaction)
+        }
+
+        #[test]
+        fn empty_line_removal() -> anyhow::Result<()> {
+            let old = r#"
+            let y = "2";
+            let z = 3;
+
+            let a = 4;
+            "#
+            .trim();
+
+            let new = r#"
+            let y = "2";
+            let z = 3;
+            let a = 4;
+            "#
+            .trim();
+
+            run_test(old, new)?;
+            Ok(())
+        }
+
+        #[test]
+        fn all_kinds_of_edits() -> anyhow::Result<()> {
+            let old = r#"
+            let x = "1";
+            let y = "2";
+            let z = 3;
+            let a = 4;
+            let b = 4;
+            // This line will be removed
+                "#
+            .trim();
+
+            // Suppose the new content has all kinds of changes:
+            // 1. Replacement (line 1)
+            // 2. Insertion (line 3)
+            // 3. Deletion (last line)
+            let new = r#"
+            let x = "this line is replaced
+                     with multiline content"
+            let y = "2";
+            let z = 3;
+            // This is a newly inserted line
+            let a = 4;
+            let b = 4;
+                            "#
+            .trim();
+
+            let edit_transaction = run_test(old, new)?;
+
+            // Expect there are 3 edits
+            assert_eq!(edit_transaction.edits().len(), 3);
+
+            Ok(())
+        }
+
+        #[test]
+        fn empty_line_with_whitespaces() -> anyhow::Result<()> {
+            // The line after `let x = x;` has multiple whitespaces in it
+            let old = r#"
+fn main() {
+    let x = x;
+
+let z = z;
+
+    let y = y;
+}
+"#
+            .trim();
+
+            let new = r#"
+fn main() {
+    let x = x;
+
+    let z = z;
+
+    let y = y;
+}
+"#
+            .trim();
+
+            run_test(old, new)?;
+            Ok(())
+        }
+
+        #[test]
+        fn newline_insertion() -> anyhow::Result<()> {
+            run_test("", "\n")?;
+            Ok(())
+        }
+
+        #[test]
+        fn newline_removal() -> anyhow::Result<()> {
+            run_test("\n", "")?;
+            Ok(())
+        }
+    }
+
+    mod inline_variable {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        #[test]
+        fn inline_variable_used_twice() -> anyhow::Result<()> {
+            let mut buffer = rust_buffer_with_language(
+                "
+fn main() {
+    let x = 1 + 2;
+    foo(x, x);
+}"
+                .trim(),
+            );
+            let edit_transaction = buffer
+                .inline_variable(CharIndex(buffer.content().find("x =").unwrap()))
+                .unwrap();
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            pretty_assertions::assert_eq!(
+                buffer.content(),
+                "
+fn main() {
+    foo(1 + 2, 1 + 2);
+}"
+                .trim()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn refuse_when_reassigned() {
+            let buffer = rust_buffer_with_language(
+                "
+fn main() {
+    let mut x = 1;
+    x = 2;
+    foo(x);
+}"
+                .trim(),
+            );
+            let char_index = CharIndex(buffer.content().find("foo(x").unwrap() + 4);
+            assert_eq!(buffer.inline_variable(char_index), None);
+        }
+
+        /// Inline the variable at the first occurrence of `needle` in `content`.
+        fn inline_at(content: &str, needle: &str) -> anyhow::Result<String> {
+            let mut buffer = rust_buffer_with_language(content.trim());
+            let edit_transaction = buffer
+                .inline_variable(CharIndex(buffer.content().find(needle).unwrap()))
+                .unwrap();
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            Ok(buffer.content())
+        }
+
+        #[test]
+        fn shadowed_in_nested_block() -> anyhow::Result<()> {
+            let content = "
+fn main() {
+    let x = 1;
+    {
+        let x = 2;
+        foo(x);
+    }
+    bar(x);
+}";
+            pretty_assertions::assert_eq!(
+                inline_at(content, "x = 1")?,
+                "
+fn main() {
+    {
+        let x = 2;
+        foo(x);
+    }
+    bar(1);
+}"
+                .trim()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn shadowed_by_redeclaration() -> anyhow::Result<()> {
+            let content = "
+fn main() {
+    let x = 1;
+    let x = x + 2;
+    foo(x);
+}";
+            pretty_assertions::assert_eq!(
+                inline_at(content, "x = 1")?,
+                "
+fn main() {
+    let x = 1 + 2;
+    foo(x);
+}"
+                .trim()
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn struct_field_shorthand() -> anyhow::Result<()> {
+            let content = "
+fn main() {
+    let x = 1;
+    Foo { x };
+}";
+            pretty_assertions::assert_eq!(
+                inline_at(content, "x = 1")?,
+                "
+fn main() {
+    Foo { x: 1 };
+}"
+                .trim()
+            );
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn wrap_in_call() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let mut buffer = rust_buffer("let x = foo + 1;");
+        let range = (CharIndex(8)..CharIndex(15)).into();
+        let edit_transaction = buffer.wrap_in_call(range, "dbg!")?;
+        let (selection_set, _) = buffer.apply_edit_transaction(
+            &edit_transaction,
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        assert_eq!(buffer.content(), "let x = dbg!(foo + 1);");
+        assert_eq!(
+            selection_set.primary_selection().range(),
+            (CharIndex(21)..CharIndex(21)).into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stub_from_call() {
+        use crate::selection::CharIndex;
+
+        let buffer = rust_buffer("fn main() { foo(a, b); }");
+        let char_index = CharIndex(buffer.content().find("foo").unwrap());
+        pretty_assertions::assert_eq!(
+            buffer.stub_from_call(char_index).unwrap(),
+            "fn foo(a: TODO, b: TODO) {\n    todo!()\n}"
+        );
+    }
+
+    #[test]
+    fn selection_to_lsp_range() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+
+        let buffer = rust_buffer("fn main() {\n    foo();\n}");
+        let selection = Selection::new((CharIndex(3)..CharIndex(19)).into());
+        assert_eq!(
+            buffer.selection_to_lsp_range(&selection)?,
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, 3),
+                lsp_types::Position::new(1, 7)
+            )
+        );
+
+        // A selection ending after a newline ends at the start of the next line
+        let selection = Selection::new((CharIndex(12)..CharIndex(23)).into());
+        assert_eq!(
+            buffer.selection_to_lsp_range(&selection)?,
+            lsp_types::Range::new(
+                lsp_types::Position::new(1, 0),
+                lsp_types::Position::new(2, 0)
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn walk_selection_ranges() {
+        use crate::selection::CharIndex;
+
+        let mut buffer = rust_buffer("fn main() { foo(a, b); }");
+        let range = |start: u32, end: u32| {
+            lsp_types::Range::new(
+                lsp_types::Position::new(0, start),
+                lsp_types::Position::new(0, end),
+            )
+        };
+        let char_index_range =
+            |start: usize, end: usize| -> crate::char_index_range::CharIndexRange {
+                (CharIndex(start)..CharIndex(end)).into()
+            };
+        buffer.set_selection_ranges(
+            [lsp_types::SelectionRange {
+                // `a`
+                range: range(16, 17),
+                parent: Some(Box::new(lsp_types::SelectionRange {
+                    // `(a, b)`
+                    range: range(15, 21),
+                    parent: Some(Box::new(lsp_types::SelectionRange {
+                        // `foo(a, b)`
+                        range: range(12, 21),
+                        parent: None,
+                    })),
+                })),
+            }]
+            .to_vec(),
+        );
+        assert_eq!(
+            buffer.expand_selection_semantic(char_index_range(16, 17)),
+            Some(char_index_range(15, 21))
+        );
+        assert_eq!(
+            buffer.expand_selection_semantic(char_index_range(15, 21)),
+            Some(char_index_range(12, 21))
+        );
+        assert_eq!(
+            buffer.expand_selection_semantic(char_index_range(12, 21)),
+            None
+        );
+        assert_eq!(
+            buffer.shrink_selection_semantic(char_index_range(12, 21)),
+            Some(char_index_range(15, 21))
+        );
+    }
+
+    #[test]
+    fn walk_selection_ranges_fallback_to_syntax_tree() {
+        use crate::selection::CharIndex;
+
+        let buffer = rust_buffer("fn main() { foo(a, b); }");
+        // From `a` to `(a, b)`
+        assert_eq!(
+            buffer.expand_selection_semantic((CharIndex(16)..CharIndex(17)).into()),
+            Some((CharIndex(15)..CharIndex(21)).into())
+        );
+    }
+
+    #[test]
+    fn ranges_of_kind() -> anyhow::Result<()> {
+        let buffer = rust_buffer(r#"fn main() { let x = "hello"; f("world", 1); }"#);
+        let actual = buffer
+            .ranges_of_kind(&["string_literal"])
+            .into_iter()
+            .map(|range| Ok(buffer.slice(&range)?.to_string()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(actual, ["\"hello\"", "\"world\""]);
+
+        // Returns nothing without a syntax tree
+        let buffer = Buffer::new(None, r#""hello""#);
+        assert!(buffer.ranges_of_kind(&["string_literal"]).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn swap_with_next_sibling() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+
+        let mut buffer = rust_buffer("fn main() { f(a, bb); }");
+        let selection = Selection::new((CharIndex(14)..CharIndex(15)).into());
+        let edit_transaction = buffer.swap_with_next_sibling(&selection).unwrap();
+        let (selection_set, _) = buffer.apply_edit_transaction(
+            &edit_transaction,
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        assert_eq!(buffer.content(), "fn main() { f(bb, a); }");
+        assert_eq!(
+            buffer
+                .slice(&selection_set.primary_selection().range())?
+                .to_string(),
+            "a"
+        );
+
+        // There is no next sibling after the last argument
+        let selection = Selection::new((CharIndex(18)..CharIndex(19)).into());
+        assert_eq!(buffer.swap_with_next_sibling(&selection), None);
+        Ok(())
+    }
+
+    #[test]
+    fn transpose_selections() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let mut buffer = Buffer::new(None, "let hello = a;");
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: Selection::new((CharIndex(4)..CharIndex(9)).into()),
+            tail: [Selection::new((CharIndex(12)..CharIndex(13)).into())].to_vec(),
+        });
+        let edit_transaction = buffer.transpose_selections(selection_set.clone()).unwrap();
+        let (selection_set, _) =
+            buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "let a = hello;");
+        assert_eq!(
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| Ok(buffer.slice(&selection.range())?.to_string()))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+            ["a", "hello"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn first_diagnostic_of_at_least() {
+        use lsp_types::DiagnosticSeverity;
+
+        let mut buffer = Buffer::new(None, "first\nsecond\nthird");
+        let diagnostic =
+            |line: u32, severity: DiagnosticSeverity, message: &str| lsp_types::Diagnostic {
+                range: lsp_types::Range::new(
+                    lsp_types::Position::new(line, 0),
+                    lsp_types::Position::new(line, 1),
+                ),
+                severity: Some(severity),
+                message: message.to_string(),
+                ..Default::default()
+            };
+        buffer.set_diagnostics(
+            [
+                diagnostic(2, DiagnosticSeverity::WARNING, "late warning"),
+                diagnostic(1, DiagnosticSeverity::ERROR, "error"),
+                diagnostic(0, DiagnosticSeverity::HINT, "early hint"),
+            ]
+            .to_vec(),
+        );
+        let message = |severity| {
+            buffer
+                .first_diagnostic_of_at_least(severity)
+                .map(|diagnostic| diagnostic.message)
+        };
+        assert_eq!(message(DiagnosticSeverity::ERROR).unwrap(), "error");
+        assert_eq!(message(DiagnosticSeverity::WARNING).unwrap(), "error");
+        assert_eq!(message(DiagnosticSeverity::HINT).unwrap(), "early hint");
+    }
+
+    #[test]
+    fn nearest_clean_line() {
+        let mut buffer = Buffer::new(None, "0\n1\n2\n3\n4\n5\n6\n7");
+        let error = |line: u32| lsp_types::Diagnostic {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(line, 0),
+                lsp_types::Position::new(line, 1),
+            ),
+            severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+            ..Default::default()
+        };
+        buffer.set_diagnostics([error(3), error(4)].to_vec());
+        assert_eq!(buffer.nearest_clean_line(3), 2);
+        assert_eq!(buffer.nearest_clean_line(4), 5);
+        assert_eq!(buffer.nearest_clean_line(6), 6);
+    }
+
+    #[test]
+    fn word_diff() {
+        use crate::selection::CharIndex;
+
+        use super::WordChange;
+
+        run_test(|_, mut buffer| {
+            buffer.update("let x = foo + 1;\nlet y = 2;\n");
+            buffer.save_without_formatting(true).unwrap();
+            buffer.update("let x = bar + 1;\nlet y = 2;\n");
+
+            assert_eq!(
+                buffer.word_diff().unwrap(),
+                [WordChange {
+                    range: (CharIndex(8)..CharIndex(11)).into(),
+                    old: "foo".to_string(),
+                    new: "bar".to_string(),
+                }]
+            );
+        })
+    }
+
+    mod whitespace_issue_spans {
+        use crate::{buffer::WhitespaceIssue, selection::CharIndex};
+
+        use super::*;
+
+        #[test]
+        fn trailing_whitespace() {
+            let buffer = rust_buffer("fn main() {  \n}");
+            assert_eq!(
+                buffer.whitespace_issue_spans(),
+                [(
+                    (CharIndex(11)..CharIndex(13)).into(),
+                    WhitespaceIssue::TrailingWhitespace
+                )]
+            );
+        }
+
+        #[test]
+        fn mixed_indentation() {
+            let buffer = rust_buffer("fn main() {\n\t  let x = 1;\n}");
+            assert_eq!(
+                buffer.whitespace_issue_spans(),
+                [(
+                    (CharIndex(12)..CharIndex(15)).into(),
+                    WhitespaceIssue::MixedIndentation
+                )]
+            );
+        }
+
+        #[test]
+        fn skip_whitespaces_within_strings() {
+            let buffer = rust_buffer("fn main() {\n    let x = \"a  \n\t  b\";\n}");
+            assert!(buffer.whitespace_issue_spans().is_empty());
+        }
+    }
+
+    #[test]
+    fn char_index_at_percent() -> anyhow::Result<()> {
+        let buffer = Buffer::new(None, &(0..10).map(|line| format!("{line}\n")).join(""));
+        assert_eq!(buffer.char_index_at_percent(50.0), buffer.line_to_char(5)?);
+        assert_eq!(buffer.char_index_at_percent(0.0), buffer.line_to_char(0)?);
+        // Clamped to the last line
+        assert_eq!(buffer.char_index_at_percent(100.0), buffer.line_to_char(9)?);
+        assert_eq!(buffer.char_index_at_percent(150.0), buffer.line_to_char(9)?);
+        Ok(())
+    }
+
+    mod find_char {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        #[test]
+        fn forward_inclusive() {
+            let buffer = Buffer::new(None, "foo(bar, baz)\nqux,");
+            assert_eq!(
+                buffer.find_char_forward(CharIndex(0), ',', true),
+                Some(CharIndex(7))
+            );
+        }
+
+        #[test]
+        fn forward_exclusive() {
+            let buffer = Buffer::new(None, "foo(bar, baz)\nqux,");
+            assert_eq!(
+                buffer.find_char_forward(CharIndex(0), ',', false),
+                Some(CharIndex(6))
+            );
+        }
+
+        #[test]
+        fn no_match_on_line() {
+            let buffer = Buffer::new(None, "foo(bar, baz)\nqux,");
+            // The `,` on the next line is not matched
+            assert_eq!(buffer.find_char_forward(CharIndex(8), ',', true), None);
+            // The `)` on the previous line is not matched
+            assert_eq!(buffer.find_char_backward(CharIndex(17), ')', true), None);
+        }
+
+        #[test]
+        fn backward() {
+            let buffer = Buffer::new(None, "foo(bar, baz)\nqux,");
+            assert_eq!(
+                buffer.find_char_backward(CharIndex(12), '(', true),
+                Some(CharIndex(3))
+            );
+            assert_eq!(
+                buffer.find_char_backward(CharIndex(12), '(', false),
+                Some(CharIndex(4))
+            );
+        }
+    }
+
+    mod repeat_char_search {
+        use crate::{buffer::CharSearch, components::editor::Direction, selection::CharIndex};
+
+        use super::*;
+
+        #[test]
+        fn repeat_inclusive_search() {
+            let mut buffer = Buffer::new(None, "a,b,c,d");
+            let search = CharSearch {
+                target: ',',
+                direction: Direction::End,
+                inclusive: true,
+            };
+            assert_eq!(buffer.search_char(CharIndex(0), search), Some(CharIndex(1)));
+            assert_eq!(buffer.repeat_char_search(CharIndex(1)), Some(CharIndex(3)));
+            assert_eq!(
+                buffer.repeat_char_search_reversed(CharIndex(3)),
+                Some(CharIndex(1))
+            );
+        }
+
+        #[test]
+        fn repeat_exclusive_search_skips_adjacent_target() {
+            let mut buffer = Buffer::new(None, "a,b,c,d");
+            let search = CharSearch {
+                target: ',',
+                direction: Direction::End,
+                inclusive: false,
+            };
+            assert_eq!(buffer.search_char(CharIndex(0), search), Some(CharIndex(0)));
+            assert_eq!(buffer.repeat_char_search(CharIndex(0)), Some(CharIndex(2)));
+        }
+
+        #[test]
+        fn reset_when_content_is_replaced() {
+            let mut buffer = Buffer::new(None, "a,b,c,d");
+            let search = CharSearch {
+                target: ',',
+                direction: Direction::End,
+                inclusive: true,
+            };
+            buffer.search_char(CharIndex(0), search);
+            buffer.update("x,y");
+            assert_eq!(buffer.repeat_char_search(CharIndex(0)), None);
+        }
+    }
+
+    mod string_content {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        #[test]
+        fn simple_string() {
+            let buffer = rust_buffer(r#"fn main() { let x = "hello"; }"#);
+            let char_index = CharIndex(buffer.content().find("ell").unwrap());
+            let start = buffer.content().find("hello").unwrap();
+            assert_eq!(
+                buffer.string_content_range(char_index),
+                Some((CharIndex(start)..CharIndex(start + 5)).into())
+            );
+            assert_eq!(
+                buffer.unescaped_string_at(char_index),
+                Some("hello".to_string())
+            );
+        }
+
+        #[test]
+        fn string_with_escapes() {
+            let buffer = rust_buffer(r#"fn main() { let x = "a\nb\"c"; }"#);
+            let char_index = CharIndex(buffer.content().find('b').unwrap());
+            assert_eq!(
+                buffer.unescaped_string_at(char_index),
+                Some("a\nb\"c".to_string())
+            );
+        }
+
+        #[test]
+        fn outside_of_string() {
+            let buffer = rust_buffer(r#"fn main() { let x = "hello"; }"#);
+            assert_eq!(buffer.string_content_range(CharIndex(0)), None);
+        }
+    }
+
+    mod convert_quotes {
+        use crate::{
+            buffer::QuoteStyle,
+            selection::{CharIndex, SelectionSet},
+        };
+
+        use super::*;
+
+        fn javascript_buffer(content: &str) -> Buffer {
+            Buffer::new(
+                shared::language::from_extension("js")
+                    .unwrap()
+                    .tree_sitter_language(),
+                content,
+            )
+        }
+
+        #[test]
+        fn double_to_single_escapes_interior_quote() -> anyhow::Result<()> {
+            let mut buffer = javascript_buffer(r#"const x = "it's \"ok\"";"#);
+            let char_index = CharIndex(buffer.content().find("it's").unwrap());
+            let edit_transaction = buffer
+                .convert_quotes(char_index, QuoteStyle::Single)
+                .unwrap();
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            assert_eq!(buffer.content(), r#"const x = 'it\'s "ok"';"#);
+            Ok(())
+        }
+
+        #[test]
+        fn refuse_template_string_with_interpolation() {
+            let buffer = javascript_buffer("const x = `a ${b}`;");
+            let char_index = CharIndex(buffer.content().find('a').unwrap());
+            assert!(buffer
+                .convert_quotes(char_index, QuoteStyle::Double)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn statement_range() {
+        use crate::selection::CharIndex;
+
+        let buffer = rust_buffer("fn main() {\n    let x = foo(1);\n    bar();\n}");
+        let start = buffer.content().find("let").unwrap();
+        let char_index = CharIndex(buffer.content().find("foo").unwrap());
+        assert_eq!(
+            buffer.statement_range(char_index),
+            Some((CharIndex(start)..CharIndex(start + "let x = foo(1);".len())).into())
+        );
+    }
+
+    #[test]
+    fn move_node_out_of_block() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection, SelectionSet};
+
+        let mut buffer = rust_buffer(
+            "
+fn main() {
+    if x {
+        foo();
+        bar();
+    }
+}"
+            .trim(),
+        );
+        let char_index = CharIndex(buffer.content().find("bar").unwrap());
+        let edit_transaction = buffer
+            .move_node_out_of_block(&Selection::new((char_index..char_index + 1).into()))
+            .unwrap();
+        let (selection_set, _) = buffer.apply_edit_transaction(
+            &edit_transaction,
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        pretty_assertions::assert_eq!(
+            buffer.content(),
+            "
+fn main() {
+    if x {
+        foo();
+    }
+    bar();
+}"
+            .trim()
+        );
+        assert_eq!(
+            buffer.slice(&selection_set.primary_selection().range())?,
+            "bar();"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unsaved_user_buffer_paths() -> anyhow::Result<()> {
+        use super::BufferOwner;
+        use std::{cell::RefCell, rc::Rc};
+
+        let dir = tempdir()?;
+        let buffer = |name: &str, dirty: bool, owner: BufferOwner| -> anyhow::Result<_> {
+            let file_path = dir.path().join(name);
+            File::create(&file_path)?;
+            let path = CanonicalizedPath::try_from(file_path)?;
+            let mut buffer = Buffer::from_path(&path, false)?;
+            if dirty {
+                buffer.update("changed");
+            }
+            buffer.set_owner(owner);
+            Ok((path, Rc::new(RefCell::new(buffer))))
+        };
+        let (dirty_user_path, dirty_user) = buffer("a.txt", true, BufferOwner::User)?;
+        let (_, clean_user) = buffer("b.txt", false, BufferOwner::User)?;
+        let (_, dirty_system) = buffer("c.txt", true, BufferOwner::System)?;
+        let (_, clean_system) = buffer("d.txt", false, BufferOwner::System)?;
+        assert_eq!(
+            Buffer::unsaved_user_buffer_paths(&[
+                dirty_user,
+                clean_user,
+                dirty_system,
+                clean_system
+            ]),
+            vec![dirty_user_path]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_buffer_rejects_user_edits() -> anyhow::Result<()> {
+        use crate::selection::SelectionSet;
+
+        let mut buffer = Buffer::new(None, "hello");
+        buffer.set_read_only(true);
+        let edit_transaction = buffer.get_edit_transaction("bye")?;
+        let error = buffer
+            .apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<BufferError>(),
+            Some(BufferError::ReadOnly)
+        ));
+        assert_eq!(buffer.content(), "hello");
+
+        // The system can still update the content
+        buffer.update("bye");
+        assert_eq!(buffer.content(), "bye");
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_buffer_refuses_to_save() {
+        run_test(|path, mut buffer| {
+            buffer.update("changed");
+            buffer.set_read_only(true);
+            let error = buffer.save_without_formatting(true).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<BufferError>(),
+                Some(BufferError::ReadOnly)
+            ));
+            assert_eq!(path.read().unwrap(), "");
+        })
+    }
+
+    #[test]
+    fn clean_read_only_buffer_saves_nothing() {
+        run_test(|path, mut buffer| {
+            buffer.set_read_only(true);
+            assert_eq!(buffer.save_without_formatting(false).unwrap(), None);
+            assert_eq!(path.read().unwrap(), "");
+        })
+    }
+
+    #[test]
+    fn read_only_buffer_reloads_external_changes() {
+        run_test(|path, mut buffer| {
+            buffer.set_read_only(true);
+            path.write("changed externally").unwrap();
+            buffer.reload().unwrap();
+            assert_eq!(buffer.content(), "changed externally");
+            assert!(!buffer.dirty());
+        })
+    }
+
+    #[test]
+    fn reload_follows_reordered_lines() {
+        run_test(|path, mut buffer| {
+            path.write("foo\nbar\nbaz\n").unwrap();
+            buffer.reload().unwrap();
+            // The swapped lines start with the same character
+            path.write("foo\nbaz\nbar\n").unwrap();
+            buffer.reload().unwrap();
+            assert_eq!(buffer.content(), "foo\nbaz\nbar\n");
+        })
+    }
+
+    #[test]
+    fn append_drops_oldest_lines_beyond_max_lines() -> anyhow::Result<()> {
+        let mut buffer = Buffer::new(None, "");
+        buffer.set_max_lines(Some(3));
+        for index in 0..10 {
+            buffer.append(&format!("line {index}\n"), 0)?;
+        }
+        assert_eq!(buffer.content(), "line 7\nline 8\nline 9\n");
+        Ok(())
+    }
+
+    #[test]
+    fn streaming_past_max_lines() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let mut buffer = rust_buffer("");
+        buffer.set_max_lines(Some(5));
+        buffer.append("let x0 = 0;\n", 0)?;
+        buffer.save_marks(vec![(CharIndex(4)..CharIndex(6)).into()]);
+        for index in 1..20 {
+            buffer.append(&format!("let x{index} = {index};\n"), 0)?;
+            assert!(buffer.len_lines() <= 5);
+        }
+        assert!(buffer.marks().is_empty());
+        let root = buffer.tree().unwrap().root_node();
+        assert!(!root.has_error());
+        assert_eq!(root.byte_range(), 0..buffer.content().len());
+        assert!(buffer.content().starts_with("let x15 = 15;\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn append_preserves_owner_and_dirty() -> anyhow::Result<()> {
+        use super::BufferOwner;
+
+        let mut buffer = Buffer::new(None, "");
+        buffer.set_max_lines(Some(2));
+        for index in 0..3 {
+            buffer.append(&format!("line {index}\n"), 0)?;
+        }
+        assert_eq!(buffer.owner(), BufferOwner::System);
+        assert!(!buffer.dirty());
+        Ok(())
+    }
+
+    mod char_index_from_cell {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        #[test]
+        fn past_end_of_line() {
+            let buffer = Buffer::new(None, "ab\ncd");
+            assert_eq!(buffer.char_index_from_cell(0, 10, 0, 4), CharIndex(2));
+            assert_eq!(buffer.char_index_from_cell(0, 10, 1, 4), CharIndex(5));
+            assert_eq!(buffer.char_index_from_cell(5, 0, 0, 4), CharIndex(3));
+        }
+
+        #[test]
+        fn tab_expanded_column() {
+            let buffer = Buffer::new(None, "a\tx");
+            assert_eq!(buffer.char_index_from_cell(0, 0, 0, 4), CharIndex(0));
+            assert_eq!(buffer.char_index_from_cell(0, 2, 0, 4), CharIndex(1));
+            assert_eq!(buffer.char_index_from_cell(0, 3, 0, 4), CharIndex(1));
+            assert_eq!(buffer.char_index_from_cell(0, 4, 0, 4), CharIndex(2));
+        }
+    }
+
+    mod extend_selection {
+        use crate::{
+            components::editor::Direction,
+            selection::{CharIndex, Selection},
+        };
+
+        use super::*;
+
+        #[test]
+        fn extend_past_end_of_file() {
+            let buffer = Buffer::new(None, "hello");
+            let selection = Selection::new((CharIndex(3)..CharIndex(3)).into());
+            let selection = buffer.extend_selection(&selection, Direction::End, 10);
+            assert_eq!(
+                selection.extended_range(),
+                (CharIndex(3)..CharIndex(5)).into()
+            );
+        }
+
+        #[test]
+        fn extend_back_past_buffer_start() {
+            let buffer = Buffer::new(None, "hello");
+            let selection = Selection::new((CharIndex(2)..CharIndex(2)).into());
+            let selection = buffer.extend_selection(&selection, Direction::End, 2);
+            assert_eq!(
+                selection.extended_range(),
+                (CharIndex(2)..CharIndex(4)).into()
+            );
+
+            // Moving the active end back across the anchor never inverts the range
+            let selection = buffer.extend_selection(&selection, Direction::Start, 10);
+            assert_eq!(
+                selection.extended_range(),
+                (CharIndex(0)..CharIndex(2)).into()
+            );
+        }
+
+        #[test]
+        fn extend_the_other_end_after_swapping_ends() {
+            let buffer = Buffer::new(None, "hello world");
+            let selection = Selection::new((CharIndex(4)..CharIndex(6)).into());
+            let selection = buffer.extend_selection(&selection, Direction::End, 2);
+            assert_eq!(
+                selection.extended_range(),
+                (CharIndex(4)..CharIndex(8)).into()
+            );
+
+            // The anchor becomes the active end, so the start is moved while the end stays
+            let selection = selection.swap_selection_ends();
+            let selection = buffer.extend_selection(&selection, Direction::Start, 3);
+            assert_eq!(
+                selection.extended_range(),
+                (CharIndex(1)..CharIndex(8)).into()
+            );
+        }
+    }
+
+    #[test]
+    fn rotate_selection_contents() -> anyhow::Result<()> {
+        use crate::{
+            components::editor::Direction,
+            selection::{CharIndex, Selection},
+        };
+        use nonempty::NonEmpty;
+
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: Selection::new((CharIndex(0)..CharIndex(1)).into()),
+            tail: [
+                Selection::new((CharIndex(3)..CharIndex(5)).into()),
+                Selection::new((CharIndex(7)..CharIndex(10)).into()),
+            ]
+            .to_vec(),
+        });
+
+        let mut buffer = Buffer::new(None, "a, bb, ccc");
+        let edit_transaction =
+            buffer.rotate_selection_contents(selection_set.clone(), Direction::End)?;
+        buffer.apply_edit_transaction(&edit_transaction, selection_set.clone(), true, true, 0)?;
+        assert_eq!(buffer.content(), "ccc, a, bb");
+
+        let mut buffer = Buffer::new(None, "a, bb, ccc");
+        let edit_transaction =
+            buffer.rotate_selection_contents(selection_set.clone(), Direction::Start)?;
+        buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "bb, ccc, a");
+        Ok(())
+    }
+
+    #[test]
+    fn insert_counter() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let mut buffer = Buffer::new(None, "foo\nbar\nspam");
+        let cursor = |index: usize| Selection::new((CharIndex(index)..CharIndex(index)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: cursor(4),
+            tail: [cursor(0), cursor(8)].to_vec(),
+        });
+        let edit_transaction = buffer.insert_counter(selection_set.clone(), 1, 1, "{}. ")?;
+        buffer.apply_edit_transaction(&edit_transaction, selection_set.clone(), true, true, 0)?;
+        assert_eq!(buffer.content(), "1. foo\n2. bar\n3. spam");
+
+        let mut buffer = Buffer::new(None, "foo\nbar\nspam");
+        let edit_transaction = buffer.insert_counter(selection_set.clone(), 8, 2, "{:02}-")?;
+        buffer.apply_edit_transaction(&edit_transaction, selection_set.clone(), true, true, 0)?;
+        assert_eq!(buffer.content(), "08-foo\n10-bar\n12-spam");
+
+        // Oversized widths and overflowing counters are rejected
+        assert!(buffer
+            .insert_counter(selection_set.clone(), 1, 1, "{:999999999}")
+            .is_err());
+        assert!(buffer
+            .insert_counter(selection_set, i64::MAX - 1, 1, "{}")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn retain_selections_matching() {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+        use regex::Regex;
+
+        let buffer = Buffer::new(None, "foo bar1 baz x2");
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: selection(0, 3),
+            tail: [selection(4, 8), selection(9, 12), selection(13, 15)].to_vec(),
+        });
+        let regex = Regex::new(r"\d").unwrap();
+        let texts = |selection_set: SelectionSet| {
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| buffer.slice(&selection.range()).unwrap().to_string())
+                .collect_vec()
+        };
+        assert_eq!(
+            texts(
+                buffer
+                    .retain_selections_matching(selection_set.clone(), &regex, false)
+                    .unwrap()
+            ),
+            ["bar1", "x2"]
+        );
+        assert_eq!(
+            texts(
+                buffer
+                    .retain_selections_matching(selection_set, &regex, true)
+                    .unwrap()
+            ),
+            ["foo", "baz"]
+        );
+    }
+
+    #[test]
+    fn split_selections() {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+        use regex::Regex;
+
+        let buffer = Buffer::new(None, "name,,age,city");
+        let selection_set = SelectionSet::new(NonEmpty::new(Selection::new(
+            (CharIndex(0)..CharIndex(14)).into(),
+        )));
+        let separator = Regex::new(",").unwrap();
+        let texts = |selection_set: SelectionSet| {
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| buffer.slice(&selection.range()).unwrap().to_string())
+                .collect_vec()
+        };
+        assert_eq!(
+            texts(
+                buffer
+                    .split_selections(selection_set.clone(), &separator, false)
+                    .unwrap()
+            ),
+            ["name", "age", "city"]
+        );
+        assert_eq!(
+            texts(
+                buffer
+                    .split_selections(selection_set, &separator, true)
+                    .unwrap()
+            ),
+            ["name", "", "age", "city"]
+        );
+    }
+
+    #[test]
+    fn surrounding_brackets() {
+        use crate::selection::CharIndex;
+
+        let buffer = Buffer::new(None, "foo(bar[1], (baz + 2))");
+        let char_index = CharIndex(buffer.content().find("baz").unwrap());
+        assert_eq!(
+            buffer.surrounding_brackets(char_index),
+            Some((
+                (CharIndex(12)..CharIndex(13)).into(),
+                (CharIndex(20)..CharIndex(21)).into()
+            ))
+        );
+        assert_eq!(buffer.surrounding_brackets(CharIndex(0)), None);
+    }
+
+    #[test]
+    fn blank_lines() {
+        let buffer = Buffer::new(None, "a\n\nb\n  \nc\nd\n\ne\nf");
+        assert_eq!(buffer.next_blank_line(0, false), 1);
+        assert_eq!(buffer.next_blank_line(1, false), 6);
+        assert_eq!(buffer.next_blank_line(1, true), 3);
+        assert_eq!(buffer.next_blank_line(6, true), 8);
+        assert_eq!(buffer.previous_blank_line(6, false), 1);
+        assert_eq!(buffer.previous_blank_line(6, true), 3);
+        assert_eq!(buffer.previous_blank_line(1, true), 0);
+    }
+
+    mod display_width {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        #[test]
+        fn tabs() {
+            let buffer = Buffer::new(None, "a\tb\tc");
+            assert_eq!(
+                buffer.display_width((CharIndex(0)..CharIndex(5)).into(), 4),
+                9
+            );
+            // The tab stops are relative to the start of the line
+            assert_eq!(
+                buffer.display_width((CharIndex(1)..CharIndex(3)).into(), 4),
+                4
+            );
+        }
+
+        #[test]
+        fn wide_characters() {
+            let buffer = Buffer::new(None, "ab漢字");
+            assert_eq!(
+                buffer.display_width((CharIndex(0)..CharIndex(4)).into(), 4),
+                6
+            );
+        }
+    }
+
+    #[test]
+    fn matching_lines() {
+        let buffer = rust_buffer("fn main() {\n    foo();\n}\n\npub fn foo() {}\n");
+        assert_eq!(
+            buffer.matching_lines(&regex::Regex::new(r"\bfn ").unwrap()),
+            [
+                (0, "fn main() {".to_string()),
+                (4, "pub fn foo() {}".to_string())
+            ]
+        );
+    }
+
+    mod delete_matching_lines {
+        use crate::selection::SelectionSet;
+
+        use super::*;
+
+        fn run_test(content: &str, invert: bool, expected: &str) -> anyhow::Result<()> {
+            let mut buffer = Buffer::new(None, content);
+            let regex = regex::Regex::new(r"^\s*//").unwrap();
+            let edit_transaction = buffer.delete_matching_lines(&regex, invert);
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            assert_eq!(buffer.content(), expected);
+            Ok(())
+        }
+
+        #[test]
+        fn delete_comment_lines() -> anyhow::Result<()> {
+            run_test(
+                "// a\n// b\nfoo();\n  // c\nbar();\n// d",
+                false,
+                "foo();\nbar();",
+            )
+        }
+
+        #[test]
+        fn keep_only_comment_lines() -> anyhow::Result<()> {
+            run_test("// a\nfoo();\n  // c\nbar();\n", true, "// a\n  // c\n")
+        }
+    }
+
+    #[test]
+    fn record_and_replay_edits() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::CharIndex,
+        };
+
+        let mut buffer = Buffer::new(None, "foo\nbar");
+        buffer.start_recording();
+        for (index, text) in [(0, "x"), (1, "y")] {
+            let char_index = CharIndex(index);
+            let edit_transaction = EditTransaction::from_action_groups(
+                [ActionGroup::new(
+                    [Action::Edit(Edit::new(
+                        buffer.rope(),
+                        (char_index..char_index).into(),
+                        text.into(),
+                    ))]
+                    .to_vec(),
+                )]
+                .to_vec(),
+            );
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+        }
+        let recorded = buffer.stop_recording();
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(buffer.content(), "xyfoo\nbar");
+
+        // Replay at the start of the second line
+        let offset = buffer.line_to_char(1)?.0 as isize;
+        buffer.replay(&recorded, offset, SelectionSet::default(), 0)?;
+        assert_eq!(buffer.content(), "xyfoo\nxybar");
+        Ok(())
+    }
+
+    #[test]
+    fn replay_edits_at_each_selection() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::{CharIndex, Selection},
+        };
+        use nonempty::NonEmpty;
+
+        let insert = |buffer: &Buffer, index: usize, text: &str| {
+            EditTransaction::from_action_groups(
+                [ActionGroup::new(
+                    [Action::Edit(Edit::new(
+                        buffer.rope(),
+                        (CharIndex(index)..CharIndex(index)).into(),
+                        text.into(),
+                    ))]
+                    .to_vec(),
+                )]
+                .to_vec(),
+            )
+        };
+        // A macro that inserts a prefix, advances past the word, and inserts a suffix
+        let recorded = [
+            insert(&Buffer::new(None, "a"), 0, "- "),
+            insert(&Buffer::new(None, "- a"), 3, "!"),
+        ];
+
+        let mut buffer = Buffer::new(None, "a\nb\nc");
+        let cursor = |index: usize| Selection::new((CharIndex(index)..CharIndex(index)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: cursor(0),
+            tail: [cursor(2), cursor(4)].to_vec(),
+        });
+        let edit_transaction = buffer.replay_edits(&recorded, selection_set.clone())?;
+        buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "- a!\n- b!\n- c!");
+        Ok(())
+    }
+
+    #[test]
+    fn is_syntactically_valid() {
+        assert!(rust_buffer("fn main() {}").is_syntactically_valid());
+        assert!(!rust_buffer("fn main() {").is_syntactically_valid());
+        assert!(Buffer::new(None, "fn main() {").is_syntactically_valid());
+    }
+
+    #[test]
+    fn selection_size() {
+        use crate::selection::{CharIndex, Selection};
+
+        let buffer = Buffer::new(None, "héllo 🦀!");
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        assert_eq!(buffer.selection_size(&selection(0, 8)), (12, 8));
+        assert_eq!(buffer.selection_size(&selection(3, 3)), (0, 0));
+    }
+
+    mod vscode_position {
+        use crate::{position::Position, selection::CharIndex};
+
+        use super::*;
+
+        /// Every char index should survive a conversion to a VS Code position and back.
+        fn assert_vscode_roundtrip(buffer: &Buffer) {
+            for index in 0..=buffer.len_chars() {
+                let char_index = CharIndex(index);
+                let position = buffer.vscode_position_to_position(
+                    &buffer.char_to_vscode_position(char_index).unwrap(),
+                );
+                assert_eq!(buffer.position_to_char(position).unwrap(), char_index);
+            }
+        }
+
+        fn vscode_position(line: u32, character: u32) -> ki_protocol_types::Position {
+            ki_protocol_types::Position { line, character }
+        }
+
+        #[test]
+        fn roundtrip() {
+            assert_vscode_roundtrip(&Buffer::new(None, "hello\nworld"));
+            assert_vscode_roundtrip(&Buffer::new(None, "hello\nworld\n"));
+        }
+
+        #[test]
+        fn clamp_past_last_line() {
+            let buffer = Buffer::new(None, "hello\nworld");
+            assert_eq!(
+                buffer.vscode_position_to_position(&vscode_position(5, 2)),
+                Position { line: 1, column: 2 }
+            );
+        }
+
+        #[test]
+        fn clamp_past_last_column() {
+            let buffer = Buffer::new(None, "hello\r\nworld\n");
+            assert_eq!(
+                buffer.vscode_position_to_position(&vscode_position(0, 100)),
+                Position { line: 0, column: 5 }
+            );
+            assert_eq!(
+                buffer.vscode_position_to_position(&vscode_position(2, 100)),
+                Position { line: 2, column: 0 }
+            );
+        }
+
+        #[test]
+        fn utf16_code_units() {
+            // '🦀' takes two UTF-16 code units, but is one char
+            let buffer = Buffer::new(None, "a🦀b\n");
+            assert_eq!(
+                buffer.vscode_position_to_position(&vscode_position(0, 3)),
+                Position { line: 0, column: 2 }
+            );
+            assert_eq!(
+                buffer.vscode_position_to_position(&vscode_position(0, 100)),
+                Position { line: 0, column: 3 }
+            );
+        }
+    }
+
+    fn highlighted_rust_buffer() -> Buffer {
+        let mut buffer = rust_buffer("fn main() { \"<&>\" }");
+        let keyword = |byte_range| HighlightedSpan {
+            byte_range,
+            style_key: StyleKey::Syntax(IndexedHighlightGroup::from_str("keyword").unwrap()),
+        };
+        let batch_id = buffer.batch_id().clone();
+        buffer.update_highlighted_spans(batch_id, HighlightedSpans([keyword(0..2)].to_vec()));
+        buffer
+    }
+
+    #[test]
+    fn to_html() {
+        let theme = crate::themes::Theme::default();
+        let html = highlighted_rust_buffer().to_html(&theme);
+        let keyword_style = theme.get_style(&StyleKey::Syntax(
+            IndexedHighlightGroup::from_str("keyword").unwrap(),
+        ));
+        let keyword_color = keyword_style.foreground_color.unwrap().to_hex();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(&format!("<span style=\"color:{keyword_color}")));
+        assert!(html.contains(">fn</span> main() { &quot;&lt;&amp;&gt;&quot; }</pre>"));
+    }
+
+    #[test]
+    fn to_ansi() {
+        use crossterm::style::{Attribute, SetAttribute, SetForegroundColor};
+
+        let theme = crate::themes::Theme::default();
+        let ansi = highlighted_rust_buffer().to_ansi(&theme);
+        let keyword_color = theme
+            .get_style(&StyleKey::Syntax(
+                IndexedHighlightGroup::from_str("keyword").unwrap(),
+            ))
+            .foreground_color
+            .unwrap();
+        assert!(ansi.starts_with(&SetForegroundColor(keyword_color.into()).to_string()));
+        assert!(ansi.contains(&format!(
+            "fn{} main() {{ \"<&>\" }}",
+            SetAttribute(Attribute::Reset)
+        )));
+    }
+
+    #[test]
+    fn affected_lines() {
+        use crate::edit::{Action, ActionGroup, Edit, EditTransaction};
+
+        let buffer = Buffer::new(None, &(0..10).map(|i| format!("line {i}\n")).join(""));
+        let edit_line = |line: usize| {
+            let start = buffer.line_to_char(line).unwrap();
+            ActionGroup::new(vec![Action::Edit(Edit::new(
+                &buffer.rope,
+                (start..start + 4).into(),
+                "LINE".into(),
+            ))])
+        };
+        let edit_transaction =
+            EditTransaction::from_action_groups(vec![edit_line(7), edit_line(3)]);
+        assert_eq!(buffer.affected_lines(&edit_transaction), 3..8);
+        assert_eq!(
+            buffer.affected_lines(&EditTransaction::from_action_groups(Vec::new())),
+            0..0
+        );
+    }
+
+    #[test]
+    fn edits_mark_diagnostics_stale() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::CharIndex,
+        };
+
+        let mut buffer = Buffer::new(None, "hello world");
+        let diagnostic = lsp_types::Diagnostic {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(0, 6),
+                lsp_types::Position::new(0, 11),
+            ),
+            ..Default::default()
+        };
+        buffer.set_diagnostics([diagnostic.clone()].to_vec());
+        assert!(!buffer.diagnostics_stale());
+
+        buffer.apply_edit_transaction(
+            &EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(
+                    &buffer.rope,
+                    (CharIndex(0)..CharIndex(0)).into(),
+                    "hey ".into(),
+                ),
+            )])]),
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        assert!(buffer.diagnostics_stale());
+
+        buffer.set_diagnostics([diagnostic].to_vec());
+        assert!(!buffer.diagnostics_stale());
+        Ok(())
+    }
+
+    #[test]
+    fn indent_outline() {
+        let buffer = Buffer::new(
+            None,
+            "fruits\n  apple\n    red\n\n  banana\nvegetables\n\tcarrot\n",
+        );
+        let outline = buffer
+            .indent_outline()
+            .into_iter()
+            .map(|entry| (entry.line, entry.indent_level, entry.text))
+            .collect_vec();
+        assert_eq!(
+            outline,
+            [
+                (0, 0, "fruits".to_string()),
+                (1, 1, "apple".to_string()),
+                (2, 2, "red".to_string()),
+                (4, 1, "banana".to_string()),
+                (5, 0, "vegetables".to_string()),
+                (6, 1, "carrot".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn folds_shift_through_edits() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::CharIndex,
+        };
+
+        let mut buffer = Buffer::new(None, "a\nfn b() {\n  c\n  d\n}\ne\n");
+        buffer.fold(1..5)?;
+        assert_eq!(buffer.visible_lines(), [0, 1, 5]);
+
+        buffer.apply_edit_transaction(
+            &EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(
+                    &buffer.rope,
+                    (CharIndex(0)..CharIndex(0)).into(),
+                    "x\ny\n".into(),
+                ),
+            )])]),
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        assert_eq!(buffer.folded_line_ranges(), [3..7]);
+        assert!(!buffer.is_folded(3));
+        assert!(buffer.is_folded(4));
+        assert!(buffer.is_folded(6));
+        assert_eq!(buffer.visible_lines(), [0, 1, 2, 3, 7]);
+
+        buffer.unfold(4..5);
+        assert_eq!(buffer.visible_lines(), (0..8).collect_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn project_root() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        File::create(dir.path().join("Cargo.toml"))?;
+        std::fs::create_dir(dir.path().join("src"))?;
+        let file_path = dir.path().join("src").join("main.rs");
+        File::create(&file_path)?;
+
+        let buffer = Buffer::from_path(&CanonicalizedPath::try_from(file_path)?, true)?;
+        assert_eq!(
+            buffer.project_root(),
+            Some(CanonicalizedPath::try_from(dir.path())?)
+        );
+        assert_eq!(Buffer::new(None, "").project_root(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn display_path() -> anyhow::Result<()> {
+        let root = tempdir()?;
+        let outside = tempdir()?;
+        let buffer = |dir: &std::path::Path| -> anyhow::Result<_> {
+            std::fs::create_dir_all(dir.join("src"))?;
+            let file_path = dir.join("src").join("main.rs");
+            File::create(&file_path)?;
+            let path = CanonicalizedPath::try_from(file_path)?;
+            Ok((path.clone(), Buffer::from_path(&path, false)?))
+        };
+        let root_path = CanonicalizedPath::try_from(root.path())?;
+
+        let (_, inside_buffer) = buffer(root.path())?;
+        assert_eq!(
+            inside_buffer.display_path(&root_path),
+            std::path::Path::new("src")
+                .join("main.rs")
+                .display()
+                .to_string()
+        );
+
+        let (outside_path, outside_buffer) = buffer(outside.path())?;
+        assert_eq!(
+            outside_buffer.display_path(&root_path),
+            outside_path.display_absolute()
+        );
+
+        assert_eq!(Buffer::new(None, "").display_path(&root_path), "[scratch]");
+        Ok(())
+    }
+
+    #[test]
+    fn selection_is_whitespace() {
+        use crate::selection::{CharIndex, Selection};
+
+        let buffer = Buffer::new(None, "fn main() {\n  \t\n}");
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        assert!(buffer.selection_is_whitespace(&selection(11, 16)));
+        assert!(buffer.selection_is_whitespace(&selection(3, 3)));
+        assert!(!buffer.selection_is_whitespace(&selection(0, 7)));
+    }
+
+    #[test]
+    fn sort_object_keys() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+
+        let json_buffer = |content: &str| {
+            Buffer::new(
+                shared::language::from_extension("json")
+                    .unwrap()
+                    .tree_sitter_language(),
+                content,
+            )
+        };
+        let cursor = |index: usize| Selection::new((CharIndex(index)..CharIndex(index)).into());
+
+        let mut buffer = json_buffer("{\n  \"b\": 1,\n  \"c\": [3],\n  \"a\": {\"z\": 0}\n}");
+        let edit_transaction = buffer.sort_object_keys(&cursor(0)).unwrap();
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "{\n  \"a\": {\"z\": 0},\n  \"b\": 1,\n  \"c\": [3]\n}"
+        );
+
+        assert!(json_buffer("[2, 1]").sort_object_keys(&cursor(1)).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn line_byte_ranges() -> anyhow::Result<()> {
+        let buffer = Buffer::new(None, "fn main() {\n  let 🦀 = 1;\n}\n");
+        let individual_ranges = (0..3)
+            .map(|line| Ok(buffer.line_to_byte_range(line)?.range().clone()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(buffer.line_byte_ranges(0..3), individual_ranges);
+        assert_eq!(buffer.line_byte_ranges(1..2), individual_ranges[1..2]);
+        Ok(())
+    }
+
+    #[test]
+    fn highlighted_spans_stay_sorted_after_uneven_shifts() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::CharIndex,
+        };
+
+        let mut buffer = Buffer::new(None, "ab\ncd\nef");
+        let keyword = |byte_range| HighlightedSpan {
+            byte_range,
+            style_key: StyleKey::Syntax(IndexedHighlightGroup::from_str("keyword").unwrap()),
+        };
+        let batch_id = buffer.batch_id().clone();
+        buffer.update_highlighted_spans(
+            batch_id,
+            HighlightedSpans([keyword(0..2), keyword(6..8)].to_vec()),
+        );
+
+        // Only the spans of the first line are shifted because it is the last visible line,
+        // which moves the first span past the second span
+        buffer.apply_edit_transaction(
+            &EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(
+                    &buffer.rope,
+                    (CharIndex(0)..CharIndex(0)).into(),
+                    "x".repeat(10).into(),
+                ),
+            )])]),
+            SelectionSet::default(),
+            false,
+            true,
+            0,
+        )?;
+        assert_eq!(
+            buffer
+                .highlighted_spans()
+                .iter()
+                .map(|span| span.byte_range.clone())
+                .collect_vec(),
+            [6..8, 10..12]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn diff_against() {
+        use super::DiffHunk;
+        use crate::selection::CharIndex;
+
+        let buffer = Buffer::new(None, "a\nb\nc\nd\n");
+        let other = Buffer::new(None, "a\nB\nc\nd\ne\n");
+        let range = |start: usize, end: usize| (CharIndex(start)..CharIndex(end)).into();
+        assert_eq!(
+            buffer.diff_against(&other),
+            [
+                DiffHunk {
+                    range: range(2, 4),
+                    other_range: range(2, 4),
+                },
+                DiffHunk {
+                    range: range(8, 8),
+                    other_range: range(8, 10),
+                },
+            ]
+        );
+        assert!(buffer.diff_against(&buffer).is_empty());
+    }
+
+    #[test]
+    fn apply_unified_diff() -> anyhow::Result<()> {
+        let old = "fn main() {\n    println!(\"hello\");\n}\n\nfn other() {}\n";
+        let new = "fn main() {\n    println!(\"hello world\");\n}\n\nfn other() {}\nfn last() {}";
+        let patch = similar::TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(1)
+            .header("a/main.rs", "b/main.rs")
+            .to_string();
+
+        let mut buffer = Buffer::new(None, old);
+        buffer.apply_unified_diff(&patch, SelectionSet::default(), 0)?;
+        assert_eq!(buffer.content(), new);
+        Ok(())
+    }
+
+    #[test]
+    fn apply_unified_diff_with_non_applying_hunk() {
+        let patch = similar::TextDiff::from_lines("a\nb\nc\n", "a\nB\nc\n")
+            .unified_diff()
+            .to_string();
+
+        let mut buffer = Buffer::new(None, "a\nx\nc\n");
+        let error = buffer
+            .apply_unified_diff(&patch, SelectionSet::default(), 0)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Hunk 1 does not apply cleanly at line 1"));
+        assert_eq!(buffer.content(), "a\nx\nc\n");
+    }
+
+    #[test]
+    fn completion_replace_range() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let mut buffer = Buffer::new(None, "let foo = bar_baz-qux ");
+        let range = |start: usize, end: usize| (CharIndex(start)..CharIndex(end)).into();
+        // The cursor is right after "bar_baz-qux", where `-` is not part of an identifier
+        assert_eq!(
+            buffer.completion_replace_range(CharIndex(21)),
+            range(18, 21)
+        );
+        assert_eq!(
+            buffer.get_word_before_char_index(CharIndex(21)).unwrap(),
+            "qux"
+        );
+        // The cursor is in the middle of "foo"
+        assert_eq!(buffer.completion_replace_range(CharIndex(6)), range(4, 6));
+        assert_eq!(
+            buffer.get_word_before_char_index(CharIndex(6)).unwrap(),
+            "fo"
+        );
+        // The cursor is after a space
+        assert_eq!(
+            buffer.completion_replace_range(CharIndex(22)),
+            range(22, 22)
+        );
+        // Kebab-case identifiers are replaced whole in languages that allow them
+        buffer.set_language(shared::language::from_extension("css").unwrap())?;
+        assert_eq!(
+            buffer.get_word_before_char_index(CharIndex(21))?,
+            "bar_baz-qux"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_position_round_trip() -> anyhow::Result<()> {
+        use crate::cursor_position_store::CursorPositionStore;
+        use crate::position::Position;
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let dir = tempdir()?;
+        let file_path = dir.path().join("main.rs");
+        File::create(&file_path)?;
+        let path = CanonicalizedPath::try_from(file_path)?;
+        path.write("fn main() {\n    let x = 1;\n}\n")?;
+        let store = CursorPositionStore::new(dir.path().join("cursor_positions.json"));
+
+        let buffer = Buffer::from_path(&path, false)?;
+        let cursor = buffer.position_to_char(Position::new(1, 8))?;
+        let selection_set =
+            SelectionSet::new(NonEmpty::new(Selection::new((cursor..cursor + 1).into())));
+        buffer.save_cursor_position(&store, &selection_set)?;
+
+        let buffer = Buffer::from_path(&path, false)?;
+        let restored = buffer.restored_selection_set(&store).unwrap();
+        assert_eq!(
+            buffer.char_to_position(restored.primary_selection().extended_range().start)?,
+            Position::new(1, 8)
+        );
+
+        // The restored position is clamped when the file shrank
+        path.write("fn main() {}")?;
+        let buffer = Buffer::from_path(&path, false)?;
+        let restored = buffer.restored_selection_set(&store).unwrap();
+        assert_eq!(
+            restored.primary_selection().extended_range().start,
+            CharIndex(buffer.len_chars())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn selections_to_line_ranges() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let buffer = Buffer::new(None, "a\nb\nc\nd\ne\n");
+        let line = |index: usize| {
+            let start = buffer.line_to_char(index).unwrap();
+            Selection::new((start..start + 2).into())
+        };
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: line(2),
+            tail: vec![line(1), line(4)],
+        });
+        assert_eq!(
+            buffer.selections_to_line_ranges(&selection_set)?,
+            vec![1..3, 4..5]
+        );
+
+        let cursor = SelectionSet::new(NonEmpty::new(Selection::new(
+            (CharIndex(0)..CharIndex(0)).into(),
+        )));
+        assert_eq!(buffer.selections_to_line_ranges(&cursor)?, vec![0..1]);
+        Ok(())
+    }
+
+    #[test]
+    fn nearest_ancestor_of_kind() {
+        use crate::selection::CharIndex;
+
+        let buffer = rust_buffer("impl Foo {\n    fn bar(&self) {\n        baz()\n    }\n}\n");
+        let cursor = CharIndex(buffer.content().find("baz").unwrap());
+        let node = buffer
+            .nearest_ancestor_of_kind(cursor, &["impl_item", "trait_item"])
+            .unwrap();
+        assert_eq!(node.kind(), "impl_item");
+        assert_eq!(node.start_byte(), 0);
+        assert_eq!(
+            buffer
+                .nearest_ancestor_of_kind(cursor, &["function_item"])
+                .unwrap()
+                .kind(),
+            "function_item"
+        );
+        assert!(buffer
+            .nearest_ancestor_of_kind(cursor, &["struct_item"])
+            .is_none());
     }
 
     #[test]
-    fn get_parent_lines_2() {
-        let buffer = Buffer::new(
-            shared::language::from_extension("rs")
-                .unwrap()
-                .tree_sitter_language(),
-            "
-fn f(
-  x: X
-) -> Result<
-  A,
-  B
-> {
-  hello
-}",
+    fn normalize_line_endings() -> anyhow::Result<()> {
+        use super::LineEnding;
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let mut buffer = Buffer::new(None, "a\r\nb\nc\r\nd");
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: selection(3, 8),
+            tail: vec![selection(8, 9)],
+        });
+
+        let edit_transaction = buffer.normalize_line_endings(LineEnding::Lf, &selection_set);
+        // The line ending changes only once the transaction is applied
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+        let (selection_set, _) =
+            buffer.apply_edit_transaction(&edit_transaction, selection_set, false, true, 0)?;
+        assert_eq!(buffer.content(), "a\nb\nc\nd");
+        assert_eq!(buffer.line_ending(), LineEnding::Lf);
+        assert_eq!(
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| buffer.slice(&selection.range()).unwrap().to_string())
+                .collect_vec(),
+            ["b\nc\n", "d"]
         );
-        let actual = buffer
-            .get_parent_lines(5)
-            .unwrap()
-            .into_iter()
-            .map(|line| line.content)
-            .collect_vec()
-            .join("\n");
-        let expected = "
-fn f(
-) -> Result<"
-            .trim()
-            .to_string();
-        pretty_assertions::assert_eq!(actual, expected)
-    }
 
-    mod replace {
+        let edit_transaction = buffer.normalize_line_endings(LineEnding::Crlf, &selection_set);
+        let (selection_set, _) =
+            buffer.apply_edit_transaction(&edit_transaction, selection_set, false, true, 0)?;
+        assert_eq!(buffer.content(), "a\r\nb\r\nc\r\nd");
+        assert_eq!(
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| buffer.slice(&selection.range()).unwrap().to_string())
+                .collect_vec(),
+            ["b\r\nc\r\n", "d"]
+        );
+        assert_eq!(buffer.line_ending(), LineEnding::Crlf);
 
-        use crate::{
-            context::{
-                LocalSearchConfig,
-                LocalSearchConfigMode::{AstGrep, Regex},
-            },
-            list::grep::RegexConfig,
-        };
+        buffer.undo(0)?;
+        assert_eq!(buffer.content(), "a\nb\nc\nd");
+        assert_eq!(buffer.line_ending(), LineEnding::Lf);
+        Ok(())
+    }
 
-        use super::*;
-        fn test(input: &str, config: LocalSearchConfig, expected: &str) -> anyhow::Result<()> {
-            let mut buffer = Buffer::new(
-                shared::language::from_extension("rs")
-                    .unwrap()
-                    .tree_sitter_language(),
-                input,
-            );
-            buffer.replace(config, SelectionSet::default(), 0)?;
-            assert_eq!(buffer.content(), expected);
+    #[test]
+    fn binary_file_is_read_only_without_tree() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("main.rs");
+        std::fs::write(&file_path, b"fn main() {}\0\x01\x02\xff\xfe")?;
+        let buffer = Buffer::from_path(&CanonicalizedPath::try_from(file_path)?, true)?;
+        assert!(buffer.is_binary());
+        assert!(buffer.read_only());
+        assert!(buffer.tree.is_none());
+        assert!(buffer.language().is_none());
+        assert!(buffer.get_formatted_content().is_none());
+
+        let file_path = dir.path().join("lib.rs");
+        std::fs::write(&file_path, "fn main() {}\n")?;
+        let buffer = Buffer::from_path(&CanonicalizedPath::try_from(file_path)?, true)?;
+        assert!(!buffer.is_binary());
+        assert!(buffer.tree.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn shebang_redetects_language() -> anyhow::Result<()> {
+        use crate::edit::{Action, ActionGroup, Edit, EditTransaction};
+        use crate::selection::CharIndex;
+        use ropey::Rope;
+
+        let type_at_start = |buffer: &mut Buffer, text: &str| -> anyhow::Result<()> {
+            let edit_transaction = EditTransaction::from_action_groups(vec![ActionGroup::new(
+                vec![Action::Edit(Edit::new(
+                    &buffer.rope,
+                    (CharIndex(0)..CharIndex(0)).into(),
+                    Rope::from_str(text),
+                ))],
+            )]);
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
             Ok(())
-        }
+        };
 
-        #[test]
-        fn literal_1() -> anyhow::Result<()> {
-            test(
-                "hel. help hel.o",
-                LocalSearchConfig::new(Regex(RegexConfig {
-                    escaped: true,
-                    case_sensitive: false,
-                    match_whole_word: false,
-                }))
-                .set_search("hel.".to_string())
-                .set_replacment("wow".to_string())
-                .to_owned(),
-                "wow help wowo",
-            )
-        }
+        let mut buffer = Buffer::new(None, "");
+        type_at_start(&mut buffer, "#!/usr/bin/env python\nx = 1\n")?;
+        assert_eq!(
+            buffer
+                .language()
+                .and_then(|language| language.tree_sitter_grammar_id()),
+            Some("python".to_string())
+        );
+        assert_eq!(
+            buffer.tree().unwrap().root_node().kind(),
+            "module",
+            "Python parsing should be active"
+        );
 
-        #[test]
-        fn regex_capture_group() -> anyhow::Result<()> {
-            test(
-                "123x456",
-                LocalSearchConfig::new(Regex(RegexConfig {
-                    escaped: false,
-                    case_sensitive: false,
-                    match_whole_word: false,
-                }))
-                .set_search(r"(\d+)".to_string())
-                .set_replacment(r"($1)".to_string())
-                .to_owned(),
-                "(123)x(456)",
-            )
-        }
+        // An explicitly set language is not replaced
+        let mut buffer = Buffer::new(None, "");
+        buffer.set_language(shared::language::from_extension("md").unwrap())?;
+        type_at_start(&mut buffer, "#!/usr/bin/env python\n")?;
+        assert_eq!(
+            buffer
+                .language()
+                .and_then(|language| language.tree_sitter_grammar_id()),
+            Some("markdown".to_string())
+        );
 
-        #[test]
-        fn ast_group_1() -> anyhow::Result<()> {
-            test(
-                "fn main() { replace(x + 1, f(2)); replace(a,b) }",
-                LocalSearchConfig::new(AstGrep)
-                    .set_search(r"replace($X,$Y)".to_string())
-                    .set_replacment(r"replace($Y,$X)".to_string())
-                    .to_owned(),
-                "fn main() { replace(f(2),x + 1); replace(b,a) }",
-            )
-        }
+        // A transaction without edits does not redetect the language
+        let mut buffer = Buffer::new(None, "#!/usr/bin/env python\n");
+        buffer.apply_edit_transaction(
+            &EditTransaction::from_action_groups(Vec::new()),
+            SelectionSet::default(),
+            true,
+            true,
+            0,
+        )?;
+        assert_eq!(buffer.language(), None);
+        Ok(())
     }
 
-    /// The TempDir is returned so that the directory is not deleted
-    /// when the TempDir object is dropped
-    fn run_test(f: impl Fn(CanonicalizedPath, Buffer)) {
-        let dir = tempdir().unwrap();
+    #[test]
+    fn selections_to_quickfix() -> anyhow::Result<()> {
+        use crate::position::Position;
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
 
+        let dir = tempdir()?;
         let file_path = dir.path().join("main.rs");
-        File::create(&file_path).unwrap();
-        let path = CanonicalizedPath::try_from(file_path).unwrap();
-        path.write("").unwrap();
+        std::fs::write(&file_path, "let a = 1;\nlet bb = 2;\nlet ccc = 3;\n")?;
+        let path = CanonicalizedPath::try_from(file_path)?;
+        let buffer = Buffer::from_path(&path, false)?;
+        let word = |word: &str| {
+            let start = CharIndex(buffer.content().find(word).unwrap());
+            Selection::new((start..start + word.len()).into())
+        };
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: word("a"),
+            tail: vec![word("bb"), word("ccc")],
+        });
 
-        let buffer = Buffer::from_path(&path, true).unwrap();
+        let items = buffer.selections_to_quickfix(&selection_set, |selection| {
+            buffer.slice(&selection.range()).unwrap().to_string()
+        })?;
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (
+                    item.location().path.clone(),
+                    item.location().range.clone(),
+                    item.info().as_ref().unwrap().content().clone()
+                ))
+                .collect_vec(),
+            [
+                (
+                    path.clone(),
+                    Position::new(0, 4)..Position::new(0, 5),
+                    "a".to_string()
+                ),
+                (
+                    path.clone(),
+                    Position::new(1, 4)..Position::new(1, 6),
+                    "bb".to_string()
+                ),
+                (
+                    path.clone(),
+                    Position::new(2, 4)..Position::new(2, 7),
+                    "ccc".to_string()
+                ),
+            ]
+        );
+        Ok(())
+    }
 
-        f(path, buffer)
+    #[test]
+    fn reindent_pasted() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let pasted = "if a {\n    b();\n}\n";
+        let content = format!("fn main() {{\n    if x {{\n        foo();\n{pasted}    }}\n}}\n");
+        let mut buffer = rust_buffer(&content);
+        let start = CharIndex(content.find(pasted).unwrap());
+        let edit_transaction = buffer.reindent_pasted((start..start + pasted.len()).into())?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "fn main() {
+    if x {
+        foo();
+        if a {
+            b();
+        }
+    }
+}
+"
+        );
+        Ok(())
     }
 
-    mod auto_format {
+    #[test]
+    fn comment_paragraph_range() {
+        use crate::selection::CharIndex;
 
-        use crate::selection::{CharIndex, SelectionSet};
+        let content = "#[test]
+fn main() {
+    // first line
+    // second line
+    // third line
+    //
+    // next paragraph
+    foo(); // trailing
+}
+";
+        let buffer = rust_buffer(content);
+        let char_index = |text: &str| CharIndex(content.find(text).unwrap());
+        let range = buffer
+            .comment_paragraph_range(char_index("second"))
+            .unwrap();
+        assert_eq!(
+            buffer.slice(&range).unwrap().to_string(),
+            "    // first line\n    // second line\n    // third line"
+        );
+        assert_eq!(
+            buffer.comment_paragraph_range(char_index("first")),
+            Some(range)
+        );
+        assert_eq!(
+            buffer
+                .comment_paragraph_range(char_index("next"))
+                .map(|range| buffer.slice(&range).unwrap().to_string()),
+            Some("    // next paragraph".to_string())
+        );
+        assert_eq!(buffer.comment_paragraph_range(char_index("#[test]")), None);
+        assert_eq!(buffer.comment_paragraph_range(char_index("foo")), None);
+    }
 
-        use super::run_test;
+    #[test]
+    fn single_char_insert_skips_line_diff() -> anyhow::Result<()> {
+        use crate::{edit::EditTransaction, selection::CharIndex};
 
-        #[test]
-        fn should_format_code() {
-            run_test(|path, mut buffer| {
-                // Update the buffer with unformatted code
-                buffer.update(" fn main\n() {}");
+        let old = "fn main() {\n    let x = 1;\n}\n".repeat(100);
+        let new = old.replacen("let x = 1;", "let xy = 1;", 1);
 
-                // Save the buffer
-                buffer.save(SelectionSet::default(), false, 0).unwrap();
+        let buffer = Buffer::new(None, &old);
+        let edit_transaction = buffer.get_edit_transaction(&new)?;
+        let edits = edit_transaction.edits();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].range(), (CharIndex(21)..CharIndex(21)).into());
+        assert_eq!(edits[0].new.to_string(), "y");
 
-                // Expect the output is formatted
-                let saved_content = path.read().unwrap();
-                let buffer_content = buffer.rope.to_string();
+        let apply = |edit_transaction: &EditTransaction| -> anyhow::Result<String> {
+            let mut buffer = Buffer::new(None, &old);
+            buffer.apply_edit_transaction(
+                edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            Ok(buffer.content())
+        };
+        let line_diff = buffer.line_diff_edit_transaction(old.clone(), &new)?;
+        assert_eq!(apply(&edit_transaction)?, new);
+        assert_eq!(apply(&line_diff)?, new);
+        Ok(())
+    }
 
-                assert_eq!(saved_content, "fn main() {}\n");
-                assert_eq!(buffer_content, "fn main() {}\n");
+    #[test]
+    fn display_cursor_position() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
 
-                // Expect the syntax node is also updated
-                assert_eq!(
-                    buffer
-                        .get_next_token(CharIndex::default(), false)
-                        .unwrap()
-                        .byte_range(),
-                    0..2
-                );
-            })
-        }
+        let content = "first\nhello world foo bar\n\tx";
+        let buffer = Buffer::new(None, content);
+        let char_index = |text: &str| CharIndex(content.find(text).unwrap());
 
-        #[test]
-        /// The formatted output should be undoable,
-        /// in case the formatter messed up the code.
-        fn should_be_undoable() {
-            run_test(|_, mut buffer| {
-                let original = " fn main\n() {}";
-                buffer.update(original);
+        // The line is wrapped into "hello world" and " foo bar"
+        assert_eq!(
+            buffer.display_cursor_position(char_index("hello"), 12, 4)?,
+            (0, 0)
+        );
+        assert_eq!(
+            buffer.display_cursor_position(char_index("bar"), 12, 4)?,
+            (1, 5)
+        );
+        assert_eq!(buffer.char_to_position(char_index("bar"))?.column, 16);
 
-                buffer.save(SelectionSet::default(), false, 0).unwrap();
+        // Tabs span up to the next tab stop
+        assert_eq!(
+            buffer.display_cursor_position(char_index("x"), 12, 4)?,
+            (0, 4)
+        );
+        Ok(())
+    }
 
-                // Expect the buffer is formatted
-                assert_ne!(buffer.rope.to_string(), original);
+    mod set_marks_from_ranges {
+        use crate::{char_index_range::CharIndexRange, selection::CharIndex};
 
-                // Undo the buffer
-                buffer.undo(0).unwrap();
+        use super::*;
 
-                let content = buffer.rope.to_string();
+        fn range(start: usize, end: usize) -> CharIndexRange {
+            (CharIndex(start)..CharIndex(end)).into()
+        }
 
-                // Expect the content is reverted to the original
-                assert_eq!(content, " fn main\n() {}");
-            })
+        #[test]
+        fn replace() {
+            let mut buffer = Buffer::new(None, "<<<<<<< a\n=======\n>>>>>>> b\n");
+            buffer.save_marks(vec![range(0, 1), range(2, 3)]);
+            buffer.set_marks_from_ranges(vec![range(2, 3), range(4, 5)], true);
+            assert_eq!(buffer.marks(), [range(2, 3), range(4, 5)]);
         }
 
         #[test]
-        fn should_not_run_when_syntax_node_is_malformed() {
-            run_test(|_, mut buffer| {
-                // Update the buffer to be invalid Rust code
-                buffer.update("fn main() {");
+        fn additive() {
+            let mut buffer = Buffer::new(None, "<<<<<<< a\n=======\n>>>>>>> b\n");
+            buffer.save_marks(vec![range(0, 1), range(2, 3)]);
+            // Unlike `save_marks`, an existing mark is not toggled off
+            buffer.set_marks_from_ranges(vec![range(2, 3), range(4, 5)], false);
+            let marks = buffer.marks();
+            assert_eq!(marks.len(), 3);
+            for mark in [range(0, 1), range(2, 3), range(4, 5)] {
+                assert!(marks.contains(&mark));
+            }
+        }
+    }
 
-                // Save the buffer
-                buffer.save(SelectionSet::default(), false, 0).unwrap();
+    #[test]
+    fn text_between_marks() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let content = "let a = 1;\nlet b = 2;\nlet c = 3;\n";
+        let mut buffer = Buffer::new(None, content);
+        let mark = |text: &str| {
+            let start = CharIndex(content.find(text).unwrap());
+            (start..start + text.len()).into()
+        };
+        buffer.save_marks(vec![mark("a"), mark("2")]);
+        let marks = buffer.marks();
+        let expected = "a = 1;\nlet b = 2";
+        assert_eq!(
+            buffer.text_between_marks(marks[0], marks[1])?.to_string(),
+            expected
+        );
+        assert_eq!(
+            buffer.text_between_marks(marks[1], marks[0])?.to_string(),
+            expected
+        );
+        Ok(())
+    }
 
-                // Expect the buffer remain unchanged,
-                // because the syntax node is invalid
-                assert_eq!(buffer.rope.to_string(), "fn main() {");
-            })
+    #[test]
+    fn cycle_marks_in_creation_order() {
+        use crate::{char_index_range::CharIndexRange, selection::CharIndex};
+
+        let range =
+            |start: usize| -> CharIndexRange { (CharIndex(start)..CharIndex(start + 1)).into() };
+        let mut buffer = Buffer::new(None, "abcdefghij");
+        for start in [5, 1, 8] {
+            buffer.save_marks(vec![range(start)]);
+        }
+        assert_eq!(buffer.marks(), [range(5), range(1), range(8)]);
+
+        let cycled = std::iter::successors(buffer.cycle_marks(&range(0)), |mark| {
+            buffer.cycle_marks(mark)
+        })
+        .take(4)
+        .collect_vec();
+        assert_eq!(cycled, [range(5), range(1), range(8), range(5)]);
+
+        // Toggling a mark off removes it from the order
+        buffer.save_marks(vec![range(1)]);
+        assert_eq!(buffer.marks(), [range(5), range(8)]);
+        assert_eq!(buffer.cycle_marks(&range(5)), Some(range(8)));
+    }
+
+    #[test]
+    fn keep_only_selections() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let content = "fn main() {\n    foo();\n    bar();\n}\n";
+        let run = |separate_with_newlines: bool| -> anyhow::Result<(String, Vec<String>)> {
+            let mut buffer = Buffer::new(None, content);
+            let selection = |text: &str| {
+                let start = CharIndex(content.find(text).unwrap());
+                Selection::new((start..start + text.len()).into())
+            };
+            let selection_set = SelectionSet::new(NonEmpty {
+                head: selection("bar()"),
+                tail: vec![selection("foo()")],
+            });
+            let edit_transaction =
+                buffer.keep_only_selections(&selection_set, separate_with_newlines);
+            let (selection_set, _) =
+                buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+            let selected = selection_set
+                .selections()
+                .iter()
+                .map(|selection| buffer.slice(&selection.range()).unwrap().to_string())
+                .collect_vec();
+            Ok((buffer.content(), selected))
+        };
+        assert_eq!(
+            run(false)?,
+            (
+                "foo()bar()".to_string(),
+                vec!["foo()".to_string(), "bar()".to_string()]
+            )
+        );
+        assert_eq!(
+            run(true)?,
+            (
+                "foo()\nbar()".to_string(),
+                vec!["foo()".to_string(), "bar()".to_string()]
+            )
+        );
+        Ok(())
+    }
+
+    mod shift_indent {
+        use crate::selection::CharIndex;
+
+        use super::*;
+
+        fn run_test(content: &str, levels: i32, expected: &str) -> anyhow::Result<()> {
+            let mut buffer = rust_buffer(content);
+            let range = (CharIndex(0)..CharIndex(content.chars().count())).into();
+            let edit_transaction = buffer.shift_indent(range, levels, "    ")?;
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            assert_eq!(buffer.content(), expected);
+            Ok(())
         }
 
         #[test]
-        fn should_not_update_buffer_if_formatter_returns_error() {
-            let code = r#"
-            let x = "1";
-                "#;
-
-            run_test(|_, mut buffer| {
-                // Update the buffer to be valid Rust code
-                // but unformatable
-                buffer.update(code);
-
-                // The code should be deemed as valid by Tree-sitter,
-                // but not to the formatter
-                assert!(!buffer.tree.as_ref().unwrap().root_node().has_error());
+        fn right_by_two() -> anyhow::Result<()> {
+            run_test(
+                "fn f() {\nlet x = \"a\n  b\";\n\nfoo();\n}\n",
+                2,
+                // The line within the string and the blank line are untouched
+                "        fn f() {\n        let x = \"a\n  b\";\n\n        foo();\n        }\n",
+            )
+        }
 
-                buffer.save(SelectionSet::default(), false, 0).unwrap();
+        #[test]
+        fn left_by_one() -> anyhow::Result<()> {
+            run_test("        a();\n  b();\nc();\n", -1, "    a();\nb();\nc();\n")
+        }
 
-                // Expect the buffer remain unchanged
-                assert_eq!(buffer.rope.to_string(), code);
-            })
+        #[test]
+        fn lines_starting_with_a_string() -> anyhow::Result<()> {
+            run_test(
+                "let v = [\n\"a\",\n\"b\nc\",\n];\n",
+                1,
+                // Only the line within the multiline string is untouched
+                "    let v = [\n    \"a\",\n    \"b\nc\",\n    ];\n",
+            )
         }
     }
 
     #[test]
-    fn only_update_syntax_highlight_spans_of_same_batch_id() {
-        run_test(|_, mut buffer| {
-            let initial_batch_id = buffer.batch_id().clone();
+    fn names_in_scope() {
+        use crate::selection::CharIndex;
 
-            let initial_spans = buffer.highlighted_spans().clone();
+        let content = "
+fn add(alpha: usize, beta: usize) -> usize {
+    {
+        let hidden = 1;
+    }
+    alpha + beta
+}
+fn other(gamma: usize) {
+    let delta = gamma;
+}
+";
+        let buffer = rust_buffer_with_language(content);
+        let names = buffer.names_in_scope(CharIndex(content.find("alpha +").unwrap()));
+        assert!(names.contains("alpha"));
+        assert!(names.contains("beta"));
+
+        // The locals of a sibling block or function are not in scope
+        assert!(!names.contains("hidden"));
+        assert!(!names.contains("gamma"));
+        assert!(!names.contains("delta"));
+    }
 
-            // Update the buffer, this should cause the batch ID to be changed
-            buffer
-                .update_content("testing", Default::default(), 1)
-                .unwrap();
+    #[test]
+    fn syntax_context_at() {
+        use super::SyntaxContext;
+        use crate::selection::CharIndex;
 
-            // Update the buffer with new highlight spans using the initial batch ID
-            let new_highlighted_spans = HighlightedSpans(
-                [HighlightedSpan {
-                    byte_range: Default::default(),
-                    style_key: StyleKey::Syntax(IndexedHighlightGroup::new(0)),
-                }]
-                .to_vec(),
-            );
-            buffer.update_highlighted_spans(initial_batch_id, new_highlighted_spans.clone());
+        let context_at = |buffer: &Buffer, text: &str| {
+            buffer.syntax_context_at(CharIndex(buffer.content().find(text).unwrap()))
+        };
 
-            // Expect the highlight spans remain the same, because the batch ID is changed
-            assert_eq!(&initial_spans, buffer.highlighted_spans());
-            assert_ne!(&new_highlighted_spans.0, buffer.highlighted_spans())
-        })
+        let buffer = rust_buffer(
+            r###"fn f() { let a = r#"raw"#; let b = "plain"; } // line
+/* block */"###,
+        );
+        assert_eq!(context_at(&buffer, "let a"), SyntaxContext::Code);
+        // `raw_string_literal` is a string node kind specific to Rust
+        assert_eq!(context_at(&buffer, "raw"), SyntaxContext::String);
+        assert_eq!(context_at(&buffer, "plain"), SyntaxContext::String);
+        assert_eq!(context_at(&buffer, "line"), SyntaxContext::Comment);
+        assert_eq!(context_at(&buffer, "block"), SyntaxContext::Comment);
+
+        let buffer = Buffer::new(
+            shared::language::from_extension("go")
+                .unwrap()
+                .tree_sitter_language(),
+            "package main\nvar s = \"interpreted\" // note\n",
+        );
+        assert_eq!(context_at(&buffer, "var"), SyntaxContext::Code);
+        // `interpreted_string_literal` is a string node kind specific to Go
+        assert_eq!(context_at(&buffer, "interpreted"), SyntaxContext::String);
+        assert_eq!(context_at(&buffer, "note"), SyntaxContext::Comment);
+
+        // Without a syntax tree, everything is code
+        let buffer = Buffer::new(None, "\"a\" // b");
+        assert_eq!(context_at(&buffer, "a"), SyntaxContext::Code);
     }
 
-    mod patch_edit {
-        use crate::edit::EditTransaction;
+    mod format_table {
+        use crate::selection::CharIndex;
 
         use super::*;
-        fn run_test(old: &str, new: &str) -> anyhow::Result<EditTransaction> {
-            let mut buffer = Buffer::new(Some(tree_sitter_md::LANGUAGE.into()), old);
-
-            let edit_transaction = buffer.get_edit_transaction(new)?;
 
-            // Apply the edit transaction
+        fn run_test(content: &str, expected: &str) -> anyhow::Result<()> {
+            let mut buffer = Buffer::new(None, content);
+            let range = (CharIndex(0)..CharIndex(content.chars().count())).into();
+            let edit_transaction = buffer.format_table(range).unwrap();
             buffer.apply_edit_transaction(
                 &edit_transaction,
                 SelectionSet::default(),
@@ -1445,109 +7844,482 @@ fn f(
                 true,
                 0,
             )?;
+            assert_eq!(buffer.content(), expected);
+            Ok(())
+        }
 
-            // Expect the content to be the same as the 2nd files
-            pretty_assertions::assert_eq!(buffer.content(), new);
+        #[test]
+        fn markdown() -> anyhow::Result<()> {
+            run_test(
+                "| Name | Age | City |\n|---|:-:|--|\n| Alexander | 7 | Rome |\n|Bo|42|Kuala Lumpur|\n",
+                "| Name      | Age | City         |
+| --------- | :-: | ------------ |
+| Alexander | 7   | Rome         |
+| Bo        | 42  | Kuala Lumpur |
+",
+            )
+        }
 
-            Ok(edit_transaction)
+        #[test]
+        fn csv() -> anyhow::Result<()> {
+            run_test(
+                "name,age\nAlexander,7\nBo,42",
+                "name,      age\nAlexander, 7\nBo,        42",
+            )
         }
 
         #[test]
-        fn empty_line_removal() -> anyhow::Result<()> {
-            let old = r#"
-            let y = "2";
-            let z = 3;
+        fn not_a_table() {
+            let buffer = Buffer::new(None, "foo\nbar | baz");
+            assert!(buffer
+                .format_table((CharIndex(0)..CharIndex(11)).into())
+                .is_none());
+        }
+    }
 
-            let a = 4;
-            "#
-            .trim();
+    #[test]
+    fn revert_single_hunk() -> anyhow::Result<()> {
+        let base = "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\n";
+        let mut buffer = Buffer::new(
+            None,
+            "fn a() {}\nfn B() {}\nfn c() {}\nfn e() {}\nfn f() {}\n",
+        );
+        let hunks = buffer.diff_against_content(base);
+        assert_eq!(hunks.len(), 2);
+
+        let edit_transaction = buffer.revert_hunk(&hunks[1], base)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "fn a() {}\nfn B() {}\nfn c() {}\nfn d() {}\n"
+        );
 
-            let new = r#"
-            let y = "2";
-            let z = 3;
-            let a = 4;
-            "#
-            .trim();
+        let hunks = buffer.diff_against_content(base);
+        assert_eq!(hunks.len(), 1);
+        let edit_transaction = buffer.revert_hunk(&hunks[0], base)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content(), base);
+        assert!(buffer.diff_against_content(base).is_empty());
+        Ok(())
+    }
 
-            run_test(old, new)?;
+    #[test]
+    fn expand_tabs_in_range() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let content = "\tx\n\tab\tc\td";
+        let mut buffer = Buffer::new(None, content);
+        // Only the second line is expanded, where its tabs are not at column boundaries
+        let range = (CharIndex(3)..CharIndex(content.chars().count())).into();
+        let edit_transaction = buffer.expand_tabs_in_range(range, 4)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content(), "\tx\n    ab  c   d");
+
+        // A tab after the start of the range is still aligned to the line's tab stops
+        let mut buffer = Buffer::new(None, "abc\td");
+        let edit_transaction =
+            buffer.expand_tabs_in_range((CharIndex(3)..CharIndex(4)).into(), 8)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content(), "abc     d");
+        Ok(())
+    }
+
+    #[test]
+    fn heuristic_definition() {
+        use crate::selection::CharIndex;
+
+        let buffer =
+            rust_buffer("fn main() {\n    foo();\n}\n\nfn foo() {\n    let mut food = 1;\n}\n");
+        assert_eq!(
+            buffer.heuristic_definition("foo"),
+            Some((CharIndex(29)..CharIndex(32)).into())
+        );
+        assert_eq!(
+            buffer.heuristic_definition("food"),
+            Some((CharIndex(49)..CharIndex(53)).into())
+        );
+        assert_eq!(buffer.heuristic_definition("bar"), None);
+    }
+
+    mod toggle_line_terminator {
+        use super::*;
+
+        fn run_test(content: &str, line: usize, expected: &str) -> anyhow::Result<()> {
+            let mut buffer = rust_buffer(content);
+            let edit_transaction = buffer.toggle_line_terminator(line, ';')?;
+            buffer.apply_edit_transaction(
+                &edit_transaction,
+                SelectionSet::default(),
+                true,
+                true,
+                0,
+            )?;
+            assert_eq!(buffer.content(), expected);
             Ok(())
         }
 
         #[test]
-        fn all_kinds_of_edits() -> anyhow::Result<()> {
-            let old = r#"
-            let x = "1";
-            let y = "2";
-            let z = 3;
-            let a = 4;
-            let b = 4;
-            // This line will be removed
-                "#
-            .trim();
+        fn add_and_remove() -> anyhow::Result<()> {
+            let without = "fn f() {\n    let x = \"a\"\n}\n";
+            let with = "fn f() {\n    let x = \"a\";\n}\n";
+            run_test(without, 1, with)?;
+            run_test(with, 1, without)
+        }
 
-            // Suppose the new content has all kinds of changes:
-            // 1. Replacement (line 1)
-            // 2. Insertion (line 3)
-            // 3. Deletion (last line)
-            let new = r#"
-            let x = "this line is replaced
-                     with multiline content"
-            let y = "2";
-            let z = 3;
-            // This is a newly inserted line
-            let a = 4;
-            let b = 4;
-                            "#
-            .trim();
+        #[test]
+        fn before_trailing_comment() -> anyhow::Result<()> {
+            run_test(
+                "fn f() {\n    foo() // bar;\n}\n",
+                1,
+                "fn f() {\n    foo(); // bar;\n}\n",
+            )
+        }
 
-            let edit_transaction = run_test(old, new)?;
+        #[test]
+        fn not_within_arguments() -> anyhow::Result<()> {
+            let content = "fn f() {\n    foo(\n        a,\n        b\n    );\n}\n";
+            run_test(content, 1, content)?;
+            run_test(content, 3, content)
+        }
+    }
 
-            // Expect there are 3 edits
-            assert_eq!(edit_transaction.edits().len(), 3);
+    #[test]
+    fn debug_render_with_selections() {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let buffer = Buffer::new(None, "hello world\nfoo");
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: selection(6, 6),
+            tail: vec![selection(0, 5), selection(15, 15)],
+        });
+        assert_eq!(
+            buffer.debug_render_with_selections(&selection_set),
+            "[hello] |world\nfoo|"
+        );
+    }
 
-            Ok(())
-        }
+    #[test]
+    fn toggle_char_case_at_selections() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection};
+        use nonempty::NonEmpty;
+
+        let mut buffer = Buffer::new(None, "abc 123");
+        let cursor = |index: usize| Selection::new((CharIndex(index)..CharIndex(index + 1)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: cursor(0),
+            tail: vec![cursor(4)],
+        });
+        let edit_transaction = buffer.toggle_char_case_at_selections(&selection_set);
+        let (selection_set, _) =
+            buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "Abc 123");
+        assert_eq!(
+            buffer.debug_render_with_selections(&selection_set),
+            "A[b]c 1[2]3"
+        );
 
-        #[test]
-        fn empty_line_with_whitespaces() -> anyhow::Result<()> {
-            // The line after `let x = x;` has multiple whitespaces in it
-            let old = r#"
-fn main() {
-    let x = x;
+        let selection_set = SelectionSet::new(NonEmpty::singleton(cursor(0)));
+        let edit_transaction = buffer.toggle_char_case_at_selections(&selection_set);
+        buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "abc 123");
 
-let z = z;
+        let selection_set = SelectionSet::new(NonEmpty::singleton(cursor(7)));
+        let edit_transaction = buffer.toggle_char_case_at_selections(&selection_set);
+        buffer.apply_edit_transaction(&edit_transaction, selection_set, true, true, 0)?;
+        assert_eq!(buffer.content(), "abc 123");
+        Ok(())
+    }
 
-    let y = y;
-}
-"#
-            .trim();
+    #[test]
+    fn save_trims_trailing_blank_lines() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("notes.txt");
+        let content = "a\n\nb\n\n\n\n";
+        std::fs::write(&file_path, content)?;
+        let path = CanonicalizedPath::try_from(file_path)?;
+        let mut buffer = Buffer::from_path(&path, false)?;
+
+        assert!(buffer.trim_trailing_blank_lines(3).is_none());
+        let edit_transaction = buffer.trim_trailing_blank_lines(0).unwrap();
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content(), "a\n\nb\n");
+        buffer.undo(0)?;
+        assert_eq!(buffer.content(), content);
+
+        buffer.set_max_trailing_blank_lines(Some(1));
+        buffer.save(SelectionSet::default(), true, 0)?;
+        // Interior blank lines are untouched
+        assert_eq!(buffer.content(), "a\n\nb\n\n");
+        assert_eq!(path.read()?, "a\n\nb\n\n");
+        Ok(())
+    }
 
-            let new = r#"
-fn main() {
-    let x = x;
+    #[test]
+    fn enclosing_block_indent() {
+        use crate::selection::CharIndex;
+
+        let content =
+            "mod m {\n  struct A {\n      x: u32,\n      y: u32,\n  }\n  struct B {\n  }\n}\n";
+        let buffer = rust_buffer(content);
+        let char_index = |text: &str| CharIndex(content.find(text).unwrap());
+        // Inside a field, a new sibling aligns with the existing fields
+        assert_eq!(
+            buffer.enclosing_block_indent(char_index("u32,\n      y")),
+            "      "
+        );
+        assert_eq!(buffer.enclosing_block_indent(char_index("y:")), "      ");
+        // An empty struct body is indented one level deeper than the struct
+        assert_eq!(
+            buffer.enclosing_block_indent(char_index("{\n  }") + 1),
+            "      "
+        );
+    }
 
-    let z = z;
+    #[test]
+    fn insert_sibling_line() -> anyhow::Result<()> {
+        use crate::components::editor::Direction;
+        use crate::selection::CharIndex;
+
+        let content = "struct A {\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n}\n";
+        let char_index = CharIndex(content.find("Vec").unwrap());
+        let mut buffer = rust_buffer(content);
+
+        let (edit_transaction, selection_set) =
+            buffer.insert_sibling_line(char_index, "z: u8,", Direction::End)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "struct A {\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n    z: u8,\n}\n"
+        );
+        assert_eq!(
+            buffer.debug_render_with_selections(&selection_set),
+            "struct A {\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n    z: u8,|\n}\n"
+        );
 
-    let y = y;
-}
-"#
-            .trim();
+        let (edit_transaction, _) = buffer.insert_sibling_line(
+            CharIndex(content.find("x").unwrap()),
+            "w: u8,",
+            Direction::Start,
+        )?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "struct A {\n    w: u8,\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n    z: u8,\n}\n"
+        );
+        Ok(())
+    }
 
-            run_test(old, new)?;
+    mod word_object {
+        use super::*;
+        use crate::selection::CharIndex;
+
+        fn run_test(char_index: usize, around: bool, expected: &str) -> anyhow::Result<()> {
+            let buffer = Buffer::new(None, "let  foo_bar = café();\nnext");
+            let range = buffer.word_object(CharIndex(char_index), around)?;
+            assert_eq!(buffer.slice(&range)?.to_string(), expected);
             Ok(())
         }
 
         #[test]
-        fn newline_insertion() -> anyhow::Result<()> {
-            run_test("", "\n")?;
-            Ok(())
+        fn inside_word() -> anyhow::Result<()> {
+            run_test(7, false, "foo_bar")?;
+            run_test(16, false, "café")?;
+            run_test(20, false, "();")
         }
 
         #[test]
-        fn newline_removal() -> anyhow::Result<()> {
-            run_test("\n", "")?;
-            Ok(())
+        fn around_word() -> anyhow::Result<()> {
+            run_test(7, true, "foo_bar ")?;
+            // Without trailing whitespace, the leading whitespace is selected
+            run_test(20, true, "();")?;
+            run_test(16, true, " café")
         }
+
+        #[test]
+        fn cursor_on_whitespace() -> anyhow::Result<()> {
+            run_test(3, false, "  ")?;
+            run_test(4, true, "  foo_bar")
+        }
+    }
+
+    #[test]
+    fn spans_of_style() {
+        use crate::{char_index_range::CharIndexRange, selection::CharIndex};
+
+        let mut buffer = rust_buffer("fn é() { let x = 1; }");
+        let style_key =
+            |group: &str| StyleKey::Syntax(IndexedHighlightGroup::from_str(group).unwrap());
+        let span = |byte_range, group: &str| HighlightedSpan {
+            byte_range,
+            style_key: style_key(group),
+        };
+        let range = |start: usize, end: usize| -> CharIndexRange {
+            (CharIndex(start)..CharIndex(end)).into()
+        };
+        let batch_id = buffer.batch_id().clone();
+        buffer.update_highlighted_spans(
+            batch_id,
+            HighlightedSpans(
+                [
+                    span(0..2, "keyword"),
+                    span(3..5, "function"),
+                    span(10..13, "keyword"),
+                ]
+                .to_vec(),
+            ),
+        );
+        // The byte ranges are converted into char ranges, `é` being two bytes long
+        assert_eq!(
+            buffer.spans_of_style(&style_key("keyword")),
+            [range(0, 2), range(9, 12)]
+        );
+        assert_eq!(buffer.spans_of_style(&style_key("function")), [range(3, 4)]);
+    }
+
+    #[test]
+    fn cursors_at_word_occurrences() {
+        use crate::selection::CharIndex;
+
+        let content = "let count = 1;\nlet counter = count + 1;\nprint(count);";
+        let buffer = Buffer::new(None, content);
+        let selection_set = buffer
+            .cursors_at_word_occurrences(CharIndex(content.rfind("count").unwrap() + 2))
+            .unwrap();
+        assert_eq!(
+            buffer.debug_render_with_selections(&selection_set),
+            "let [count] = 1;\nlet counter = [count] + 1;\nprint([count]);"
+        );
+        assert_eq!(selection_set.cursor_index, 2);
+        assert!(buffer.cursors_at_word_occurrences(CharIndex(9)).is_none());
+    }
+
+    #[test]
+    fn tab_stops_for_line() {
+        let buffer = Buffer::new(None, "\tx\nab\tcde\t文\t\n");
+        assert_eq!(buffer.tab_stops_for_line(0, 4), [4]);
+        // The tabs are 2, 1 and 2 columns wide, as `文` spans two columns
+        assert_eq!(buffer.tab_stops_for_line(1, 4), [4, 8, 12]);
+        assert!(buffer.tab_stops_for_line(5, 4).is_empty());
+    }
+
+    #[test]
+    fn normalize_typography() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let content = "She said “it’s done” — finally…";
+        let mut buffer = Buffer::new(None, content);
+        let range = (CharIndex(0)..CharIndex(content.chars().count())).into();
+        let edit_transaction = buffer.normalize_typography(range)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content(), "She said \"it's done\" - finally...");
+
+        // Only strings and comments are normalized in code
+        let content = "fn f() { let a = '’'; let b = \"it’s\"; } // don’t";
+        let mut buffer = rust_buffer(content);
+        let range = (CharIndex(0)..CharIndex(content.chars().count())).into();
+        let edit_transaction = buffer.normalize_typography(range)?;
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "fn f() { let a = '’'; let b = \"it's\"; } // don't"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn extract_to_file() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let content = "mod a {\n    fn helper() {\n        1\n    }\n\n    fn main() {}\n}\n";
+        let mut buffer = rust_buffer(content);
+        let start = content.find("fn helper").unwrap();
+        let end = content.find("\n\n").unwrap();
+        let (new_file, edit_transaction) = buffer.extract_to_file(
+            (CharIndex(start)..CharIndex(end)).into(),
+            "helper.rs".into(),
+            Some("mod helper;"),
+        )?;
+        assert_eq!(new_file.path, std::path::PathBuf::from("helper.rs"));
+        assert_eq!(new_file.content, "fn helper() {\n    1\n}\n");
+
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(
+            buffer.content(),
+            "mod a {\n    mod helper;\n\n    fn main() {}\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn content_hash() -> anyhow::Result<()> {
+        use crate::{
+            edit::{Action, ActionGroup, Edit, EditTransaction},
+            selection::CharIndex,
+        };
+        use ropey::Rope;
+        use std::ops::Range;
+
+        let mut buffer = Buffer::new(None, "hello world");
+        let hash = buffer.content_hash();
+        assert_eq!(buffer.content_hash(), hash);
+
+        let edit = |buffer: &Buffer, range: Range<usize>, new: &str| {
+            EditTransaction::from_action_groups(vec![ActionGroup::new(vec![Action::Edit(
+                Edit::new(
+                    &buffer.rope,
+                    (CharIndex(range.start)..CharIndex(range.end)).into(),
+                    Rope::from_str(new),
+                ),
+            )])])
+        };
+        let edit_transaction = edit(&buffer, 0..5, "howdy");
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        let changed_hash = buffer.content_hash();
+        assert_ne!(changed_hash, hash);
+
+        // Replacing text with itself is a no-op
+        let edit_transaction = edit(&buffer, 6..11, "world");
+        buffer.apply_edit_transaction(&edit_transaction, SelectionSet::default(), true, true, 0)?;
+        assert_eq!(buffer.content_hash(), changed_hash);
+
+        // The hash depends on the content only
+        assert_eq!(
+            Buffer::new(None, "howdy world").content_hash(),
+            changed_hash
+        );
+
+        // It is the checksum that the host application sends
+        assert_eq!(changed_hash, crc32fast::hash("howdy world".as_bytes()));
+
+        // Reordered lines are a different content
+        assert_ne!(
+            Buffer::new(None, "foo\nbar\nbaz\n").content_hash(),
+            Buffer::new(None, "foo\nbaz\nbar\n").content_hash()
+        );
+
+        buffer.update("hello world");
+        assert_eq!(buffer.content_hash(), hash);
+        Ok(())
+    }
+
+    #[test]
+    fn block_statements_range() -> anyhow::Result<()> {
+        use crate::selection::CharIndex;
+
+        let content = "fn f() {\n    let a = 1;\n    a + 1\n}\nfn g() {}\n";
+        let buffer = rust_buffer(content);
+        let range = buffer
+            .block_statements_range(CharIndex(content.find("a + 1").unwrap()))
+            .unwrap();
+        assert_eq!(buffer.slice(&range)?.to_string(), "let a = 1;\n    a + 1");
+        assert_eq!(
+            buffer.block_statements_range(CharIndex(content.find("{}").unwrap())),
+            None
+        );
+        Ok(())
     }
 }
 
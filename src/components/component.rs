@@ -122,7 +122,7 @@ pub trait Component: Any + AnyComponent {
         match event {
             Event::Key(event) => self.handle_key_event(context, event),
             Event::Paste(content) => self.handle_paste_event(content, context),
-            Event::Mouse(event) => self.handle_mouse_event(event),
+            Event::Mouse(event) => self.handle_mouse_event(context, event),
             _ => Ok(Default::default()),
         }
     }
@@ -137,6 +137,7 @@ pub trait Component: Any + AnyComponent {
 
     fn handle_mouse_event(
         &mut self,
+        _context: &Context,
         _event: crossterm::event::MouseEvent,
     ) -> anyhow::Result<Dispatches> {
         Ok(Default::default())
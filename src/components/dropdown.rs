@@ -8,6 +8,7 @@ use itertools::Itertools;
 
 use nucleo_matcher::Utf32Str;
 use shared::{canonicalized_path::CanonicalizedPath, icons::get_icon_config};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use super::suggestive_editor::{Decoration, Info};
 
@@ -18,6 +19,9 @@ pub(crate) struct DropdownItem {
     display: String,
     group: Option<String>,
     info: Option<Info>,
+    /// Secondary text (e.g. a type signature) rendered right-aligned next to `display`,
+    /// unlike `info` which is shown in a separate panel.
+    detail: Option<String>,
     /// Sorting will be based on `rank` if defined, otherwise sorting will be based on `display`
     rank: Option<Box<[usize]>>,
 
@@ -37,6 +41,7 @@ impl DropdownItem {
             display,
             group: Default::default(),
             info: Default::default(),
+            detail: None,
             rank: None,
             on_focused: Default::default(),
             resolved: false,
@@ -51,6 +56,14 @@ impl DropdownItem {
         self.info.as_ref()
     }
 
+    pub(crate) fn set_detail(self, detail: Option<String>) -> Self {
+        Self { detail, ..self }
+    }
+
+    pub(crate) fn detail(&self) -> Option<String> {
+        self.detail.clone()
+    }
+
     pub(crate) fn set_dispatches(self, dispatches: Dispatches) -> DropdownItem {
         Self { dispatches, ..self }
     }
@@ -433,6 +446,11 @@ impl Dropdown {
         DropdownRender {
             title: self.title.clone(),
             content: self.content(),
+            details: self
+                .content_lines()
+                .into_iter()
+                .map(|(_, detail)| detail)
+                .collect(),
             decorations: self.decorations(),
             highlight_line_index: self.current_item_line_index(),
             info: self.current_item().and_then(|item| item.info),
@@ -440,36 +458,41 @@ impl Dropdown {
     }
 
     fn content(&self) -> String {
-        self.filtered_item_groups
-            .iter()
-            .map(|group| {
-                if let Some(group_key) = group.group_key.as_ref() {
-                    let items_len = group.items.len();
-                    let items = group
-                        .items
-                        .iter()
-                        .enumerate()
-                        .map(|(index, item)| {
-                            let content = item.item.display();
-                            let indicator = if index == items_len.saturating_sub(1) {
-                                "└─"
-                            } else {
-                                "├─"
-                            };
-                            format!(" {} {}", indicator, content)
-                        })
-                        .join("\n");
-                    format!("■┬ {}\n{}", group_key, items)
-                } else {
-                    group
-                        .items
-                        .iter()
-                        .map(|item| item.item.display())
-                        .join("\n")
-                }
-            })
-            .collect::<Vec<String>>()
-            .join("\n\n")
+        self.content_lines()
+            .into_iter()
+            .map(|(line, _)| line)
+            .join("\n")
+    }
+
+    /// Each line of the content, along with the detail of the item on that line.
+    fn content_lines(&self) -> Vec<(String, Option<String>)> {
+        let groups = self.filtered_item_groups.iter().map(|group| {
+            if let Some(group_key) = group.group_key.as_ref() {
+                let items_len = group.items.len();
+                let items = group.items.iter().enumerate().map(|(index, item)| {
+                    let content = item.item.display();
+                    let indicator = if index == items_len.saturating_sub(1) {
+                        "└─"
+                    } else {
+                        "├─"
+                    };
+                    (format!(" {} {}", indicator, content), item.item.detail())
+                });
+                std::iter::once((format!("■┬ {}", group_key), None))
+                    .chain(items)
+                    .collect_vec()
+            } else {
+                group
+                    .items
+                    .iter()
+                    .map(|item| (item.item.display(), item.item.detail()))
+                    .collect_vec()
+            }
+        });
+        // Groups are separated by an empty line
+        Itertools::intersperse(groups, vec![(String::new(), None)])
+            .flatten()
+            .collect()
     }
 
     pub(crate) fn apply_movement(&mut self, movement: Movement) {
@@ -1015,16 +1038,90 @@ mod test_dropdown {
             .collect_vec();
         indices == order
     }
+
+    #[test]
+    fn detail_is_right_aligned_and_truncated() {
+        use crate::lsp::completion::CompletionItem;
+
+        let item = DropdownItem::from(CompletionItem {
+            detail: Some("fn(usize) -> bool".to_string()),
+            ..CompletionItem::from_label("is_even".to_string())
+        });
+        assert_eq!(item.detail(), Some("fn(usize) -> bool".to_string()));
+
+        let mut dropdown = Dropdown::new(DropdownConfig {
+            title: "Completion".to_string(),
+        });
+        dropdown.set_items(vec![item, DropdownItem::new("x".to_string())]);
+        let render = dropdown.render();
+        assert_eq!(render.content, " is_even\nx");
+        assert_eq!(
+            render.content_with_details(30),
+            " is_even     fn(usize) -> bool\nx"
+        );
+        // Truncated when narrow
+        assert_eq!(render.content_with_details(16), " is_even fn(usi…\nx");
+        // Omitted when there's no space for it
+        assert_eq!(render.content_with_details(9), " is_even\nx");
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct DropdownRender {
     pub(crate) content: String,
+    /// The detail of each line of `content`, see `DropdownItem::detail`.
+    pub(crate) details: Vec<Option<String>>,
     pub(crate) decorations: Vec<Decoration>,
     pub(crate) title: String,
     pub(crate) highlight_line_index: usize,
     pub(crate) info: Option<Info>,
 }
+impl DropdownRender {
+    /// Returns `content` with the details right-aligned within `width` columns.
+    pub(crate) fn content_with_details(&self, width: usize) -> String {
+        self.content
+            .lines()
+            .zip_longest(&self.details)
+            .filter_map(|pair| match pair {
+                itertools::EitherOrBoth::Both(line, Some(detail)) => {
+                    Some(align_detail(line, detail, width))
+                }
+                itertools::EitherOrBoth::Both(line, None) | itertools::EitherOrBoth::Left(line) => {
+                    Some(line.to_string())
+                }
+                itertools::EitherOrBoth::Right(_) => None,
+            })
+            .join("\n")
+    }
+}
+
+/// Right-aligns `detail` after `line` within `width` columns.
+///
+/// If there's not enough space, `detail` is truncated with an ellipsis,
+/// or omitted if not even one of its characters fits.
+fn align_detail(line: &str, detail: &str, width: usize) -> String {
+    let line_width = line.width();
+    // The line and the detail are separated by at least one space
+    let available_width = width.saturating_sub(line_width + 1);
+    let detail = if detail.width() <= available_width {
+        detail.to_string()
+    } else if available_width >= 2 {
+        let mut truncated_width = 0;
+        let truncated = detail
+            .chars()
+            .take_while(|char| {
+                truncated_width += char.width().unwrap_or_default();
+                truncated_width < available_width
+            })
+            .collect::<String>();
+        format!("{truncated}…")
+    } else {
+        return line.to_string();
+    };
+    let padding = width.saturating_sub(line_width + detail.width());
+    format!("{line}{}{detail}", " ".repeat(padding))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 struct FilteredDropdownItem {
     item_index: u32,
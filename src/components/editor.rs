@@ -8,10 +8,12 @@ use super::{
 };
 use crate::{
     app::{Dimension, Dispatch, ToHostApp},
-    buffer::Buffer,
+    buffer::{Buffer, CharSearch, LineEnding, QuoteStyle},
     components::component::Component,
     context::LocalSearchConfig,
     edit::{Action, ActionGroup, Edit, EditTransaction},
+    git::{DiffMode, GitOperation},
+    grid::DEFAULT_TAB_SIZE,
     list::grep::RegexConfig,
     lsp::completion::PositionalEdit,
     position::Position,
@@ -26,6 +28,7 @@ use crate::{
     clipboard::CopiedTexts,
     context::{Context, GlobalMode, LocalSearchConfigMode, Search},
     lsp::{completion::CompletionItemEdit, process::ResponseContext},
+    quickfix_list::QuickfixListType,
     selection_mode::{self, regex::get_regex},
     surround::EnclosureKind,
     transformation::{MyRegex, Transformation},
@@ -40,6 +43,7 @@ use shared::canonicalized_path::CanonicalizedPath;
 use std::{
     cell::{Ref, RefCell, RefMut},
     ops::{Not, Range},
+    path::PathBuf,
     rc::Rc,
 };
 use DispatchEditor::*;
@@ -123,6 +127,7 @@ impl Component for Editor {
 
     fn handle_mouse_event(
         &mut self,
+        context: &Context,
         mouse_event: crossterm::event::MouseEvent,
     ) -> anyhow::Result<Dispatches> {
         const SCROLL_HEIGHT: usize = 1;
@@ -135,7 +140,9 @@ impl Component for Editor {
                 self.apply_scroll(Direction::End, SCROLL_HEIGHT);
                 Ok(Default::default())
             }
-            MouseEventKind::Down(MouseButton::Left) => Ok(Default::default()),
+            MouseEventKind::Down(MouseButton::Left) => {
+                Ok(self.move_cursor_to_cell(mouse_event.row, mouse_event.column, context))
+            }
             _ => Ok(Default::default()),
         }
     }
@@ -167,7 +174,7 @@ impl Component for Editor {
                 CopiedTexts::new(NonEmpty::singleton(content)),
                 context,
             ),
-            event::event::Event::Mouse(event) => self.handle_mouse_event(event),
+            event::event::Event::Mouse(event) => self.handle_mouse_event(context, event),
             _ => Ok(Default::default()),
         }
     }
@@ -343,6 +350,203 @@ impl Component for Editor {
             ExecuteCompletion { replacement, edit } => {
                 return self.execute_completion(replacement, edit, context)
             }
+            InlineVariable => return self.inline_variable(context),
+            WrapInCall { function_name } => return self.wrap_in_call(&function_name, context),
+            InsertFunctionStub => return self.insert_function_stub(context),
+            SortObjectKeys => return self.sort_object_keys(context),
+            ExpandSelectionSemantic => return self.select_semantic(true, context),
+            ShrinkSelectionSemantic => return self.select_semantic(false, context),
+            SelectAllStrings => {
+                return Ok(self.select_ranges_of_kind("Select all strings", STRING_KINDS, context))
+            }
+            SelectAllComments => {
+                return Ok(self.select_ranges_of_kind(
+                    "Select all comments",
+                    COMMENT_KINDS,
+                    context,
+                ))
+            }
+            SwapWithNextSibling => return self.swap_with_next_sibling(context),
+            TransposeSelections => return self.transpose_selections(context),
+            GoToFirstError => return Ok(self.go_to_first_error(context)),
+            GoToNearestCleanLine => return self.go_to_nearest_clean_line(context),
+            SelectWordChanges => return self.select_word_changes(context),
+            SelectLineChanges => return self.select_line_changes(context),
+            SelectWhitespaceIssues => return Ok(self.select_whitespace_issues(context)),
+            GoToPercent(percent) => return self.go_to_percent(percent, context),
+            FindChar(search) => return Ok(self.find_char(search, context)),
+            RepeatCharSearch { reversed } => return Ok(self.repeat_char_search(reversed, context)),
+            SelectStringContent => return Ok(self.select_string_content(context)),
+            ConvertQuotes(style) => return self.convert_quotes(style, context),
+            SelectStatement => return Ok(self.select_statement(context)),
+            MoveNodeOutOfBlock => return self.move_node_out_of_block(context),
+            ToggleReadOnly => return Ok(self.toggle_read_only()),
+            TailFile => return self.tail_file(context),
+            SetMaxLines(max_lines) => self.buffer_mut().set_max_lines(max_lines),
+            ExtendSelectionByChar(direction) => {
+                return Ok(self.extend_selection_by_char(direction, context))
+            }
+            CollapseToPrimary => {
+                let selection_set = self.selection_set.clone().collapse_to_primary();
+                return Ok(self.update_selection_set(selection_set, true, context));
+            }
+            CollapseToLast => {
+                let selection_set = self.selection_set.clone().collapse_to_last();
+                return Ok(self.update_selection_set(selection_set, true, context));
+            }
+            RotateSelectionContents(direction) => {
+                return self.rotate_selection_contents(direction, context)
+            }
+            InsertCounter { format } => return self.insert_counter(&format, context),
+            RetainSelectionsMatching { pattern, invert } => {
+                return self.retain_selections_matching(&pattern, invert, context)
+            }
+            SplitSelections { separator } => return self.split_selections(&separator, context),
+            MergeSelections => {
+                let selection_set = self.selection_set.clone().merge_selections();
+                return Ok(self.update_selection_set(selection_set, true, context));
+            }
+            SelectInsideBrackets => return Ok(self.select_inside_brackets(context)),
+            GoToBlankLine(direction) => return self.go_to_blank_line(direction, context),
+            SelectMatchingLines { pattern } => {
+                return self.select_matching_lines(&pattern, context)
+            }
+            DeleteMatchingLines { pattern, invert } => {
+                return self.delete_matching_lines(&pattern, invert, context)
+            }
+            ToggleRecording => return Ok(self.toggle_recording()),
+            ReplayRecording => return self.replay_recording(context),
+            ReplayRecordingAtSelections => return self.replay_recording_at_selections(context),
+            CopyAsHtml {
+                use_system_clipboard,
+            } => {
+                return Ok(Dispatches::one(Dispatch::SetClipboardContent {
+                    use_system_clipboard,
+                    copied_texts: CopiedTexts::new(NonEmpty::singleton(
+                        self.buffer().to_html(context.theme()),
+                    )),
+                }))
+            }
+            CopyAsAnsi {
+                use_system_clipboard,
+            } => {
+                return Ok(Dispatches::one(Dispatch::SetClipboardContent {
+                    use_system_clipboard,
+                    copied_texts: CopiedTexts::new(NonEmpty::singleton(
+                        self.buffer().to_ansi(context.theme()),
+                    )),
+                }))
+            }
+            FoldSelectedLines => return self.fold_selected_lines(),
+            UnfoldSelectedLines => {
+                let line_ranges = self
+                    .buffer()
+                    .selections_to_line_ranges(&self.selection_set)?;
+                for lines in line_ranges {
+                    self.buffer_mut().unfold(lines)
+                }
+            }
+            ApplyPatch {
+                use_system_clipboard,
+            } => return self.apply_patch(use_system_clipboard, context),
+            ToggleLineEndings => return self.toggle_line_endings(context),
+            SelectionsToQuickfixList => return self.selections_to_quickfix_list(),
+            ReindentPasted => return self.reindent_pasted(context),
+            SelectCommentParagraph => {
+                let range = self
+                    .buffer()
+                    .comment_paragraph_range(self.get_cursor_char_index());
+                return Ok(self.select_ranges(
+                    "Comment paragraph",
+                    range.into_iter().collect(),
+                    context,
+                ));
+            }
+            MarkConflicts => return self.mark_conflicts(),
+            CopyBetweenMarks {
+                use_system_clipboard,
+            } => return self.copy_between_marks(use_system_clipboard),
+            CycleMarks => {
+                let mark = self
+                    .buffer()
+                    .cycle_marks(&self.selection_set.primary_selection().extended_range());
+                return Ok(self.select_ranges("Cycle marks", mark.into_iter().collect(), context));
+            }
+            KeepOnlySelections {
+                separate_with_newlines,
+            } => {
+                let edit_transaction = self
+                    .buffer()
+                    .keep_only_selections(&self.selection_set, separate_with_newlines);
+                return self.apply_edit_transaction(edit_transaction, context);
+            }
+            ShiftIndent { levels } => return self.shift_indent(levels, context),
+            PreviewFormat => return Ok(self.preview_format(context)),
+            ShowNamesInScope => {
+                let names = self
+                    .buffer()
+                    .names_in_scope(self.get_cursor_char_index())
+                    .into_iter()
+                    .sorted()
+                    .join("\n");
+                return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                    "Names in scope".to_string(),
+                    names,
+                ))));
+            }
+            FormatTable => {
+                let edit_transaction = self
+                    .buffer()
+                    .format_table(self.selection_set.primary_selection().extended_range());
+                return self.apply_edit_transaction_or_show_info(
+                    "Align table",
+                    edit_transaction,
+                    context,
+                );
+            }
+            RevertHunk => return self.revert_hunk(context),
+            ExpandTabs => return self.expand_tabs(context),
+            GoToHeuristicDefinition => return self.go_to_heuristic_definition(context),
+            ToggleLineTerminator(terminator) => {
+                return self.toggle_line_terminator(terminator, context)
+            }
+            ToggleCharCase => {
+                let edit_transaction = self
+                    .buffer()
+                    .toggle_char_case_at_selections(&self.selection_set);
+                return self.apply_edit_transaction(edit_transaction, context);
+            }
+            InsertSiblingLine(direction) => return self.insert_sibling_line(direction, context),
+            SelectWordObject { around } => return self.select_word_object(around, context),
+            SelectSameHighlight => return self.select_same_highlight(context),
+            SelectWordOccurrences => {
+                let Some(selection_set) = self
+                    .buffer()
+                    .cursors_at_word_occurrences(self.get_cursor_char_index())
+                else {
+                    return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                        "Word occurrences".to_string(),
+                        "The cursor is not on a word.".to_string(),
+                    ))));
+                };
+                return Ok(self.update_selection_set(
+                    selection_set.set_mode(SelectionMode::Custom),
+                    true,
+                    context,
+                ));
+            }
+            NormalizeTypography => return self.normalize_typography(context),
+            ExtractToFile { path } => return self.extract_to_file(path, context),
+            SelectBlockStatements => {
+                let range = self
+                    .buffer()
+                    .block_statements_range(self.get_cursor_char_index());
+                return Ok(self.select_ranges(
+                    "Block contents",
+                    range.into_iter().collect(),
+                    context,
+                ));
+            }
         }
         Ok(Default::default())
     }
@@ -373,6 +577,8 @@ impl Clone for Editor {
             normal_mode_override: self.normal_mode_override.clone(),
             reveal: self.reveal.clone(),
             visible_line_ranges: Default::default(),
+            recording_origin: None,
+            recorded_edits: self.recorded_edits.clone(),
         }
     }
 }
@@ -401,6 +607,11 @@ pub(crate) struct Editor {
     /// This is only used when Ki is running as an embedded component,
     /// for example, inside VS Code.
     visible_line_ranges: Option<Vec<Range<usize>>>,
+
+    /// The cursor when the current recording of edits started.
+    recording_origin: Option<CharIndex>,
+    /// The last recorded edits, along with the cursor when their recording started.
+    recorded_edits: Option<(CharIndex, Vec<EditTransaction>)>,
 }
 
 #[derive(Default)]
@@ -603,7 +814,11 @@ impl Editor {
         self.apply_dispatches(
             context,
             [
-                SetContent(render.content.clone()),
+                // Reference: NEED_TO_REDUCE_WIDTH_BY_1
+                SetContent(
+                    render
+                        .content_with_details((self.dimension().width as usize).saturating_sub(1)),
+                ),
                 SetDecorations(render.decorations.clone()),
                 SelectLineAt(render.highlight_line_index),
             ]
@@ -629,6 +844,8 @@ impl Editor {
             normal_mode_override: None,
             reveal: None,
             visible_line_ranges: Default::default(),
+            recording_origin: None,
+            recorded_edits: None,
         }
     }
 
@@ -651,6 +868,8 @@ impl Editor {
             normal_mode_override: None,
             reveal: None,
             visible_line_ranges: Default::default(),
+            recording_origin: None,
+            recorded_edits: None,
         };
 
         // Select the first line of the file
@@ -1108,19 +1327,7 @@ impl Editor {
         let buffer = self.buffer();
         let edit_transactions = self.selection_set.map(move |selection| {
             let rope = buffer.rope();
-            let current_char_index = selection.range().start;
-            let word_start = rope
-                .chars()
-                .enumerate()
-                .take(current_char_index.0)
-                .collect_vec()
-                .iter()
-                .rev()
-                .take_while(|(_, c)| c.is_alphanumeric() || c == &'_' || c == &'-')
-                .last()
-                .map(|(char_index, _)| CharIndex(*char_index))
-                .unwrap_or(current_char_index);
-            let range: CharIndexRange = (word_start..selection.range().start).into();
+            let range = buffer.completion_replace_range(selection.range().start);
             let start = range.start;
             EditTransaction::from_action_groups(
                 [ActionGroup::new(
@@ -1512,6 +1719,70 @@ impl Editor {
         self.apply_edit_transaction(edit_transaction, context)
     }
 
+    /// Pairs that are closed automatically, none if the buffer has no language.
+    fn auto_pairs(&self) -> &'static [(char, char)] {
+        self.buffer()
+            .language()
+            .map(|language| language.auto_pairs())
+            .unwrap_or_default()
+    }
+
+    /// Inserts a typed character.
+    ///
+    /// If it opens one of the `auto_pairs`, its closing character is inserted after the cursor,
+    /// unless the cursor is followed by a character that is not a whitespace or a closing character.
+    /// Typing a closing character that is already after the cursor moves over it instead.
+    pub(crate) fn insert_char(&mut self, c: char, context: &Context) -> anyhow::Result<Dispatches> {
+        let pairs = self.auto_pairs();
+        let is_closing = |char: char| pairs.iter().any(|(_, close)| *close == char);
+        let edit_transaction = EditTransaction::from_action_groups(
+            self.selection_set
+                .map(|selection| {
+                    let buffer = self.buffer();
+                    let cursor = selection.to_char_index(&Direction::End);
+                    let next = buffer.rope().get_char(cursor.0);
+                    let previous = cursor
+                        .0
+                        .checked_sub(1)
+                        .and_then(|index| buffer.rope().get_char(index));
+                    let new_char_index = selection.extended_range().start + 1;
+                    let select = Action::Select(
+                        selection
+                            .clone()
+                            .set_range((new_char_index..new_char_index).into()),
+                    );
+                    if next == Some(c) && is_closing(c) {
+                        return ActionGroup::new([select].to_vec());
+                    }
+                    let new = match pairs.iter().find(|(open, _)| *open == c) {
+                        Some((open, close))
+                            if next.map_or(true, |next| next.is_whitespace() || is_closing(next))
+                                // Quotes are not paired right after a word, e.g. `don't`
+                                && (open != close
+                                    || !previous.is_some_and(char::is_alphanumeric)) =>
+                        {
+                            format!("{open}{close}")
+                        }
+                        _ => c.to_string(),
+                    };
+                    ActionGroup::new(
+                        [
+                            Action::Edit(Edit::new(
+                                buffer.rope(),
+                                (cursor..cursor).into(),
+                                Rope::from_str(&new),
+                            )),
+                            select,
+                        ]
+                        .to_vec(),
+                    )
+                })
+                .into(),
+        );
+
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
     pub(crate) fn get_request_params(&self) -> Option<RequestParams> {
         let position = self.get_cursor_position().ok()?;
         self.path().map(|path| RequestParams {
@@ -2483,6 +2754,14 @@ impl Editor {
         Ok(())
     }
 
+    /// Pairs that can surround the selections, according to the language of the buffer.
+    pub(crate) fn surround_pairs(&self) -> &'static [(char, char)] {
+        self.buffer()
+            .language()
+            .unwrap_or_default()
+            .surround_pairs()
+    }
+
     pub(crate) fn surround(
         &mut self,
         open: String,
@@ -2585,6 +2864,33 @@ impl Editor {
         format!("{: <5}x{}", selection_mode, cursor_count)
     }
 
+    /// The size of the primary selection for the status line, or `None` if it is empty.
+    pub(crate) fn display_selection_size(&self) -> Option<String> {
+        let selection = self.selection_set.primary_selection();
+        let buffer = self.buffer();
+        let (bytes, chars) = buffer.selection_size(selection);
+        if chars == 0 {
+            return None;
+        }
+        let width = buffer.display_width(selection.extended_range(), DEFAULT_TAB_SIZE);
+        Some(format!("{chars} chars, {bytes} bytes, {width} cols"))
+    }
+
+    /// The 1-based line and on-screen column of the cursor for the status line,
+    /// where the column accounts for soft wrapping and tabs.
+    pub(crate) fn display_cursor_position(&self) -> Option<String> {
+        let buffer = self.buffer();
+        let char_index = self.get_cursor_char_index();
+        let line = buffer.char_to_line(char_index).ok()?;
+        // Reference: the line number column of `Grid::render_content`
+        let line_number_width = buffer.len_lines().max(1).to_string().len() + 1;
+        let wrap_width = (self.dimension().width as usize).saturating_sub(line_number_width);
+        let (_, column) = buffer
+            .display_cursor_position(char_index, wrap_width, DEFAULT_TAB_SIZE)
+            .ok()?;
+        Some(format!("{}:{}", line + 1, column + 1))
+    }
+
     pub(crate) fn visible_line_range(&self) -> Range<usize> {
         self.visible_line_range_given_scroll_offset_and_height(
             self.scroll_offset,
@@ -3812,8 +4118,893 @@ impl Editor {
         self.buffer_mut()
             .update_content(new_content, current_selection_set, last_visible_line)
     }
+
+    /// Apply `edit_transaction` if there is one, otherwise show that `action`
+    /// is not applicable at the current selection.
+    fn apply_edit_transaction_or_show_info(
+        &mut self,
+        action: &str,
+        edit_transaction: Option<EditTransaction>,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        match edit_transaction {
+            Some(edit_transaction) => self.apply_edit_transaction(edit_transaction, context),
+            None => Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                action.to_string(),
+                "Not applicable at the current selection.".to_string(),
+            )))),
+        }
+    }
+
+    fn inline_variable(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self.buffer().inline_variable(self.get_cursor_char_index());
+        self.apply_edit_transaction_or_show_info("Inline variable", edit_transaction, context)
+    }
+
+    fn wrap_in_call(
+        &mut self,
+        function_name: &str,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let edit_transactions = self
+            .selection_set
+            .map(|selection| {
+                let buffer = self.buffer();
+                if buffer.selection_is_whitespace(selection) {
+                    Ok(None)
+                } else {
+                    buffer
+                        .wrap_in_call(selection.extended_range(), function_name)
+                        .map(Some)
+                }
+            })
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect_vec();
+        let edit_transaction =
+            (!edit_transactions.is_empty()).then(|| EditTransaction::merge(edit_transactions));
+        self.apply_edit_transaction_or_show_info("Wrap in call", edit_transaction, context)
+    }
+
+    fn sort_object_keys(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .sort_object_keys(self.selection_set.primary_selection());
+        self.apply_edit_transaction_or_show_info("Sort object keys", edit_transaction, context)
+    }
+
+    fn insert_function_stub(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .insert_function_stub(self.get_cursor_char_index());
+        self.apply_edit_transaction_or_show_info("Function stub", edit_transaction, context)
+    }
+
+    /// Expand (or shrink) the primary selection to the next selection range provided by the LSP,
+    /// which are requested if the buffer has none, or to the next syntax node meanwhile.
+    fn select_semantic(&mut self, expand: bool, context: &Context) -> anyhow::Result<Dispatches> {
+        let range = self.selection_set.primary_selection().extended_range();
+        let (new_range, has_selection_ranges) = {
+            let buffer = self.buffer();
+            let new_range = if expand {
+                buffer.expand_selection_semantic(range)
+            } else {
+                buffer.shrink_selection_semantic(range)
+            };
+            (new_range, buffer.has_selection_ranges())
+        };
+        let request = if has_selection_ranges {
+            Dispatches::default()
+        } else {
+            Dispatches::one(Dispatch::RequestSelectionRanges)
+        };
+        let Some(new_range) = new_range else {
+            return Ok(request);
+        };
+        let selection_set = SelectionSet::new(NonEmpty::singleton(
+            self.selection_set
+                .primary_selection()
+                .clone()
+                .set_range(new_range),
+        ))
+        .set_mode(SelectionMode::Custom);
+        Ok(self
+            .update_selection_set(selection_set, true, context)
+            .chain(request))
+    }
+
+    /// Replace the selections with one selection per range,
+    /// or show an info if there are no ranges.
+    fn select_ranges(
+        &mut self,
+        action: &str,
+        ranges: Vec<CharIndexRange>,
+        context: &Context,
+    ) -> Dispatches {
+        let Some(ranges) = NonEmpty::from_vec(ranges) else {
+            return Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                action.to_string(),
+                "No matches found.".to_string(),
+            )));
+        };
+        let selection_set =
+            SelectionSet::new(ranges.map(Selection::new)).set_mode(SelectionMode::Custom);
+        self.update_selection_set(selection_set, true, context)
+    }
+
+    fn select_ranges_of_kind(
+        &mut self,
+        action: &str,
+        kinds: &[&str],
+        context: &Context,
+    ) -> Dispatches {
+        let ranges = self.buffer().ranges_of_kind(kinds);
+        self.select_ranges(action, ranges, context)
+    }
+
+    fn swap_with_next_sibling(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .swap_with_next_sibling(self.selection_set.primary_selection());
+        self.apply_edit_transaction_or_show_info(
+            "Swap with next sibling",
+            edit_transaction,
+            context,
+        )
+    }
+
+    fn transpose_selections(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .transpose_selections(self.selection_set.clone());
+        self.apply_edit_transaction_or_show_info("Transpose selections", edit_transaction, context)
+    }
+
+    fn go_to_first_error(&mut self, context: &Context) -> Dispatches {
+        let Some(diagnostic) = self
+            .buffer()
+            .first_diagnostic_of_at_least(lsp_types::DiagnosticSeverity::ERROR)
+        else {
+            return Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "First error".to_string(),
+                "No errors found.".to_string(),
+            )));
+        };
+        self.select_ranges("First error", vec![diagnostic.range], context)
+    }
+
+    fn go_to_nearest_clean_line(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let line = {
+            let buffer = self.buffer();
+            buffer.nearest_clean_line(buffer.char_to_line(self.get_cursor_char_index())?)
+        };
+        self.select_line_at(line, context)
+    }
+
+    /// Select the words changed since the last save, and list the changes in an info.
+    fn select_word_changes(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let word_changes = self.buffer().word_diff()?;
+        let info = word_changes
+            .iter()
+            .map(|change| format!("{:?} → {:?}", change.old, change.new))
+            .join("\n");
+        let ranges = word_changes
+            .into_iter()
+            .map(|change| change.range)
+            .collect_vec();
+        let dispatches = self.select_ranges("Word changes", ranges, context);
+        Ok(if info.is_empty() {
+            dispatches
+        } else {
+            dispatches.append(Dispatch::ShowEditorInfo(Info::new(
+                "Word changes".to_string(),
+                info,
+            )))
+        })
+    }
+
+    /// Select the lines changed since the last save.
+    /// Lines that were only deleted are selected as an empty range where they used to be.
+    fn select_line_changes(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let ranges = {
+            let buffer = self.buffer();
+            let Some(path) = buffer.path() else {
+                return Ok(Default::default());
+            };
+            buffer
+                .diff_against(&Buffer::new(None, &path.read()?))
+                .into_iter()
+                .map(|hunk| hunk.range)
+                .collect_vec()
+        };
+        Ok(self.select_ranges("Line changes", ranges, context))
+    }
+
+    fn select_whitespace_issues(&mut self, context: &Context) -> Dispatches {
+        let ranges = self
+            .buffer()
+            .whitespace_issue_spans()
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect_vec();
+        self.select_ranges("Whitespace issues", ranges, context)
+    }
+
+    fn go_to_percent(&mut self, percent: usize, context: &Context) -> anyhow::Result<Dispatches> {
+        let line = {
+            let buffer = self.buffer();
+            buffer.char_to_line(buffer.char_index_at_percent(percent as f32))?
+        };
+        self.select_line_at(line, context)
+    }
+
+    /// Select the character found by `search` within the current line, like Vim's `f` and `t`.
+    /// The search is remembered for `repeat_char_search`.
+    fn find_char(&mut self, search: CharSearch, context: &Context) -> Dispatches {
+        let cursor = self.get_cursor_char_index();
+        let found = self.buffer_mut().search_char(cursor, search);
+        self.select_found_char(found, context)
+    }
+
+    /// Repeat the last `find_char`, like Vim's `;` (or `,` if `reversed`).
+    fn repeat_char_search(&mut self, reversed: bool, context: &Context) -> Dispatches {
+        let cursor = self.get_cursor_char_index();
+        let found = if reversed {
+            self.buffer().repeat_char_search_reversed(cursor)
+        } else {
+            self.buffer().repeat_char_search(cursor)
+        };
+        self.select_found_char(found, context)
+    }
+
+    fn select_found_char(&mut self, found: Option<CharIndex>, context: &Context) -> Dispatches {
+        let ranges = found
+            .map(|index| (index..index + 1).into())
+            .into_iter()
+            .collect_vec();
+        self.select_ranges("Find char", ranges, context)
+    }
+
+    /// Select the content of the string under the cursor, and show its unescaped value.
+    fn select_string_content(&mut self, context: &Context) -> Dispatches {
+        let cursor = self.get_cursor_char_index();
+        let (range, unescaped) = {
+            let buffer = self.buffer();
+            (
+                buffer.string_content_range(cursor),
+                buffer.unescaped_string_at(cursor),
+            )
+        };
+        self.select_ranges("String content", range.into_iter().collect_vec(), context)
+            .append_some(unescaped.map(|unescaped| {
+                Dispatch::ShowEditorInfo(Info::new("String content".to_string(), unescaped))
+            }))
+    }
+
+    fn convert_quotes(
+        &mut self,
+        style: QuoteStyle,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .convert_quotes(self.get_cursor_char_index(), style);
+        self.apply_edit_transaction_or_show_info("Convert quotes", edit_transaction, context)
+    }
+
+    fn select_statement(&mut self, context: &Context) -> Dispatches {
+        let range = self.buffer().statement_range(self.get_cursor_char_index());
+        self.select_ranges("Statement", range.into_iter().collect_vec(), context)
+    }
+
+    fn move_node_out_of_block(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .move_node_out_of_block(self.selection_set.primary_selection());
+        self.apply_edit_transaction_or_show_info("Move out of block", edit_transaction, context)
+    }
+
+    fn toggle_read_only(&mut self) -> Dispatches {
+        if self.buffer().is_binary() {
+            return Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Read-only".to_string(),
+                "Binary files are always read-only.".to_string(),
+            )));
+        }
+        let read_only = !self.buffer().read_only();
+        self.buffer_mut().set_read_only(read_only);
+        Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+            "Read-only".to_string(),
+            if read_only {
+                "The buffer is now read-only.".to_string()
+            } else {
+                "The buffer is now editable.".to_string()
+            },
+        )))
+    }
+
+    /// Append the content added to the file after the current content of the buffer,
+    /// without marking the buffer as dirty, which is meant for following log files.
+    fn tail_file(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let info = |content: &str| {
+            Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Tail file".to_string(),
+                content.to_string(),
+            ))))
+        };
+        let Some(path) = self.buffer().path() else {
+            return info("The buffer is not backed by a file.");
+        };
+        let file_content = path.read()?;
+        let content = self.buffer().content();
+        let Some(appended) = file_content
+            .find(&content)
+            .map(|start| &file_content[start + content.len()..])
+        else {
+            return info("The file was modified other than by appending.");
+        };
+        if appended.is_empty() {
+            return info("No new content.");
+        }
+        let last_visible_line = self.last_visible_line(context);
+        self.buffer_mut().append(appended, last_visible_line)?;
+        self.clamp(context)?;
+        Ok(self.get_document_did_change_dispatch())
+    }
+
+    /// Select the character rendered at the terminal cell (`row`, `column`),
+    /// which is outside of the title and the line numbers of this editor.
+    fn move_cursor_to_cell(&mut self, row: u16, column: u16, context: &Context) -> Dispatches {
+        let Position {
+            line: top,
+            column: left,
+        } = self.rectangle.origin;
+        let line_number_width = self.buffer().len_lines().max(1).to_string().len() + 1;
+        let (Some(row), Some(column)) = (
+            (row as usize).checked_sub(top + self.window_title_height(context) as usize),
+            (column as usize).checked_sub(left + line_number_width),
+        ) else {
+            return Default::default();
+        };
+        let char_index = self.buffer().char_index_from_cell(
+            row,
+            column,
+            self.scroll_offset as usize,
+            DEFAULT_TAB_SIZE,
+        );
+        let end = (char_index + 1).min(CharIndex(self.buffer().len_chars()));
+        self.select_ranges("Click", vec![(char_index..end).into()], context)
+    }
+
+    /// Move the active end of every selection by a character, keeping the other end as the anchor.
+    fn extend_selection_by_char(&mut self, direction: Direction, context: &Context) -> Dispatches {
+        let selection_set = {
+            let buffer = self.buffer();
+            let selections = self
+                .selection_set
+                .map(|selection| buffer.extend_selection(selection, direction.clone(), 1));
+            self.selection_set.clone().set_selections(selections)
+        };
+        self.update_selection_set(selection_set, true, context)
+    }
+
+    fn rotate_selection_contents(
+        &mut self,
+        direction: Direction,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let edit_transaction = self
+            .buffer()
+            .rotate_selection_contents(self.selection_set.clone(), direction)?;
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    fn insert_counter(&mut self, format: &str, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction =
+            self.buffer()
+                .insert_counter(self.selection_set.clone(), 1, 1, format)?;
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    fn retain_selections_matching(
+        &mut self,
+        pattern: &str,
+        invert: bool,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let regex = regex::Regex::new(pattern)?;
+        let Some(selection_set) =
+            self.buffer()
+                .retain_selections_matching(self.selection_set.clone(), &regex, invert)
+        else {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Retain selections".to_string(),
+                "No selections would remain.".to_string(),
+            ))));
+        };
+        Ok(self.update_selection_set(selection_set, true, context))
+    }
+
+    fn split_selections(
+        &mut self,
+        separator: &str,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let separator = regex::Regex::new(separator)?;
+        let Some(selection_set) =
+            self.buffer()
+                .split_selections(self.selection_set.clone(), &separator, false)
+        else {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Split selections".to_string(),
+                "Nothing left after splitting.".to_string(),
+            ))));
+        };
+        Ok(self.update_selection_set(selection_set, true, context))
+    }
+
+    /// Select the content of the innermost bracket pair enclosing the cursor.
+    fn select_inside_brackets(&mut self, context: &Context) -> Dispatches {
+        let ranges = self
+            .buffer()
+            .surrounding_brackets(self.get_cursor_char_index())
+            .map(|(open, close)| (open.end..close.start).into())
+            .into_iter()
+            .collect_vec();
+        self.select_ranges("Inside brackets", ranges, context)
+    }
+
+    /// Select the nearest blank (or whitespace-only) line in `direction`, like Vim's `{` and `}`.
+    fn go_to_blank_line(
+        &mut self,
+        direction: Direction,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let line = {
+            let buffer = self.buffer();
+            let from = buffer.char_to_line(self.get_cursor_char_index())?;
+            match direction {
+                Direction::Start => buffer.previous_blank_line(from, true),
+                Direction::End => buffer.next_blank_line(from, true),
+            }
+        };
+        self.select_line_at(line, context)
+    }
+
+    fn select_matching_lines(
+        &mut self,
+        pattern: &str,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let regex = regex::Regex::new(pattern)?;
+        let ranges = {
+            let buffer = self.buffer();
+            buffer
+                .matching_lines(&regex)
+                .into_iter()
+                .map(|(line, text)| {
+                    let start = buffer.line_to_char(line)?;
+                    Ok((start..start + text.chars().count()).into())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        Ok(self.select_ranges("Matching lines", ranges, context))
+    }
+
+    fn delete_matching_lines(
+        &mut self,
+        pattern: &str,
+        invert: bool,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let regex = regex::Regex::new(pattern)?;
+        let edit_transaction = self.buffer().delete_matching_lines(&regex, invert);
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    fn toggle_recording(&mut self) -> Dispatches {
+        let info = if let Some(origin) = self.recording_origin.take() {
+            let recorded = self.buffer_mut().stop_recording();
+            let info = format!("Recorded {} edit(s).", recorded.len());
+            self.recorded_edits = Some((origin, recorded));
+            info
+        } else {
+            self.buffer_mut().start_recording();
+            self.recording_origin = Some(self.get_cursor_char_index());
+            "Recording edits.".to_string()
+        };
+        Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+            "Record edits".to_string(),
+            info,
+        )))
+    }
+
+    /// Replay the recorded edits relative to the cursor,
+    /// as if the recording had started at the cursor.
+    fn replay_recording(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let Some((origin, recorded)) = self.recorded_edits.clone() else {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Replay edits".to_string(),
+                "No edits were recorded.".to_string(),
+            ))));
+        };
+        let offset = self.get_cursor_char_index().0 as isize - origin.0 as isize;
+        let last_visible_line = self.last_visible_line(context);
+        let selection_set = self.buffer_mut().replay(
+            &recorded,
+            offset,
+            self.selection_set.clone(),
+            last_visible_line,
+        )?;
+        Ok(self
+            .update_selection_set(selection_set, false, context)
+            .chain(self.get_document_did_change_dispatch()))
+    }
+
+    /// Replay the recorded edits once at each selection, as one undo step.
+    fn replay_recording_at_selections(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let Some((_, recorded)) = self.recorded_edits.clone() else {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Replay edits".to_string(),
+                "No edits were recorded.".to_string(),
+            ))));
+        };
+        let edit_transaction = self
+            .buffer()
+            .replay_edits(&recorded, self.selection_set.clone())?;
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// The 0-based line range spanned by the primary selection.
+    /// Fold the lines of each selection, where selections on adjacent lines share one fold.
+    fn fold_selected_lines(&mut self) -> anyhow::Result<Dispatches> {
+        let line_ranges = self
+            .buffer()
+            .selections_to_line_ranges(&self.selection_set)?;
+        let errors = line_ranges
+            .into_iter()
+            .filter_map(|lines| self.buffer_mut().fold(lines).err())
+            .map(|error| error.to_string())
+            .collect_vec();
+        Ok(if errors.is_empty() {
+            Dispatches::default()
+        } else {
+            Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Fold".to_string(),
+                errors.join("\n"),
+            )))
+        })
+    }
+
+    /// Apply the unified diff in the clipboard to this buffer.
+    fn apply_patch(
+        &mut self,
+        use_system_clipboard: bool,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let Some(copied_texts) = context.get_clipboard_content(use_system_clipboard, 0)? else {
+            return Ok(Default::default());
+        };
+        let last_visible_line = self.last_visible_line(context);
+        let selection_set = self.selection_set.clone();
+        let (selection_set, _) = self.buffer_mut().apply_unified_diff(
+            &copied_texts.get(0),
+            selection_set,
+            last_visible_line,
+        )?;
+        Ok(self
+            .update_selection_set(selection_set, false, context)
+            .chain(self.get_document_did_change_dispatch()))
+    }
+
+    /// Convert every line terminator to CRLF if the buffer uses LF, and to LF otherwise.
+    fn toggle_line_endings(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = {
+            let buffer = self.buffer();
+            let line_ending = match buffer.line_ending() {
+                LineEnding::Lf => LineEnding::Crlf,
+                LineEnding::Crlf => LineEnding::Lf,
+            };
+            buffer.normalize_line_endings(line_ending, &self.selection_set)
+        };
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Save the selections as a quickfix list, where each item shows its selected text.
+    fn selections_to_quickfix_list(&self) -> anyhow::Result<Dispatches> {
+        let buffer = self.buffer();
+        let items = buffer.selections_to_quickfix(&self.selection_set, |selection| {
+            buffer
+                .slice(&selection.extended_range())
+                .map(|slice| slice.to_string())
+                .unwrap_or_default()
+        })?;
+        Ok(Dispatches::one(Dispatch::SetQuickfixList(
+            QuickfixListType::Items(items),
+        )))
+    }
+
+    /// Re-indent the lines of each selection (e.g. the text just pasted) to fit where they are,
+    /// keeping their relative indentation.
+    fn reindent_pasted(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = EditTransaction::merge(
+            self.selection_set
+                .map(|selection| self.buffer().reindent_pasted(selection.extended_range()))
+                .into_iter()
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        );
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Replace the marks with the regions delimited by git conflict markers.
+    fn mark_conflicts(&mut self) -> anyhow::Result<Dispatches> {
+        let ranges = {
+            let buffer = self.buffer();
+            let content = buffer.content();
+            lazy_regex::regex!(r"(?ms)^<{7}.*?^>{7}[^\n]*")
+                .find_iter(&content)
+                .map(|conflict| buffer.byte_range_to_char_index_range(&conflict.range()))
+                .collect::<anyhow::Result<Vec<_>>>()?
+        };
+        if ranges.is_empty() {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Mark conflicts".to_string(),
+                "No conflicts found.".to_string(),
+            ))));
+        }
+        self.buffer_mut().set_marks_from_ranges(ranges, true);
+        Ok(Dispatches::default())
+    }
+
+    /// Copy the text from the start of the first mark to the end of the last mark,
+    /// where the marks are ordered by their creation.
+    fn copy_between_marks(&self, use_system_clipboard: bool) -> anyhow::Result<Dispatches> {
+        let buffer = self.buffer();
+        let marks = buffer.marks();
+        let [first, .., last] = marks.as_slice() else {
+            return Ok(Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Copy between marks".to_string(),
+                "At least two marks are needed.".to_string(),
+            ))));
+        };
+        let text = buffer.text_between_marks(*first, *last)?;
+        Ok(Dispatches::one(Dispatch::SetClipboardContent {
+            use_system_clipboard,
+            copied_texts: CopiedTexts::new(NonEmpty::singleton(text.to_string())),
+        }))
+    }
+
+    /// Shift the lines of the selections by `levels` of indentation,
+    /// leaving the lines within multiline strings untouched.
+    fn shift_indent(&mut self, levels: i32, context: &Context) -> anyhow::Result<Dispatches> {
+        let indent_unit = INDENT_CHAR.to_string().repeat(INDENT_WIDTH);
+        let edit_transaction = {
+            let buffer = self.buffer();
+            EditTransaction::merge(
+                buffer
+                    .selections_to_line_ranges(&self.selection_set)?
+                    .into_iter()
+                    .map(|lines| {
+                        buffer.shift_indent(
+                            buffer.line_range_to_char_index_range(lines)?,
+                            levels,
+                            &indent_unit,
+                        )
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )
+        };
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Select the ranges that the formatter would change on save, and list the changes in an info,
+    /// without applying them.
+    fn preview_format(&mut self, context: &Context) -> Dispatches {
+        let info = |content: &str| {
+            Dispatches::one(Dispatch::ShowEditorInfo(Info::new(
+                "Preview format".to_string(),
+                content.to_string(),
+            )))
+        };
+        let Some(changes) = self.buffer().format_preview() else {
+            return info("No formatter is configured, or formatting failed.");
+        };
+        if changes.is_empty() {
+            return info("Already formatted.");
+        }
+        let content = {
+            let buffer = self.buffer();
+            changes
+                .iter()
+                .map(|(range, new)| {
+                    let old = buffer
+                        .slice(range)
+                        .map(|rope| rope.to_string())
+                        .unwrap_or_default();
+                    format!("{old:?} → {new:?}")
+                })
+                .join("\n")
+        };
+        let ranges = changes.into_iter().map(|(range, _)| range).collect_vec();
+        self.select_ranges("Preview format", ranges, context)
+            .append(Dispatch::ShowEditorInfo(Info::new(
+                "Preview format".to_string(),
+                content,
+            )))
+    }
+
+    /// Reverts the lines changed since the last commit that contain the cursor,
+    /// leaving the other changes of the file untouched.
+    fn revert_hunk(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let Some(path) = self.buffer().path() else {
+            return Ok(Default::default());
+        };
+        let base = path.content_at_last_commit(
+            &DiffMode::UnstagedAgainstCurrentBranch,
+            &context.current_working_directory().try_into()?,
+        )?;
+        let cursor = self.get_cursor_char_index();
+        let edit_transaction = self
+            .buffer()
+            .diff_against_content(&base)
+            .into_iter()
+            .find(|hunk| {
+                hunk.range.start == cursor || (hunk.range.start..hunk.range.end).contains(&cursor)
+            })
+            .map(|hunk| self.buffer().revert_hunk(&hunk, &base))
+            .transpose()?;
+        self.apply_edit_transaction_or_show_info("Revert hunk", edit_transaction, context)
+    }
+
+    /// Replace the tabs within the selections (e.g. pasted content) with spaces,
+    /// up to the next tab stop.
+    fn expand_tabs(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = EditTransaction::merge(
+            self.selection_set
+                .map(|selection| {
+                    self.buffer()
+                        .expand_tabs_in_range(selection.extended_range(), DEFAULT_TAB_SIZE)
+                })
+                .into_iter()
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        );
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Select the definition of the name selected by the primary selection,
+    /// found by looking for the name after a definition keyword of the language.
+    fn go_to_heuristic_definition(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let range = {
+            let buffer = self.buffer();
+            let name = buffer
+                .slice(&self.selection_set.primary_selection().extended_range())?
+                .to_string();
+            buffer.heuristic_definition(name.trim())
+        };
+        Ok(self.select_ranges("Heuristic definition", range.into_iter().collect(), context))
+    }
+
+    /// Add or remove `terminator` at the end of the code of each selected line.
+    fn toggle_line_terminator(
+        &mut self,
+        terminator: char,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let edit_transaction = {
+            let buffer = self.buffer();
+            EditTransaction::merge(
+                self.selection_set
+                    .map(|selection| buffer.char_to_line(selection.extended_range().start))
+                    .into_iter()
+                    .collect::<anyhow::Result<Vec<_>>>()?
+                    .into_iter()
+                    .unique()
+                    .map(|line| buffer.toggle_line_terminator(line, terminator))
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )
+        };
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Open a new line above or below the current statement, indented like its siblings
+    /// in the enclosing block, and enter insert mode on it.
+    fn insert_sibling_line(
+        &mut self,
+        direction: Direction,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let (edit_transaction, selection_set) =
+            self.buffer()
+                .insert_sibling_line(self.get_cursor_char_index(), "", direction)?;
+        let dispatches = self.apply_edit_transaction(edit_transaction, context)?;
+        self.set_selection_set(selection_set, context);
+        Ok(dispatches.chain(self.enter_insert_mode(Direction::Start, context)?))
+    }
+
+    /// Select the word at the start of each selection, like Vim's `iw` and `aw`.
+    fn select_word_object(
+        &mut self,
+        around: bool,
+        context: &Context,
+    ) -> anyhow::Result<Dispatches> {
+        let ranges = self
+            .selection_set
+            .map(|selection| self.buffer().word_object(selection.range().start, around))
+            .into_iter()
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(self.select_ranges(
+            if around { "Around word" } else { "Inside word" },
+            ranges,
+            context,
+        ))
+    }
+
+    /// Select every span highlighted like the one under the cursor,
+    /// to see which text a highlight group applies to.
+    fn select_same_highlight(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let ranges = {
+            let buffer = self.buffer();
+            let byte = buffer.char_to_byte(self.get_cursor_char_index())?;
+            buffer
+                .highlighted_spans()
+                .iter()
+                .find(|span| span.byte_range.contains(&byte))
+                .map(|span| buffer.spans_of_style(&span.style_key))
+                .unwrap_or_default()
+        };
+        Ok(self.select_ranges("Same highlight", ranges, context))
+    }
+
+    /// Replace the curly quotes, dashes and ellipses within the selections
+    /// (e.g. text pasted from the web) with their ASCII counterparts.
+    fn normalize_typography(&mut self, context: &Context) -> anyhow::Result<Dispatches> {
+        let edit_transaction = EditTransaction::merge(
+            self.selection_set
+                .map(|selection| {
+                    self.buffer()
+                        .normalize_typography(selection.extended_range())
+                })
+                .into_iter()
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        );
+        self.apply_edit_transaction(edit_transaction, context)
+    }
+
+    /// Move the lines of the primary selection into a new file at `path`.
+    fn extract_to_file(&mut self, path: PathBuf, context: &Context) -> anyhow::Result<Dispatches> {
+        if path.exists() {
+            return Err(anyhow::anyhow!(
+                "The path \"{}\" already exists",
+                path.display()
+            ));
+        }
+        let (new_file, edit_transaction) = self.buffer().extract_to_file(
+            self.selection_set.primary_selection().extended_range(),
+            path,
+            None,
+        )?;
+        if let Some(parent) = new_file.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&new_file.path, new_file.content)?;
+        self.apply_edit_transaction(edit_transaction, context)
+    }
 }
 
+/// Syntax node kinds of string literals across the supported grammars.
+const STRING_KINDS: &[&str] = &[
+    "string_literal",
+    "raw_string_literal",
+    "interpreted_string_literal",
+    "string",
+    "template_string",
+];
+
+/// Syntax node kinds of comments across the supported grammars.
+const COMMENT_KINDS: &[&str] = &["comment", "line_comment", "block_comment"];
+
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub(crate) enum ViewAlignment {
     Top,
@@ -3948,6 +5139,119 @@ pub(crate) enum DispatchEditor {
         replacement: String,
         edit: Option<CompletionItemEdit>,
     },
+    InlineVariable,
+    WrapInCall {
+        function_name: String,
+    },
+    InsertFunctionStub,
+    ExpandSelectionSemantic,
+    ShrinkSelectionSemantic,
+    SelectAllStrings,
+    SelectAllComments,
+    SwapWithNextSibling,
+    TransposeSelections,
+    GoToFirstError,
+    GoToNearestCleanLine,
+    SelectWordChanges,
+    SelectWhitespaceIssues,
+    /// Select the line at the given percentage (0 to 100) of the file
+    GoToPercent(usize),
+    FindChar(CharSearch),
+    RepeatCharSearch {
+        reversed: bool,
+    },
+    SelectStringContent,
+    ConvertQuotes(QuoteStyle),
+    SelectStatement,
+    MoveNodeOutOfBlock,
+    ToggleReadOnly,
+    /// Append the content added to the file since it was loaded, like `tail -f`
+    TailFile,
+    /// Drop the oldest lines beyond this count when tailing the file
+    SetMaxLines(Option<usize>),
+    ExtendSelectionByChar(Direction),
+    CollapseToPrimary,
+    CollapseToLast,
+    RotateSelectionContents(Direction),
+    /// Insert 1, 2, 3, ... at the selections, formatted by `format` (see `Buffer::insert_counter`)
+    InsertCounter {
+        format: String,
+    },
+    /// Keep (or with `invert`, remove) the selections whose text matches `pattern`
+    RetainSelectionsMatching {
+        pattern: String,
+        invert: bool,
+    },
+    /// Split each selection at the matches of `separator`, dropping empty pieces
+    SplitSelections {
+        separator: String,
+    },
+    MergeSelections,
+    SelectInsideBrackets,
+    GoToBlankLine(Direction),
+    /// Select every line matching `pattern`, like Vim's `:g/pattern/`
+    SelectMatchingLines {
+        pattern: String,
+    },
+    /// Delete every line matching (or with `invert`, not matching) `pattern`, as one undo step
+    DeleteMatchingLines {
+        pattern: String,
+        invert: bool,
+    },
+    /// Start recording the edits, or stop and keep the recording for `ReplayRecording`
+    ToggleRecording,
+    ReplayRecording,
+    ReplayRecordingAtSelections,
+    /// Copy the content, highlighted with the current theme, as a standalone HTML document
+    CopyAsHtml {
+        use_system_clipboard: bool,
+    },
+    /// Copy the content, highlighted with the current theme, as ANSI-colored text
+    CopyAsAnsi {
+        use_system_clipboard: bool,
+    },
+    /// Fold the lines of the primary selection, hiding all of them but the first
+    FoldSelectedLines,
+    UnfoldSelectedLines,
+    SortObjectKeys,
+    SelectLineChanges,
+    ApplyPatch {
+        use_system_clipboard: bool,
+    },
+    ToggleLineEndings,
+    SelectionsToQuickfixList,
+    ReindentPasted,
+    SelectCommentParagraph,
+    MarkConflicts,
+    CopyBetweenMarks {
+        use_system_clipboard: bool,
+    },
+    CycleMarks,
+    KeepOnlySelections {
+        separate_with_newlines: bool,
+    },
+    ShiftIndent {
+        levels: i32,
+    },
+    PreviewFormat,
+    ShowNamesInScope,
+    FormatTable,
+    RevertHunk,
+    ExpandTabs,
+    GoToHeuristicDefinition,
+    ToggleLineTerminator(char),
+    ToggleCharCase,
+    InsertSiblingLine(Direction),
+    SelectWordObject {
+        around: bool,
+    },
+    SelectSameHighlight,
+    SelectWordOccurrences,
+    NormalizeTypography,
+    ExtractToFile {
+        path: PathBuf,
+    },
+    SelectBlockStatements,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -3965,5 +5269,5 @@ impl std::fmt::Display for SurroundKind {
     }
 }
 
-const INDENT_CHAR: char = ' ';
-const INDENT_WIDTH: usize = 4;
+pub(crate) const INDENT_CHAR: char = ' ';
+pub(crate) const INDENT_WIDTH: usize = 4;
@@ -117,13 +117,13 @@ pub(crate) const KEYMAP_SURROUND: KeyboardMeaningLayout = [
 
 pub(crate) const KEYMAP_SPACE: KeyboardMeaningLayout = [
     [
-        QSave, SaveA, Explr, _____, KeybL, /****/ _____, RevlS, RevlC, RevlM, _____,
+        QSave, SaveA, Explr, Rfctr, KeybL, /****/ _____, RevlS, RevlC, RevlM, _____,
     ],
     [
         Theme, Symbl, Buffr, File_, GitFC, /****/ _____, LHovr, LCdAc, Pipe_, _____,
     ],
     [
-        UndoT, _____, _____, _____, TSNSx, /****/ _____, LRnme, _____, _____, SHelp,
+        UndoT, Selct, Edits, _____, TSNSx, /****/ Navig, LRnme, _____, _____, SHelp,
     ],
 ];
 
@@ -714,6 +714,14 @@ pub(crate) enum Meaning {
     TSNSx,
     /// LSP Code Actions
     LCdAc,
+    /// Refactor
+    Rfctr,
+    /// Selections
+    Selct,
+    /// Edits
+    Edits,
+    /// Navigate
+    Navig,
     /// Pick Buffers
     Buffr,
     /// Set Replacement
@@ -7,6 +7,7 @@ use itertools::Itertools;
 
 use crate::{
     app::{Dispatch, Dispatches, FilePickerKind, Scope},
+    buffer::QuoteStyle,
     components::editor::Movement,
     context::{Context, LocalSearchConfigMode, Search},
     git::DiffMode,
@@ -628,6 +629,18 @@ impl Editor {
                 "Help".to_string(),
                 Dispatch::ToEditor(DispatchEditor::ShowHelp),
             ),
+            Keymap::new_extended(
+                "shift+left",
+                "Extend ←".to_string(),
+                "Extend selection backward by a character".to_string(),
+                Dispatch::ToEditor(ExtendSelectionByChar(Direction::Start)),
+            ),
+            Keymap::new_extended(
+                "shift+right",
+                "Extend →".to_string(),
+                "Extend selection forward by a character".to_string(),
+                Dispatch::ToEditor(ExtendSelectionByChar(Direction::End)),
+            ),
             #[cfg(unix)]
             Keymap::new("ctrl+z", "Suspend".to_string(), Dispatch::Suspend),
         ]
@@ -766,7 +779,7 @@ impl Editor {
         {
             Ok(dispatches)
         } else if let KeyCode::Char(c) = event.code {
-            return self.insert(&c.to_string(), context);
+            return self.insert_char(c, context);
         } else {
             Ok(Default::default())
         }
@@ -905,7 +918,10 @@ impl Editor {
         self.normal_mode_keymap_legend_config(
             context,
             "Extend",
-            Some(extend_mode_normal_mode_override(context)),
+            Some(extend_mode_normal_mode_override(
+                self.surround_pairs(),
+                context,
+            )),
         )
     }
     pub(crate) fn transform_keymap_legend_config(&self, context: &Context) -> KeymapLegendConfig {
@@ -1073,7 +1089,7 @@ impl Editor {
                             .keyboard_layout_kind()
                             .get_space_keymap(&Meaning::QNSav),
                         "Quit No Save".to_string(),
-                        Dispatch::QuitAll,
+                        Dispatch::ConfirmQuitAll,
                     ),
                     Keymap::new(
                         context
@@ -1090,6 +1106,10 @@ impl Editor {
                         {
                             let cursor_char_index = self.get_cursor_char_index();
                             Dispatch::RequestCodeAction {
+                                range: self
+                                    .buffer()
+                                    .selection_to_lsp_range(self.selection_set.primary_selection())
+                                    .ok(),
                                 diagnostics: self
                                     .buffer()
                                     .diagnostics()
@@ -1105,6 +1125,34 @@ impl Editor {
                             }
                         },
                     ),
+                    Keymap::new(
+                        context
+                            .keyboard_layout_kind()
+                            .get_space_keymap(&Meaning::Rfctr),
+                        "Refactor".to_string(),
+                        Dispatch::ShowKeymapLegend(self.refactor_keymap_legend_config()),
+                    ),
+                    Keymap::new(
+                        context
+                            .keyboard_layout_kind()
+                            .get_space_keymap(&Meaning::Selct),
+                        "Selections".to_string(),
+                        Dispatch::ShowKeymapLegend(self.selections_keymap_legend_config()),
+                    ),
+                    Keymap::new(
+                        context
+                            .keyboard_layout_kind()
+                            .get_space_keymap(&Meaning::Edits),
+                        "Edits".to_string(),
+                        Dispatch::ShowKeymapLegend(self.edits_keymap_legend_config()),
+                    ),
+                    Keymap::new(
+                        context
+                            .keyboard_layout_kind()
+                            .get_space_keymap(&Meaning::Navig),
+                        "Navigate".to_string(),
+                        Dispatch::ShowKeymapLegend(self.navigate_keymap_legend_config()),
+                    ),
                     Keymap::new(
                         context
                             .keyboard_layout_kind()
@@ -1148,6 +1196,457 @@ impl Editor {
         }
     }
 
+    pub(crate) fn refactor_keymap_legend_config(&self) -> KeymapLegendConfig {
+        KeymapLegendConfig {
+            title: "Refactor".to_string(),
+            body: KeymapLegendBody::Mnemonic(Keymaps::new(&[
+                Keymap::new(
+                    "i",
+                    "Inline variable".to_string(),
+                    Dispatch::ToEditor(InlineVariable),
+                ),
+                Keymap::new(
+                    "f",
+                    "Function stub".to_string(),
+                    Dispatch::ToEditor(InsertFunctionStub),
+                ),
+                Keymap::new(
+                    "w",
+                    "Wrap in call".to_string(),
+                    Dispatch::OpenWrapInCallPrompt,
+                ),
+                Keymap::new(
+                    "s",
+                    "Swap with next sibling".to_string(),
+                    Dispatch::ToEditor(SwapWithNextSibling),
+                ),
+                Keymap::new(
+                    "o",
+                    "Move out of block".to_string(),
+                    Dispatch::ToEditor(MoveNodeOutOfBlock),
+                ),
+                Keymap::new(
+                    "k",
+                    "Sort object keys".to_string(),
+                    Dispatch::ToEditor(SortObjectKeys),
+                ),
+                Keymap::new(
+                    "n",
+                    "Names in scope".to_string(),
+                    Dispatch::ToEditor(ShowNamesInScope),
+                ),
+                Keymap::new(
+                    "x",
+                    "Extract to file".to_string(),
+                    Dispatch::OpenExtractToFilePrompt,
+                ),
+            ])),
+        }
+    }
+
+    pub(crate) fn edits_keymap_legend_config(&self) -> KeymapLegendConfig {
+        KeymapLegendConfig {
+            title: "Edits".to_string(),
+            body: KeymapLegendBody::Mnemonic(Keymaps::new(&[
+                Keymap::new(
+                    "t",
+                    "Transpose selections".to_string(),
+                    Dispatch::ToEditor(TransposeSelections),
+                ),
+                Keymap::new(
+                    "'",
+                    "Single quotes".to_string(),
+                    Dispatch::ToEditor(ConvertQuotes(QuoteStyle::Single)),
+                ),
+                Keymap::new(
+                    "\"",
+                    "Double quotes".to_string(),
+                    Dispatch::ToEditor(ConvertQuotes(QuoteStyle::Double)),
+                ),
+                Keymap::new(
+                    "`",
+                    "Backticks".to_string(),
+                    Dispatch::ToEditor(ConvertQuotes(QuoteStyle::Backtick)),
+                ),
+                Keymap::new(
+                    "r",
+                    "Toggle read-only".to_string(),
+                    Dispatch::ToEditor(ToggleReadOnly),
+                ),
+                Keymap::new("a", "Tail file".to_string(), Dispatch::ToEditor(TailFile)),
+                Keymap::new("l", "Limit lines".to_string(), Dispatch::OpenMaxLinesPrompt),
+                Keymap::new(
+                    ".",
+                    "Rotate contents forward".to_string(),
+                    Dispatch::ToEditor(RotateSelectionContents(Direction::End)),
+                ),
+                Keymap::new(
+                    ",",
+                    "Rotate contents backward".to_string(),
+                    Dispatch::ToEditor(RotateSelectionContents(Direction::Start)),
+                ),
+                Keymap::new(
+                    "n",
+                    "Insert counter".to_string(),
+                    Dispatch::OpenInsertCounterPrompt,
+                ),
+                Keymap::new(
+                    "d",
+                    "Delete matching lines".to_string(),
+                    Dispatch::OpenDeleteMatchingLinesPrompt { invert: false },
+                ),
+                Keymap::new(
+                    "v",
+                    "Keep matching lines".to_string(),
+                    Dispatch::OpenDeleteMatchingLinesPrompt { invert: true },
+                ),
+                Keymap::new(
+                    "q",
+                    "Toggle recording".to_string(),
+                    Dispatch::ToEditor(ToggleRecording),
+                ),
+                Keymap::new(
+                    "@",
+                    "Replay recording".to_string(),
+                    Dispatch::ToEditor(ReplayRecording),
+                ),
+                Keymap::new(
+                    "e",
+                    "Replay at each selection".to_string(),
+                    Dispatch::ToEditor(ReplayRecordingAtSelections),
+                ),
+                Keymap::new(
+                    "h",
+                    "Copy as HTML".to_string(),
+                    Dispatch::ToEditor(CopyAsHtml {
+                        use_system_clipboard: true,
+                    }),
+                ),
+                Keymap::new(
+                    "c",
+                    "Copy as ANSI".to_string(),
+                    Dispatch::ToEditor(CopyAsAnsi {
+                        use_system_clipboard: true,
+                    }),
+                ),
+                Keymap::new(
+                    "p",
+                    "Apply patch".to_string(),
+                    Dispatch::ToEditor(ApplyPatch {
+                        use_system_clipboard: true,
+                    }),
+                ),
+                Keymap::new(
+                    "f",
+                    "Toggle CRLF/LF".to_string(),
+                    Dispatch::ToEditor(ToggleLineEndings),
+                ),
+                Keymap::new(
+                    "i",
+                    "Reindent pasted".to_string(),
+                    Dispatch::ToEditor(ReindentPasted),
+                ),
+                Keymap::new(
+                    "m",
+                    "Copy between marks".to_string(),
+                    Dispatch::ToEditor(CopyBetweenMarks {
+                        use_system_clipboard: false,
+                    }),
+                ),
+                Keymap::new(
+                    "k",
+                    "Keep only selections".to_string(),
+                    Dispatch::ToEditor(KeepOnlySelections {
+                        separate_with_newlines: true,
+                    }),
+                ),
+                Keymap::new(
+                    ">",
+                    "Shift indentation".to_string(),
+                    Dispatch::OpenShiftIndentPrompt,
+                ),
+                Keymap::new(
+                    "o",
+                    "Preview format".to_string(),
+                    Dispatch::ToEditor(PreviewFormat),
+                ),
+                Keymap::new(
+                    "b",
+                    "Align table".to_string(),
+                    Dispatch::ToEditor(FormatTable),
+                ),
+                Keymap::new(
+                    "u",
+                    "Revert hunk".to_string(),
+                    Dispatch::ToEditor(RevertHunk),
+                ),
+                Keymap::new(
+                    "x",
+                    "Expand tabs".to_string(),
+                    Dispatch::ToEditor(ExpandTabs),
+                ),
+                Keymap::new(
+                    ";",
+                    "Toggle semicolon".to_string(),
+                    Dispatch::ToEditor(ToggleLineTerminator(';')),
+                ),
+                Keymap::new(
+                    "~",
+                    "Toggle char case".to_string(),
+                    Dispatch::ToEditor(ToggleCharCase),
+                ),
+                Keymap::new(
+                    "s",
+                    "Sibling line below".to_string(),
+                    Dispatch::ToEditor(InsertSiblingLine(Direction::End)),
+                ),
+                Keymap::new(
+                    "alt+s",
+                    "Sibling line above".to_string(),
+                    Dispatch::ToEditor(InsertSiblingLine(Direction::Start)),
+                ),
+                Keymap::new(
+                    "y",
+                    "Normalize typography".to_string(),
+                    Dispatch::ToEditor(NormalizeTypography),
+                ),
+            ])),
+        }
+    }
+
+    pub(crate) fn navigate_keymap_legend_config(&self) -> KeymapLegendConfig {
+        KeymapLegendConfig {
+            title: "Navigate".to_string(),
+            body: KeymapLegendBody::Mnemonic(Keymaps::new(&[
+                Keymap::new(
+                    "e",
+                    "First error".to_string(),
+                    Dispatch::ToEditor(GoToFirstError),
+                ),
+                Keymap::new(
+                    "c",
+                    "Nearest clean line".to_string(),
+                    Dispatch::ToEditor(GoToNearestCleanLine),
+                ),
+                Keymap::new(
+                    "p",
+                    "Go to percentage".to_string(),
+                    Dispatch::OpenGoToPercentPrompt,
+                ),
+                Keymap::new(
+                    "f",
+                    "Find char forward".to_string(),
+                    Dispatch::OpenFindCharPrompt {
+                        direction: Direction::End,
+                        inclusive: true,
+                    },
+                ),
+                Keymap::new(
+                    "t",
+                    "Till char forward".to_string(),
+                    Dispatch::OpenFindCharPrompt {
+                        direction: Direction::End,
+                        inclusive: false,
+                    },
+                ),
+                Keymap::new(
+                    "alt+f",
+                    "Find char backward".to_string(),
+                    Dispatch::OpenFindCharPrompt {
+                        direction: Direction::Start,
+                        inclusive: true,
+                    },
+                ),
+                Keymap::new(
+                    "alt+t",
+                    "Till char backward".to_string(),
+                    Dispatch::OpenFindCharPrompt {
+                        direction: Direction::Start,
+                        inclusive: false,
+                    },
+                ),
+                Keymap::new(
+                    ";",
+                    "Repeat char search".to_string(),
+                    Dispatch::ToEditor(RepeatCharSearch { reversed: false }),
+                ),
+                Keymap::new(
+                    ",",
+                    "Repeat char search reversed".to_string(),
+                    Dispatch::ToEditor(RepeatCharSearch { reversed: true }),
+                ),
+                Keymap::new(
+                    "}",
+                    "Next blank line".to_string(),
+                    Dispatch::ToEditor(GoToBlankLine(Direction::End)),
+                ),
+                Keymap::new(
+                    "{",
+                    "Previous blank line".to_string(),
+                    Dispatch::ToEditor(GoToBlankLine(Direction::Start)),
+                ),
+                Keymap::new(
+                    "o",
+                    "Outline (by indentation)".to_string(),
+                    Dispatch::OpenIndentOutlinePrompt,
+                ),
+                Keymap::new(
+                    "z",
+                    "Fold lines".to_string(),
+                    Dispatch::ToEditor(FoldSelectedLines),
+                ),
+                Keymap::new(
+                    "u",
+                    "Unfold lines".to_string(),
+                    Dispatch::ToEditor(UnfoldSelectedLines),
+                ),
+                Keymap::new(
+                    "m",
+                    "Next mark (by creation)".to_string(),
+                    Dispatch::ToEditor(CycleMarks),
+                ),
+                Keymap::new(
+                    "d",
+                    "Definition (heuristic)".to_string(),
+                    Dispatch::ToEditor(GoToHeuristicDefinition),
+                ),
+            ])),
+        }
+    }
+
+    pub(crate) fn selections_keymap_legend_config(&self) -> KeymapLegendConfig {
+        KeymapLegendConfig {
+            title: "Selections".to_string(),
+            body: KeymapLegendBody::Mnemonic(Keymaps::new(&[
+                Keymap::new(
+                    "e",
+                    "Expand (semantic)".to_string(),
+                    Dispatch::ToEditor(ExpandSelectionSemantic),
+                ),
+                Keymap::new(
+                    "s",
+                    "Shrink (semantic)".to_string(),
+                    Dispatch::ToEditor(ShrinkSelectionSemantic),
+                ),
+                Keymap::new(
+                    "q",
+                    "Select all strings".to_string(),
+                    Dispatch::ToEditor(SelectAllStrings),
+                ),
+                Keymap::new(
+                    "/",
+                    "Select all comments".to_string(),
+                    Dispatch::ToEditor(SelectAllComments),
+                ),
+                Keymap::new(
+                    "w",
+                    "Word changes".to_string(),
+                    Dispatch::ToEditor(SelectWordChanges),
+                ),
+                Keymap::new(
+                    "t",
+                    "Whitespace issues".to_string(),
+                    Dispatch::ToEditor(SelectWhitespaceIssues),
+                ),
+                Keymap::new(
+                    "i",
+                    "String content".to_string(),
+                    Dispatch::ToEditor(SelectStringContent),
+                ),
+                Keymap::new(
+                    "m",
+                    "Statement".to_string(),
+                    Dispatch::ToEditor(SelectStatement),
+                ),
+                Keymap::new(
+                    "p",
+                    "Keep primary only".to_string(),
+                    Dispatch::ToEditor(CollapseToPrimary),
+                ),
+                Keymap::new(
+                    "l",
+                    "Keep last only".to_string(),
+                    Dispatch::ToEditor(CollapseToLast),
+                ),
+                Keymap::new(
+                    "k",
+                    "Keep matching".to_string(),
+                    Dispatch::OpenRetainSelectionsPrompt { invert: false },
+                ),
+                Keymap::new(
+                    "x",
+                    "Remove matching".to_string(),
+                    Dispatch::OpenRetainSelectionsPrompt { invert: true },
+                ),
+                Keymap::new(
+                    ",",
+                    "Split by".to_string(),
+                    Dispatch::OpenSplitSelectionsPrompt,
+                ),
+                Keymap::new(
+                    "u",
+                    "Merge overlapping".to_string(),
+                    Dispatch::ToEditor(MergeSelections),
+                ),
+                Keymap::new(
+                    "b",
+                    "Inside brackets".to_string(),
+                    Dispatch::ToEditor(SelectInsideBrackets),
+                ),
+                Keymap::new(
+                    "g",
+                    "Matching lines".to_string(),
+                    Dispatch::OpenSelectMatchingLinesPrompt,
+                ),
+                Keymap::new(
+                    "c",
+                    "Line changes".to_string(),
+                    Dispatch::ToEditor(SelectLineChanges),
+                ),
+                Keymap::new(
+                    "f",
+                    "To quickfix list".to_string(),
+                    Dispatch::ToEditor(SelectionsToQuickfixList),
+                ),
+                Keymap::new(
+                    "o",
+                    "Comment paragraph".to_string(),
+                    Dispatch::ToEditor(SelectCommentParagraph),
+                ),
+                Keymap::new(
+                    "n",
+                    "Mark conflicts".to_string(),
+                    Dispatch::ToEditor(MarkConflicts),
+                ),
+                Keymap::new(
+                    "v",
+                    "Inside word".to_string(),
+                    Dispatch::ToEditor(SelectWordObject { around: false }),
+                ),
+                Keymap::new(
+                    "a",
+                    "Around word".to_string(),
+                    Dispatch::ToEditor(SelectWordObject { around: true }),
+                ),
+                Keymap::new(
+                    "h",
+                    "Same highlight".to_string(),
+                    Dispatch::ToEditor(SelectSameHighlight),
+                ),
+                Keymap::new(
+                    "r",
+                    "Word occurrences".to_string(),
+                    Dispatch::ToEditor(SelectWordOccurrences),
+                ),
+                Keymap::new(
+                    "j",
+                    "Block contents".to_string(),
+                    Dispatch::ToEditor(SelectBlockStatements),
+                ),
+            ])),
+        }
+    }
+
     fn search_current_selection_keymap(
         &self,
         context: &Context,
@@ -1449,6 +1948,7 @@ pub(crate) struct KeymapOverride {
 }
 
 fn generate_enclosures_keymaps(
+    is_offered: impl Fn(&EnclosureKind) -> bool,
     get_dispatch: impl Fn(EnclosureKind) -> Dispatch,
     context: &Context,
 ) -> Keymaps {
@@ -1463,6 +1963,7 @@ fn generate_enclosures_keymaps(
             (Meaning::BckTk, EnclosureKind::Backticks),
         ]
         .into_iter()
+        .filter(|(_, enclosure)| is_offered(enclosure))
         .map(|(meaning, enclosure)| {
             let (open, close) = enclosure.open_close_symbols_str();
             Keymap::new(
@@ -1475,7 +1976,30 @@ fn generate_enclosures_keymaps(
     )
 }
 
-pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeOverride {
+/// Only the enclosures of `surround_pairs` are offered,
+/// for example, angular brackets are not offered for prose.
+pub(crate) fn surround_keymap_legend_config(
+    surround_pairs: &[(char, char)],
+    context: &Context,
+) -> KeymapLegendConfig {
+    KeymapLegendConfig {
+        title: "Surround".to_string(),
+
+        body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
+            |enclosure| surround_pairs.contains(&enclosure.open_close_symbols()),
+            |enclosure| {
+                let (open, close) = enclosure.open_close_symbols_str();
+                Dispatch::ToEditor(Surround(open.to_string(), close.to_string()))
+            },
+            context,
+        )),
+    }
+}
+
+pub(crate) fn extend_mode_normal_mode_override(
+    surround_pairs: &[(char, char)],
+    context: &Context,
+) -> NormalModeOverride {
     fn select_surround_keymap_legend_config(
         kind: SurroundKind,
         context: &Context,
@@ -1484,6 +2008,7 @@ pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeO
             title: format!("Select Surround ({:?})", kind),
 
             body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
+                |_| true,
                 |enclosure| {
                     Dispatch::ToEditor(SelectSurround {
                         enclosure,
@@ -1500,26 +2025,13 @@ pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeO
             title: "Delete Surround".to_string(),
 
             body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
+                |_| true,
                 |enclosure| Dispatch::ToEditor(DeleteSurround(enclosure)),
                 context,
             )),
         }
     }
 
-    fn surround_keymap_legend_config(context: &Context) -> KeymapLegendConfig {
-        KeymapLegendConfig {
-            title: "Surround".to_string(),
-
-            body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
-                |enclosure| {
-                    let (open, close) = enclosure.open_close_symbols_str();
-                    Dispatch::ToEditor(Surround(open.to_string(), close.to_string()))
-                },
-                context,
-            )),
-        }
-    }
-
     fn change_surround_from_keymap_legend_config(
         context: &Context,
     ) -> super::keymap_legend::KeymapLegendConfig {
@@ -1527,6 +2039,7 @@ pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeO
             title: "Change Surround from:".to_string(),
 
             body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
+                |_| true,
                 |enclosure| {
                     Dispatch::ShowKeymapLegend(change_surround_to_keymap_legend_config(
                         enclosure, context,
@@ -1545,6 +2058,7 @@ pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeO
             title: format!("Change Surround from {} to:", from.to_str()),
 
             body: KeymapLegendBody::Positional(generate_enclosures_keymaps(
+                |_| true,
                 |enclosure| {
                     Dispatch::ToEditor(ChangeSurround {
                         from,
@@ -1582,7 +2096,10 @@ pub(crate) fn extend_mode_normal_mode_override(context: &Context) -> NormalModeO
         }),
         open: Some(KeymapOverride {
             description: "Surround",
-            dispatch: Dispatch::ShowKeymapLegend(surround_keymap_legend_config(context)),
+            dispatch: Dispatch::ShowKeymapLegend(surround_keymap_legend_config(
+                surround_pairs,
+                context,
+            )),
         }),
         v: Some(KeymapOverride {
             description: "Select All",
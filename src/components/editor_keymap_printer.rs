@@ -280,7 +280,7 @@ impl KeymapPrintSections {
             KeymapPrintSection::from_keymaps(
                 "Extend".to_string(),
                 &Keymaps::new(&editor.keymap_overridable(
-                    &extend_mode_normal_mode_override(&context),
+                    &extend_mode_normal_mode_override(editor.surround_pairs(), &context),
                     true,
                     &context,
                 )),
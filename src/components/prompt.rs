@@ -62,6 +62,17 @@ pub(crate) enum PromptHistoryKey {
         maintain: bool,
     },
     KeyboardLayout,
+    WrapInCall,
+    ExtractToFile,
+    GoToPercent,
+    MaxLines,
+    InsertCounter,
+    ShiftIndent,
+    Outline,
+    RetainSelectionsMatching,
+    SplitSelections,
+    MatchingLines,
+    FindChar,
 }
 
 impl Prompt {
@@ -49,7 +49,9 @@ impl Editor {
                 width,
             }
         };
+        let has_folds = !self.buffer().folded_line_ranges().is_empty();
         let grid = match &self.reveal {
+            None if has_folds => self.get_folded_grid(context, render_area, focused),
             None => self.get_grid_with_dimension(
                 context,
                 render_area,
@@ -148,6 +150,80 @@ impl Editor {
         Some(result)
     }
 
+    /// Render the lines that are not hidden by folds,
+    /// where each run of visible lines is rendered as a section, like the sections of `Reveal`.
+    fn get_folded_grid(
+        &self,
+        context: &Context,
+        render_area: Dimension,
+        focused: bool,
+    ) -> crate::grid::Grid {
+        let (runs, cursor_line) = {
+            let buffer = self.buffer();
+            let runs = buffer
+                .visible_lines()
+                .into_iter()
+                .map(|line| line..line + 1)
+                .coalesce(|previous, current| {
+                    if previous.end == current.start {
+                        Ok(previous.start..current.end)
+                    } else {
+                        Err((previous, current))
+                    }
+                })
+                .collect_vec();
+            let cursor_line = buffer
+                .char_to_line(self.get_cursor_char_index())
+                .unwrap_or_default();
+            (runs, cursor_line)
+        };
+        let scroll_offset = self.scroll_offset() as usize;
+        let last_run_index = runs.len().saturating_sub(1);
+        let (grid, _) = runs
+            .into_iter()
+            .enumerate()
+            .filter(|(_, run)| run.end > scroll_offset)
+            .fold(
+                (
+                    Grid::new(Dimension {
+                        height: 0,
+                        width: render_area.width,
+                    }),
+                    render_area.height,
+                ),
+                |(grid, remaining_height), (index, run)| {
+                    if remaining_height == 0 {
+                        return (grid, remaining_height);
+                    }
+                    let start = run.start.max(scroll_offset);
+                    // The last run extends to the end of the buffer, so it fills the rest
+                    let height = if index == last_run_index {
+                        remaining_height
+                    } else {
+                        ((run.end - start) as u16).min(remaining_height)
+                    };
+                    let protected_range = (start..run.end)
+                        .contains(&cursor_line)
+                        .then(|| self.selection_set.primary_selection().range());
+                    let borderize_first_line = grid.height() > 0;
+                    let section = self.get_grid_with_dimension(
+                        context,
+                        Dimension {
+                            height,
+                            width: render_area.width,
+                        },
+                        start as u16,
+                        protected_range,
+                        borderize_first_line,
+                        true,
+                        focused,
+                    );
+                    (grid.merge_vertical(section), remaining_height - height)
+                },
+            );
+        grid
+    }
+
     fn get_splitted_grid(
         &self,
         context: &Context,
@@ -673,6 +749,8 @@ impl Editor {
         let content = buffer.rope().to_string();
 
         let diagnostics = buffer.diagnostics();
+        // Stale diagnostics are dimmed until the LSP republishes them
+        let diagnostics_stale = buffer.diagnostics_stale();
         let diagnostics = diagnostics
             .iter()
             .sorted_by(|a, b| a.severity.cmp(&b.severity))
@@ -682,6 +760,7 @@ impl Editor {
                 is_cursor: false,
                 range: HighlightSpanRange::CharIndexRange(diagnostic.range),
                 source: Source::StyleKey(match diagnostic.severity {
+                    _ if diagnostics_stale => DiagnosticsDefault,
                     Some(DiagnosticSeverity::ERROR) => DiagnosticsError,
                     Some(DiagnosticSeverity::WARNING) => DiagnosticsWarning,
                     Some(DiagnosticSeverity::INFORMATION) => DiagnosticsInformation,
@@ -734,14 +813,16 @@ impl Editor {
                 |span| span.byte_range.clone(),
             )
             .iter()
-            .chain(hidden_parent_line_ranges.iter().flat_map(|line_range| {
-                let byte_range = buffer
-                    .line_range_to_byte_range(line_range)
-                    .unwrap_or_default();
-                filter_items_by_range(spans, byte_range.start, byte_range.end, |span| {
-                    span.byte_range.clone()
-                })
-            }))
+            .chain(
+                hidden_parent_line_ranges
+                    .iter()
+                    .flat_map(|line_range| buffer.line_byte_ranges(line_range.clone()))
+                    .flat_map(|byte_range| {
+                        filter_items_by_range(spans, byte_range.start, byte_range.end, |span| {
+                            span.byte_range.clone()
+                        })
+                    }),
+            )
             .map(|span| HighlightSpan {
                 range: HighlightSpanRange::ByteRange(span.byte_range.clone()),
                 source: Source::StyleKey(span.style_key.clone()),
@@ -6,13 +6,13 @@ use DispatchEditor::*;
 
 use crate::selection_range::SelectionRange;
 use crate::{
-    buffer::Buffer,
+    buffer::{directory_entries, matching_path_entries, Buffer},
     lsp::completion::{Completion, CompletionItem},
 };
 
 use itertools::Itertools;
 use my_proc_macros::key;
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 
 use super::dropdown::{Dropdown, DropdownConfig};
 use super::editor::{Direction, DispatchEditor, IfCurrentNotFound};
@@ -28,7 +28,18 @@ use super::{
 pub(crate) struct SuggestiveEditor {
     editor: Editor,
     completion_dropdown: Dropdown,
+    /// The items of the latest LSP completion, which are shown along with `path_completions`.
+    lsp_completion_items: Vec<DropdownItem>,
+    /// The directory last listed for path completion, and its entries,
+    /// so that the directory is only read again when the typed directory changes.
+    path_entries: Option<(PathBuf, Vec<String>)>,
+    /// The filesystem entries currently offered for the path typed at the cursor.
+    path_completions: Vec<String>,
+    /// Advertised by the LSP server.
     trigger_characters: Vec<String>,
+    /// Added by the user, for example, `/` for path completion,
+    /// which are merged with `trigger_characters`.
+    user_trigger_characters: Vec<String>,
     filter: SuggestiveEditorFilter,
 }
 
@@ -42,6 +53,7 @@ impl From<CompletionItem> for DropdownItem {
     fn from(item: CompletionItem) -> Self {
         DropdownItem::new(format!("{} {}", item.emoji(), item.label()))
             .set_info(item.info())
+            .set_detail(item.detail.clone())
             .set_dispatches(item.dispatches())
             .set_on_focused(Dispatches::one(Dispatch::ResolveCompletionItem(
                 item.completion_item(),
@@ -70,6 +82,14 @@ impl Component for SuggestiveEditor {
         Ok(dispatches.chain(update_filter_result?))
     }
 
+    fn handle_mouse_event(
+        &mut self,
+        context: &Context,
+        event: crossterm::event::MouseEvent,
+    ) -> anyhow::Result<Dispatches> {
+        self.editor.handle_mouse_event(context, event)
+    }
+
     fn handle_key_event(
         &mut self,
         context: &Context,
@@ -93,6 +113,10 @@ impl Component for SuggestiveEditor {
         // relevant completions.
         let dispatches = self.editor.handle_key_event(context, event.clone())?;
 
+        if self.editor.mode == Mode::Insert {
+            self.update_path_completions();
+        }
+
         let render_dropdown_dispatch = self.update_filter()?;
         Ok(render_dropdown_dispatch
             .chain(dispatches)
@@ -131,7 +155,11 @@ impl SuggestiveEditor {
             completion_dropdown: Dropdown::new(DropdownConfig {
                 title: "Completion".to_string(),
             }),
+            lsp_completion_items: Vec::new(),
+            path_entries: None,
+            path_completions: Vec::new(),
             trigger_characters: vec![],
+            user_trigger_characters: vec![],
             filter,
         }
     }
@@ -180,10 +208,72 @@ impl SuggestiveEditor {
     }
 
     pub(crate) fn set_completion(&mut self, completion: Completion) {
-        self.completion_dropdown.set_items(completion.items);
+        self.lsp_completion_items = completion.items;
+        self.set_completion_dropdown_items();
         self.trigger_characters = completion.trigger_characters;
     }
 
+    fn set_completion_dropdown_items(&mut self) {
+        self.completion_dropdown.set_items(
+            self.lsp_completion_items
+                .iter()
+                .cloned()
+                .chain(
+                    self.path_completions
+                        .iter()
+                        .map(|name| CompletionItem::from_path_entry(name.clone()).into()),
+                )
+                .collect(),
+        );
+    }
+
+    /// Offers the filesystem entries matching the path typed at the cursor, if any,
+    /// without waiting for the LSP server, which might not provide path completions.
+    fn update_path_completions(&mut self) {
+        let query = self
+            .editor
+            .buffer()
+            .path_completion_query(self.editor.get_cursor_char_index());
+        let path_completions = match query {
+            Some((directory, partial_name)) => {
+                if self
+                    .path_entries
+                    .as_ref()
+                    .is_none_or(|(listed, _)| listed != &directory)
+                {
+                    let entries = directory_entries(&directory);
+                    self.path_entries = Some((directory, entries));
+                }
+                self.path_entries
+                    .as_ref()
+                    .map(|(_, entries)| matching_path_entries(entries, &partial_name))
+                    .unwrap_or_default()
+            }
+            None => {
+                self.path_entries = None;
+                Vec::new()
+            }
+        };
+        if path_completions != self.path_completions {
+            self.path_completions = path_completions;
+            self.set_completion_dropdown_items();
+        }
+    }
+
+    pub(crate) fn set_user_trigger_characters(&mut self, user_trigger_characters: Vec<String>) {
+        self.user_trigger_characters = user_trigger_characters;
+    }
+
+    /// The trigger characters of the LSP server merged with the ones added by the user.
+    pub(crate) fn trigger_characters(&self) -> Vec<String> {
+        self.trigger_characters
+            .iter()
+            .chain(&self.user_trigger_characters)
+            .unique()
+            .cloned()
+            .collect()
+    }
+
     pub(crate) fn render_completion_dropdown(&self, ignore_insert_mode: bool) -> Dispatches {
         log::info!(
             "ignore_insert_mode = {ignore_insert_mode} mode = {:?}",
@@ -225,7 +315,9 @@ impl SuggestiveEditor {
                     // The filter should be empty if the current character is a trigger
                     // character, so that we can show all the completion items.
                     Some(current_char)
-                        if self.trigger_characters.contains(&current_char.to_string()) =>
+                        if self
+                            .trigger_characters()
+                            .contains(&current_char.to_string()) =>
                     {
                         "".to_string()
                     }
@@ -265,6 +357,8 @@ impl SuggestiveEditor {
     fn select_completion_item(&mut self) -> Result<Dispatches, anyhow::Error> {
         let current_item = self.completion_dropdown.current_item();
         if let Some(completion) = current_item {
+            self.lsp_completion_items.clear();
+            self.path_completions.clear();
             self.completion_dropdown.set_items(Vec::new());
             Ok(Dispatches::one(Dispatch::CloseDropdown).chain(completion.dispatches))
         } else {
@@ -1012,6 +1106,122 @@ mod test_suggestive_editor {
             ])
         })
     }
+
+    #[test]
+    fn user_trigger_characters() -> anyhow::Result<()> {
+        let run = |user_trigger_characters: Vec<String>| -> anyhow::Result<SuggestiveEditor> {
+            let mut editor = editor(SuggestiveEditorFilter::CurrentWord);
+            editor.set_user_trigger_characters(user_trigger_characters);
+            let _ = editor
+                .editor_mut()
+                .enter_insert_mode(Direction::Start, &Context::default())?;
+            editor.set_completion(dummy_completion());
+            let _ = editor.handle_events(keys!("p a"))?;
+            assert_eq!(editor.all_filtered_items().len(), 1);
+            let _ = editor.handle_events(keys!("-"))?;
+            Ok(editor)
+        };
+
+        // '-' is part of the current word, so it filters out every item by default
+        assert_eq!(run(Vec::new())?.all_filtered_items().len(), 0);
+
+        // '-' is not advertised by the server, but as a user trigger character,
+        // it shows all the items
+        let mut editor = run(vec!["-".to_string(), ".".to_string()])?;
+        assert_eq!(editor.all_filtered_items().len(), 3);
+
+        // The user trigger characters are merged with the ones of the server without duplicates
+        assert_eq!(editor.trigger_characters(), [".", "-"]);
+        Ok(())
+    }
+
+    #[test]
+    fn path_completion_in_string() -> anyhow::Result<()> {
+        use crate::selection::{CharIndex, Selection, SelectionSet};
+
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir(dir.path().join("src"))?;
+        std::fs::write(dir.path().join("script.sh"), "")?;
+        std::fs::write(dir.path().join("readme.md"), "")?;
+        let content = r#"fn main() { let path = "./s"; }"#;
+        let file_path = dir.path().join("main.rs");
+        std::fs::write(&file_path, content)?;
+        let buffer = Buffer::from_path(&CanonicalizedPath::try_from(file_path)?, true)?;
+
+        let mut editor = SuggestiveEditor::from_buffer(
+            Rc::new(RefCell::new(buffer)),
+            SuggestiveEditorFilter::CurrentWord,
+        );
+        let cursor = CharIndex(content.find("./s").unwrap() + 3);
+        editor.editor_mut().set_selection_set(
+            SelectionSet::new(nonempty::NonEmpty::new(Selection::new(
+                (cursor..cursor).into(),
+            ))),
+            &Context::default(),
+        );
+        let _ = editor
+            .editor_mut()
+            .enter_insert_mode(Direction::Start, &Context::default())?;
+
+        // Expect the directory entries starting with the partially typed name to appear
+        let _ = editor.handle_events(keys!("c"))?;
+        let displays = |editor: &mut SuggestiveEditor| {
+            editor
+                .all_filtered_items()
+                .into_iter()
+                .map(|item| item.display())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            displays(&mut editor),
+            [format!(
+                "{} script.sh",
+                CompletionItem::from_path_entry("script.sh".to_string()).emoji()
+            )]
+        );
+
+        let _ = editor.handle_events(keys!("backspace r"))?;
+        assert_eq!(
+            displays(&mut editor),
+            [format!(
+                "{} src/",
+                CompletionItem::from_path_entry("src/".to_string()).emoji()
+            )]
+        );
+
+        // Expect the path completions to be shown along with the LSP completion items
+        let _ = editor.handle_dispatch(Completion(Completion {
+            trigger_characters: vec![],
+            items: vec![CompletionItem::from_label("Spongebob".to_string()).into()],
+        }))?;
+        let unfiltered_displays = |editor: &SuggestiveEditor| {
+            editor
+                .completion_dropdown
+                .items()
+                .into_iter()
+                .map(|item| item.display())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            unfiltered_displays(&editor),
+            [
+                CompletionItem::from_label("Spongebob".to_string()),
+                CompletionItem::from_path_entry("src/".to_string())
+            ]
+            .map(|item| format!("{} {}", item.emoji(), item.label()))
+        );
+
+        // Expect the path completions to be removed once the typed content is no longer a path
+        let _ = editor.handle_events(keys!("backspace backspace backspace"))?;
+        assert_eq!(
+            unfiltered_displays(&editor),
+            [format!(
+                "{} Spongebob",
+                CompletionItem::from_label("Spongebob".to_string()).emoji()
+            )]
+        );
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
@@ -1,5 +1,5 @@
 use crate::app::{LocalSearchConfigUpdate, Scope};
-use crate::buffer::BufferOwner;
+use crate::buffer::{BufferOwner, QuoteStyle};
 use crate::char_index_range::CharIndexRange;
 use crate::clipboard::CopiedTexts;
 use crate::components::editor::{DispatchEditor::*, Movement::*};
@@ -4712,3 +4712,1701 @@ fuor
 }
 
 // TODO: store also failed search input in history
+
+#[test]
+fn auto_pairs_follow_language() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("".to_string())),
+            Editor(EnterInsertMode(Direction::End)),
+            App(HandleKeyEvents(keys!("v e c < u 8 > (").to_vec())),
+            Expect(CurrentComponentContent("vec<u8>()")),
+            Editor(SetLanguage(shared::language::from_extension("md").unwrap())),
+            Editor(SetContent("".to_string())),
+            Editor(EnterInsertMode(Direction::End)),
+            App(HandleKeyEvents(keys!("< (").to_vec())),
+            Expect(CurrentComponentContent("<()")),
+        ])
+    })
+}
+
+#[test]
+fn surround_pairs_follow_language() -> anyhow::Result<()> {
+    let surround_descriptions = |extension: &str| -> anyhow::Result<Vec<String>> {
+        let mut editor = crate::components::editor::Editor::from_text(None, "");
+        editor
+            .buffer_mut()
+            .set_language(shared::language::from_extension(extension).unwrap())?;
+        Ok(super::editor_keymap_legend::surround_keymap_legend_config(
+            editor.surround_pairs(),
+            &Context::default(),
+        )
+        .keymaps()
+        .iter()
+        .map(|keymap| keymap.description.clone())
+        .collect_vec())
+    };
+    assert!(surround_descriptions("rs")?.contains(&"< >".to_string()));
+
+    let markdown = surround_descriptions("md")?;
+    assert!(markdown.contains(&"( )".to_string()));
+    assert!(!markdown.contains(&"< >".to_string()));
+    Ok(())
+}
+
+#[test]
+fn inline_variable() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    let x = 1 + 2;\n    foo(x, x);\n}".to_string(),
+            )),
+            Editor(MatchLiteral("x =".to_string())),
+            Editor(InlineVariable),
+            Expect(CurrentComponentContent(
+                "fn main() {\n    foo(1 + 2, 1 + 2);\n}",
+            )),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(InlineVariable),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn wrap_in_call() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { foo(x + 1) }".to_string())),
+            Editor(MatchLiteral("x + 1".to_string())),
+            Editor(WrapInCall {
+                function_name: "dbg!".to_string(),
+            }),
+            Expect(CurrentComponentContent("fn main() { foo(dbg!(x + 1)) }")),
+            Editor(Insert("?".to_string())),
+            Expect(CurrentComponentContent("fn main() { foo(dbg!(x + 1)?) }")),
+        ])
+    })
+}
+
+#[test]
+fn wrap_in_call_skips_whitespace_selection() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { foo(x + 1) }".to_string())),
+            Editor(MatchLiteral(" ".to_string())),
+            Editor(WrapInCall {
+                function_name: "dbg!".to_string(),
+            }),
+            Expect(CurrentComponentContent("fn main() { foo(x + 1) }")),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn insert_function_stub() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { foo(a, b); }".to_string())),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(InsertFunctionStub),
+            Expect(CurrentComponentContent(
+                "fn main() { foo(a, b); }\n\nfn foo(a: TODO, b: TODO) {\n    todo!()\n}",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn expand_and_shrink_selection_semantic_without_lsp() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { foo(x + y) }".to_string())),
+            Editor(MatchLiteral("x".to_string())),
+            Editor(ExpandSelectionSemantic),
+            Expect(CurrentSelectedTexts(&["x + y"])),
+            Editor(ShrinkSelectionSemantic),
+            Expect(CurrentSelectedTexts(&["x"])),
+        ])
+    })
+}
+
+#[test]
+fn select_all_strings_and_comments() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                r#"fn main() { let x = "hello"; /* a */ f("world"); /* b */ }"#.to_string(),
+            )),
+            Editor(SelectAllStrings),
+            Expect(CurrentSelectedTexts(&["\"hello\"", "\"world\""])),
+            Editor(SelectAllComments),
+            Expect(CurrentSelectedTexts(&["/* a */", "/* b */"])),
+        ])
+    })
+}
+
+#[test]
+fn swap_with_next_sibling() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { f(x, yy); }".to_string())),
+            Editor(MatchLiteral("x".to_string())),
+            Editor(SwapWithNextSibling),
+            Expect(CurrentComponentContent("fn main() { f(yy, x); }")),
+            Expect(CurrentSelectedTexts(&["x"])),
+            Editor(SwapWithNextSibling),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn transpose_selections() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                r#"fn main() { f("x", "yy", "zzz"); }"#.to_string(),
+            )),
+            Editor(SelectAllStrings),
+            Editor(TransposeSelections),
+            Expect(CurrentComponentContent(
+                r#"fn main() { f("zzz", "x", "yy"); }"#,
+            )),
+            Editor(MatchLiteral("main".to_string())),
+            Editor(TransposeSelections),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn go_to_first_error() -> anyhow::Result<()> {
+    execute_test(|s| {
+        let diagnostic =
+            |start: u32, end: u32, severity: lsp_types::DiagnosticSeverity| lsp_types::Diagnostic {
+                range: lsp_types::Range::new(
+                    lsp_types::Position::new(0, start),
+                    lsp_types::Position::new(0, end),
+                ),
+                severity: Some(severity),
+                ..Default::default()
+            };
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { let x = 123 }".to_string())),
+            Editor(GoToFirstError),
+            Expect(EditorInfoContent("No errors found.")),
+            App(HandleLspNotification(LspNotification::PublishDiagnostics(
+                lsp_types::PublishDiagnosticsParams {
+                    uri: s.main_rs().to_url().unwrap(),
+                    diagnostics: [
+                        diagnostic(0, 2, lsp_types::DiagnosticSeverity::WARNING),
+                        diagnostic(12, 15, lsp_types::DiagnosticSeverity::ERROR),
+                    ]
+                    .to_vec(),
+                    version: None,
+                },
+            ))),
+            Editor(GoToFirstError),
+            Expect(CurrentSelectedTexts(&["let"])),
+        ])
+    })
+}
+
+#[test]
+fn go_to_nearest_clean_line() -> anyhow::Result<()> {
+    execute_test(|s| {
+        let error = |line: u32| lsp_types::Diagnostic {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(line, 0),
+                lsp_types::Position::new(line, 1),
+            ),
+            severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+            ..Default::default()
+        };
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a\nb\nc\nd\ne\nf".to_string())),
+            App(HandleLspNotification(LspNotification::PublishDiagnostics(
+                lsp_types::PublishDiagnosticsParams {
+                    uri: s.main_rs().to_url().unwrap(),
+                    diagnostics: [error(1), error(2), error(3)].to_vec(),
+                    version: None,
+                },
+            ))),
+            Editor(MatchLiteral("c".to_string())),
+            Editor(GoToNearestCleanLine),
+            Expect(CurrentSelectedTexts(&["e\n"])),
+        ])
+    })
+}
+
+#[test]
+fn select_word_changes() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {\n    let x = 1;\n}\n".to_string())),
+            Editor(Save),
+            Editor(SelectWordChanges),
+            Expect(EditorInfoContent("No matches found.")),
+            Editor(MatchLiteral("x".to_string())),
+            Editor(ReplaceCurrentSelectionWith("y".to_string())),
+            Editor(SelectWordChanges),
+            Expect(CurrentSelectedTexts(&["y"])),
+            Expect(EditorInfoContent("\"x\" → \"y\"")),
+        ])
+    })
+}
+
+#[test]
+fn select_whitespace_issues() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {  \n \tfoo();\n}".to_string())),
+            Editor(SelectWhitespaceIssues),
+            Expect(CurrentSelectedTexts(&["  ", " \t"])),
+        ])
+    })
+}
+
+#[test]
+fn go_to_percent() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("0\n1\n2\n3\n4\n5\n6\n7\n8\n9".to_string())),
+            App(OpenGoToPercentPrompt),
+            App(HandleKeyEvents(keys!("5 0 enter").to_vec())),
+            Expect(CurrentSelectedTexts(&["5\n"])),
+        ])
+    })
+}
+
+#[test]
+fn find_char() -> anyhow::Result<()> {
+    let search = |target: char, direction: Direction, inclusive: bool| {
+        Editor(FindChar(crate::buffer::CharSearch {
+            target,
+            direction,
+            inclusive,
+        }))
+    };
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() { foo(a, b); }\nbar(c)".to_string())),
+            Editor(MatchLiteral("foo".to_string())),
+            App(OpenFindCharPrompt {
+                direction: Direction::End,
+                inclusive: true,
+            }),
+            App(HandleKeyEvents(keys!("b enter").to_vec())),
+            Expect(CurrentSelectedTexts(&["b"])),
+            search('f', Direction::Start, true),
+            Expect(CurrentSelectedTexts(&["f"])),
+            search('n', Direction::Start, false),
+            Expect(CurrentSelectedTexts(&["("])),
+            search(',', Direction::End, false),
+            Expect(CurrentSelectedTexts(&["a"])),
+            // The search stops at the end of the line
+            search('c', Direction::End, true),
+            Expect(EditorInfoContent("No matches found.")),
+        ])
+    })
+}
+
+#[test]
+fn repeat_char_search() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("f(a, b, c, d)".to_string())),
+            Editor(RepeatCharSearch { reversed: false }),
+            Expect(EditorInfoContent("No matches found.")),
+            Editor(FindChar(crate::buffer::CharSearch {
+                target: ',',
+                direction: Direction::End,
+                inclusive: false,
+            })),
+            Expect(CurrentSelectedTexts(&["a"])),
+            Editor(RepeatCharSearch { reversed: false }),
+            Expect(CurrentSelectedTexts(&["b"])),
+            Editor(RepeatCharSearch { reversed: false }),
+            Expect(CurrentSelectedTexts(&["c"])),
+            Editor(RepeatCharSearch { reversed: true }),
+            Expect(CurrentSelectedTexts(&[" "])),
+        ])
+    })
+}
+
+#[test]
+fn select_string_content() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(r#"fn main() { let x = "a\"b"; }"#.to_string())),
+            Editor(SelectStringContent),
+            Expect(EditorInfoContent("No matches found.")),
+            Editor(MatchLiteral("b".to_string())),
+            Editor(SelectStringContent),
+            Expect(CurrentSelectedTexts(&[r#"a\"b"#])),
+            Expect(EditorInfoContent(r#"a"b"#)),
+        ])
+    })
+}
+
+#[test]
+fn convert_quotes() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(r#"fn main() { let x = "it's"; }"#.to_string())),
+            Editor(MatchLiteral("it".to_string())),
+            Editor(ConvertQuotes(QuoteStyle::Single)),
+            Expect(CurrentComponentContent(r#"fn main() { let x = 'it\'s'; }"#)),
+        ])
+    })
+}
+
+#[test]
+fn select_statement() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    let x = foo(1);\n    bar();\n}".to_string(),
+            )),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(SelectStatement),
+            Expect(CurrentSelectedTexts(&["let x = foo(1);"])),
+        ])
+    })
+}
+
+#[test]
+fn move_node_out_of_block() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    if x {\n        foo();\n        bar();\n    }\n}".to_string(),
+            )),
+            Editor(MatchLiteral("bar".to_string())),
+            Editor(MoveNodeOutOfBlock),
+            Expect(CurrentComponentContent(
+                "fn main() {\n    if x {\n        foo();\n    }\n    bar();\n}",
+            )),
+            Expect(CurrentSelectedTexts(&["bar();"])),
+        ])
+    })
+}
+
+#[test]
+fn confirm_quit_all_with_unsaved_changes() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {}".to_string())),
+            App(ConfirmQuitAll),
+            Expect(CurrentComponentTitle(
+                "Quit without saving src/main.rs?".to_string(),
+            )),
+        ])
+    })
+}
+
+#[test]
+fn toggle_read_only() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(ToggleReadOnly),
+            Expect(EditorInfoContent("The buffer is now read-only.")),
+            Editor(ToggleReadOnly),
+            Expect(EditorInfoContent("The buffer is now editable.")),
+            Editor(SetContent("fn main() {}".to_string())),
+            Expect(CurrentComponentContent("fn main() {}")),
+        ])
+    })
+}
+
+#[test]
+fn save_all_skips_read_only_buffers() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {}".to_string())),
+            Editor(ToggleReadOnly),
+            App(OpenFile {
+                path: s.foo_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn foo() {}".to_string())),
+            App(SaveAll),
+            // Foo is saved after the read-only buffer, which must not abort Save All
+            Expect(Not(Box::new(EditorIsDirty()))),
+        ])
+    })
+}
+
+#[test]
+fn save_all_with_binary_file_open() -> anyhow::Result<()> {
+    execute_test(|s| {
+        std::fs::write(s.temp_dir().to_path_buf().join("data.bin"), b"\0\x01binary").unwrap();
+        let path = s.temp_dir().join("data.bin").unwrap();
+        Box::new([
+            App(OpenFile {
+                path: path.clone(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            App(OpenFile {
+                path: s.foo_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn foo() {}".to_string())),
+            App(SaveAll),
+            // Foo is saved after the binary buffer, which must not abort Save All
+            Expect(Not(Box::new(EditorIsDirty()))),
+        ])
+    })
+}
+
+#[test]
+fn binary_file_cannot_be_made_editable() -> anyhow::Result<()> {
+    execute_test(|s| {
+        std::fs::write(s.temp_dir().to_path_buf().join("data.bin"), b"\0\x01binary").unwrap();
+        let path = s.temp_dir().join("data.bin").unwrap();
+        Box::new([
+            App(OpenFile {
+                path,
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(ToggleReadOnly),
+            Expect(EditorInfoContent("Binary files are always read-only.")),
+        ])
+    })
+}
+
+#[test]
+fn tail_file() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a\nb".to_string())),
+            Editor(Save),
+            Editor(TailFile),
+            Expect(EditorInfoContent("No new content.")),
+            Editor(SetContent("a\n".to_string())),
+            Editor(TailFile),
+            Expect(CurrentComponentContent("a\nb")),
+            Editor(SetContent("c".to_string())),
+            Editor(TailFile),
+            Expect(EditorInfoContent(
+                "The file was modified other than by appending.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn tail_file_with_max_lines() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("1\n2\n3\n4".to_string())),
+            Editor(Save),
+            Editor(SetContent("1\n".to_string())),
+            App(OpenMaxLinesPrompt),
+            App(HandleKeyEvents(keys!("2 enter").to_vec())),
+            Editor(TailFile),
+            Expect(CurrentComponentContent("3\n4")),
+        ])
+    })
+}
+
+#[test]
+fn mouse_click_selects_the_clicked_char() -> anyhow::Result<()> {
+    let click = |row: u16, column: u16| {
+        App(HandleMouseEvent(crossterm::event::MouseEvent {
+            kind: crossterm::event::MouseEventKind::Down(crossterm::event::MouseButton::Left),
+            column,
+            row,
+            modifiers: crossterm::event::KeyModifiers::NONE,
+        }))
+    };
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("alpha\nbeta\ngamma\nlok".to_string())),
+            Editor(SetRectangle(Rectangle {
+                origin: Position::default(),
+                width: 100,
+                height: 3,
+            })),
+            Editor(SetScrollOffset(2)),
+            Expect(EditorGrid("🦀  main.rs [*]\n3│█amma\n4│lok")),
+            // The title is at row 0, and the line numbers span 2 columns
+            click(1, 4),
+            Expect(CurrentSelectedTexts(&["m"])),
+            // Clicking past the end of the line selects its line break
+            click(1, 50),
+            Expect(CurrentSelectedTexts(&["\n"])),
+        ])
+    })
+}
+
+#[test]
+fn extend_selection_by_char() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("hello".to_string())),
+            Editor(MatchLiteral("ell".to_string())),
+            App(HandleKeyEvent(key!("shift+right"))),
+            Expect(CurrentSelectedTexts(&["ello"])),
+            // The selection is clamped to the end of the buffer
+            App(HandleKeyEvent(key!("shift+right"))),
+            Expect(CurrentSelectedTexts(&["ello"])),
+            Editor(ExtendSelectionByChar(Direction::Start)),
+            Editor(ExtendSelectionByChar(Direction::Start)),
+            Editor(ExtendSelectionByChar(Direction::Start)),
+            Expect(CurrentSelectedTexts(&["hell"])),
+        ])
+    })
+}
+
+#[test]
+fn collapse_to_primary_or_last() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(r#"fn main() { f("x", "y", "z"); }"#.to_string())),
+            Editor(SelectAllStrings),
+            Editor(CollapseToLast),
+            Expect(CurrentSelectedTexts(&["\"z\""])),
+            Editor(SelectAllStrings),
+            Editor(CollapseToPrimary),
+            Expect(CurrentSelectedTexts(&["\"x\""])),
+        ])
+    })
+}
+
+#[test]
+fn rotate_selection_contents() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                r#"fn main() { f("x", "yy", "zzz"); }"#.to_string(),
+            )),
+            Editor(SelectAllStrings),
+            Editor(RotateSelectionContents(Direction::Start)),
+            Expect(CurrentComponentContent(
+                r#"fn main() { f("yy", "zzz", "x"); }"#,
+            )),
+            Editor(RotateSelectionContents(Direction::End)),
+            Expect(CurrentComponentContent(
+                r#"fn main() { f("x", "yy", "zzz"); }"#,
+            )),
+        ])
+    })
+}
+
+#[test]
+fn insert_counter() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(r#"fn main() { f("x", "y", "z"); }"#.to_string())),
+            Editor(SelectAllStrings),
+            Editor(InsertCounter {
+                format: "{}.".to_string(),
+            }),
+            Expect(CurrentComponentContent(
+                r#"fn main() { f(1."x", 2."y", 3."z"); }"#,
+            )),
+        ])
+    })
+}
+
+#[test]
+fn retain_selections_matching() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                r#"fn main() { f("x1", "y", "z2"); }"#.to_string(),
+            )),
+            Editor(SelectAllStrings),
+            Editor(RetainSelectionsMatching {
+                pattern: r"\d".to_string(),
+                invert: false,
+            }),
+            Expect(CurrentSelectedTexts(&[r#""x1""#, r#""z2""#])),
+            Editor(SelectAllStrings),
+            Editor(RetainSelectionsMatching {
+                pattern: r"\d".to_string(),
+                invert: true,
+            }),
+            Expect(CurrentSelectedTexts(&[r#""y""#])),
+            Editor(RetainSelectionsMatching {
+                pattern: "y".to_string(),
+                invert: true,
+            }),
+            Expect(CurrentSelectedTexts(&[r#""y""#])),
+            Expect(EditorInfoContent("No selections would remain.")),
+        ])
+    })
+}
+
+#[test]
+fn split_selections() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("alpha, beta,,gamma".to_string())),
+            Editor(MatchLiteral("alpha, beta,,gamma".to_string())),
+            Editor(SplitSelections {
+                separator: r",\s*".to_string(),
+            }),
+            Expect(CurrentSelectedTexts(&["alpha", "beta", "gamma"])),
+        ])
+    })
+}
+
+#[test]
+fn merge_selections() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(r#"f("x", "y")"#.to_string())),
+            Editor(SelectAllStrings),
+            Editor(SetSelectionMode(IfCurrentNotFound::LookForward, Line)),
+            Expect(CurrentSelectedTexts(&[r#"f("x", "y")"#, r#"f("x", "y")"#])),
+            Editor(MergeSelections),
+            Expect(CurrentSelectedTexts(&[r#"f("x", "y")"#])),
+        ])
+    })
+}
+
+#[test]
+fn select_inside_brackets() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("foo(bar[1], (baz + 2))".to_string())),
+            Editor(MatchLiteral("baz".to_string())),
+            Editor(SelectInsideBrackets),
+            Expect(CurrentSelectedTexts(&["baz + 2"])),
+            Editor(SelectInsideBrackets),
+            Expect(CurrentSelectedTexts(&["baz + 2"])),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(SelectInsideBrackets),
+            Expect(EditorInfoContent("No matches found.")),
+        ])
+    })
+}
+
+#[test]
+fn go_to_blank_line() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("alpha\n \nbeta\ngamma\n  \ndelta".to_string())),
+            Editor(MatchLiteral("beta".to_string())),
+            Editor(GoToBlankLine(Direction::End)),
+            Expect(CurrentSelectedTexts(&["  \n"])),
+            Editor(GoToBlankLine(Direction::Start)),
+            Expect(CurrentSelectedTexts(&[" \n"])),
+            Editor(GoToBlankLine(Direction::Start)),
+            Expect(CurrentSelectedTexts(&["alpha\n"])),
+        ])
+    })
+}
+
+#[test]
+fn select_matching_lines() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    foo();\n}\n\npub fn foo() {}\n".to_string(),
+            )),
+            Editor(SelectMatchingLines {
+                pattern: r"\bfn ".to_string(),
+            }),
+            Expect(CurrentSelectedTexts(&["fn main() {", "pub fn foo() {}"])),
+            Editor(SelectMatchingLines {
+                pattern: "bar".to_string(),
+            }),
+            Expect(EditorInfoContent("No matches found.")),
+        ])
+    })
+}
+
+#[test]
+fn delete_matching_lines() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("// a\nfoo();\n  // c\nbar();\n".to_string())),
+            Editor(DeleteMatchingLines {
+                pattern: r"^\s*//".to_string(),
+                invert: false,
+            }),
+            Expect(CurrentComponentContent("foo();\nbar();\n")),
+            Editor(Undo),
+            Editor(DeleteMatchingLines {
+                pattern: r"^\s*//".to_string(),
+                invert: true,
+            }),
+            Expect(CurrentComponentContent("// a\n  // c\n")),
+        ])
+    })
+}
+
+#[test]
+fn record_and_replay_edits() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("foo\nbar".to_string())),
+            Editor(ReplayRecording),
+            Expect(EditorInfoContent("No edits were recorded.")),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(ToggleRecording),
+            Editor(EnterInsertMode(Direction::Start)),
+            Editor(Insert("xy".to_string())),
+            Editor(EnterNormalMode),
+            Editor(ToggleRecording),
+            Expect(EditorInfoContent("Recorded 1 edit(s).")),
+            Editor(MatchLiteral("bar".to_string())),
+            Editor(ReplayRecording),
+            Expect(CurrentComponentContent("xyfoo\nxybar")),
+        ])
+    })
+}
+
+#[test]
+fn replay_recording_at_selections() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a b c\nfoo".to_string())),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(ToggleRecording),
+            Editor(EnterInsertMode(Direction::Start)),
+            Editor(Insert("- ".to_string())),
+            Editor(EnterNormalMode),
+            Editor(ToggleRecording),
+            Editor(MatchLiteral("a b c".to_string())),
+            Editor(SplitSelections {
+                separator: " ".to_string(),
+            }),
+            Editor(ReplayRecordingAtSelections),
+            Expect(CurrentComponentContent("- a - b - c\n- foo")),
+        ])
+    })
+}
+
+#[test]
+fn copy_as_html() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("<b>".to_string())),
+            Editor(CopyAsHtml {
+                use_system_clipboard: false,
+            }),
+            Editor(Paste {
+                direction: Direction::End,
+                use_system_clipboard: false,
+            }),
+            Expect(AppGridContains("&lt;b&gt;")),
+        ])
+    })
+}
+
+#[test]
+fn indent_outline_prompt() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("alpha\n  beta\n    gamma\ndelta".to_string())),
+            App(OpenIndentOutlinePrompt),
+            App(HandleKeyEvents(keys!("g a m m a enter").to_vec())),
+            Expect(CurrentSelectedTexts(&["    gamma\n"])),
+        ])
+    })
+}
+
+#[test]
+fn fold_and_unfold_lines() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            App(TerminalDimensionChanged(crate::app::Dimension {
+                height: 10,
+                width: 40,
+            })),
+            Editor(SetContent("alpha\nbeta\ngamma\ndelta\nepsilon".to_string())),
+            Editor(MatchLiteral("beta\ngamma\ndelta".to_string())),
+            Editor(FoldSelectedLines),
+            Expect(AppGridContains("beta")),
+            Expect(Not(Box::new(AppGridContains("gamma")))),
+            Expect(AppGridContains("epsilon")),
+            Editor(UnfoldSelectedLines),
+            Expect(AppGridContains("gamma")),
+            Editor(MatchLiteral("alpha".to_string())),
+            Editor(FoldSelectedLines),
+            Expect(EditorInfoContent(
+                "A fold must span at least 2 lines, but got 0..1",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn fold_selections_on_adjacent_lines_together() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            App(TerminalDimensionChanged(crate::app::Dimension {
+                height: 10,
+                width: 40,
+            })),
+            Editor(SetContent("alpha\nbeta\ngamma\ndelta\nepsilon".to_string())),
+            Editor(SelectMatchingLines {
+                pattern: "^(beta|gamma)$".to_string(),
+            }),
+            Editor(FoldSelectedLines),
+            Expect(AppGridContains("beta")),
+            Expect(Not(Box::new(AppGridContains("gamma")))),
+            Expect(AppGridContains("delta")),
+        ])
+    })
+}
+
+#[test]
+fn sort_object_keys() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetLanguage(
+                shared::language::from_extension("json").unwrap(),
+            )),
+            Editor(SetContent(r#"{"b": 1, "a": 2}"#.to_string())),
+            Editor(MatchLiteral("1".to_string())),
+            Editor(SortObjectKeys),
+            Expect(CurrentComponentContent(r#"{"a": 2, "b": 1}"#)),
+        ])
+    })
+}
+
+#[test]
+fn select_line_changes() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {\n    let x = 1;\n}\n".to_string())),
+            Editor(Save),
+            Editor(SelectLineChanges),
+            Expect(EditorInfoContent("No matches found.")),
+            Editor(MatchLiteral("x".to_string())),
+            Editor(ReplaceCurrentSelectionWith("y".to_string())),
+            Editor(SelectLineChanges),
+            Expect(CurrentSelectedTexts(&["    let y = 1;\n"])),
+        ])
+    })
+}
+
+#[test]
+fn apply_patch() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a\nb\nc\n".to_string())),
+            App(SetClipboardContent {
+                copied_texts: CopiedTexts::one("@@ -2 +2 @@\n-b\n+B\n".to_string()),
+                use_system_clipboard: false,
+            }),
+            Editor(ApplyPatch {
+                use_system_clipboard: false,
+            }),
+            Expect(CurrentComponentContent("a\nB\nc\n")),
+            Expect(CurrentSelectedTexts(&["B\n"])),
+        ])
+    })
+}
+
+#[test]
+fn toggle_line_endings() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a\nb\r\nc".to_string())),
+            Editor(ToggleLineEndings),
+            Expect(CurrentComponentContent("a\r\nb\r\nc")),
+            Editor(ToggleLineEndings),
+            Expect(CurrentComponentContent("a\nb\nc")),
+        ])
+    })
+}
+
+#[test]
+fn selections_to_quickfix_list() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("ab cd".to_string())),
+            Editor(MatchLiteral("ab cd".to_string())),
+            Editor(SplitSelections {
+                separator: " ".to_string(),
+            }),
+            Editor(SelectionsToQuickfixList),
+            Expect(ExpectKind::BufferQuickfixListItems(
+                [
+                    Position { line: 0, column: 0 }..Position { line: 0, column: 2 },
+                    Position { line: 0, column: 3 }..Position { line: 0, column: 5 },
+                ]
+                .to_vec(),
+            )),
+        ])
+    })
+}
+
+#[test]
+fn reindent_pasted() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    foo();\nif a {\n    b();\n}\n}".to_string(),
+            )),
+            Editor(MatchLiteral("if a {\n    b();\n}\n".to_string())),
+            Editor(ReindentPasted),
+            Expect(CurrentComponentContent(
+                "fn main() {\n    foo();\n    if a {\n        b();\n    }\n}",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn select_comment_paragraph() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    // first\n    // second\n    //\n    // next\n}".to_string(),
+            )),
+            Editor(MatchLiteral("second".to_string())),
+            Editor(SelectCommentParagraph),
+            Expect(CurrentSelectedTexts(&["    // first\n    // second"])),
+        ])
+    })
+}
+
+#[test]
+fn mark_conflicts() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "a\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> other\nd".to_string(),
+            )),
+            Editor(MatchLiteral("a".to_string())),
+            Editor(ToggleMark),
+            Editor(MarkConflicts),
+            Editor(SetSelectionMode(IfCurrentNotFound::LookForward, Mark)),
+            Editor(CursorAddToAllSelections),
+            Expect(CurrentSelectedTexts(&[
+                "<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> other",
+            ])),
+        ])
+    })
+}
+
+#[test]
+fn copy_between_marks() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a foo b bar c".to_string())),
+            Editor(CopyBetweenMarks {
+                use_system_clipboard: false,
+            }),
+            Expect(EditorInfoContent("At least two marks are needed.")),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(ToggleMark),
+            Editor(MatchLiteral("bar".to_string())),
+            Editor(ToggleMark),
+            Editor(CopyBetweenMarks {
+                use_system_clipboard: false,
+            }),
+            Editor(MatchLiteral("c".to_string())),
+            Editor(ReplaceWithCopiedText {
+                cut: false,
+                use_system_clipboard: false,
+            }),
+            Expect(CurrentComponentContent("a foo b bar foo b bar")),
+        ])
+    })
+}
+
+#[test]
+fn cycle_marks_in_creation_order() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("one two three".to_string())),
+            Editor(MatchLiteral("three".to_string())),
+            Editor(ToggleMark),
+            Editor(SelectAll),
+            Editor(MatchLiteral("two".to_string())),
+            Editor(ToggleMark),
+            Editor(SelectAll),
+            Editor(MatchLiteral("one".to_string())),
+            Editor(ToggleMark),
+            Editor(CycleMarks),
+            Expect(CurrentSelectedTexts(&["three"])),
+            Editor(CycleMarks),
+            Expect(CurrentSelectedTexts(&["two"])),
+            Editor(CycleMarks),
+            Expect(CurrentSelectedTexts(&["one"])),
+        ])
+    })
+}
+
+#[test]
+fn keep_only_selections() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a foo b bar c".to_string())),
+            Editor(MatchLiteral("foo b bar".to_string())),
+            Editor(SplitSelections {
+                separator: " b ".to_string(),
+            }),
+            Editor(KeepOnlySelections {
+                separate_with_newlines: true,
+            }),
+            Expect(CurrentComponentContent("foo\nbar")),
+            Expect(CurrentSelectedTexts(&["foo", "bar"])),
+        ])
+    })
+}
+
+#[test]
+fn shift_indent() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("  a\n        b\nc".to_string())),
+            Editor(MatchLiteral("a\n        b".to_string())),
+            Editor(ShiftIndent { levels: -1 }),
+            Expect(CurrentComponentContent("a\n    b\nc")),
+            Editor(ShiftIndent { levels: 2 }),
+            Expect(CurrentComponentContent("        a\n            b\nc")),
+        ])
+    })
+}
+
+#[test]
+fn preview_format() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {}\n".to_string())),
+            Editor(PreviewFormat),
+            Expect(EditorInfoContent("Already formatted.")),
+            Editor(SetContent(" fn main\n() {}".to_string())),
+            Editor(PreviewFormat),
+            Expect(Not(Box::new(EditorInfoContent("Already formatted.")))),
+            Expect(CurrentComponentContent(" fn main\n() {}")),
+        ])
+    })
+}
+
+#[test]
+fn show_names_in_scope() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn add(beta: usize, alpha: usize) -> usize {\n    alpha + beta\n}\nfn other(gamma: usize) {}"
+                    .to_string(),
+            )),
+            Editor(MatchLiteral("alpha +".to_string())),
+            Editor(ShowNamesInScope),
+            Expect(EditorInfoContent("alpha\nbeta")),
+        ])
+    })
+}
+
+#[test]
+fn format_table() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("name,age\nAlexander,7\nBo,42".to_string())),
+            Editor(SelectAll),
+            Editor(FormatTable),
+            Expect(CurrentComponentContent(
+                "name,      age\nAlexander, 7\nBo,        42",
+            )),
+            Editor(SetContent("foo".to_string())),
+            Editor(SelectAll),
+            Editor(FormatTable),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn revert_hunk() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "mod bar;\n\nfn main() {\n    foo::bar();\n    println!(\"Hello, world!\");\n}\n"
+                    .to_string(),
+            )),
+            Editor(MatchLiteral("bar".to_string())),
+            Editor(RevertHunk),
+            Expect(CurrentComponentContent(
+                "mod foo;\n\nfn main() {\n    foo::bar();\n    println!(\"Hello, world!\");\n}\n",
+            )),
+            Editor(MatchLiteral("main".to_string())),
+            Editor(RevertHunk),
+            Expect(EditorInfoContent(
+                "Not applicable at the current selection.",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn expand_tabs() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("\tx\n\tab\tc\td".to_string())),
+            Editor(MatchLiteral("ab\tc".to_string())),
+            Editor(ExpandTabs),
+            Expect(CurrentComponentContent("\tx\n\tab  c\td")),
+        ])
+    })
+}
+
+#[test]
+fn go_to_heuristic_definition() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    foo();\n}\n\nfn foo() {}\n".to_string(),
+            )),
+            Editor(MatchLiteral("foo".to_string())),
+            Editor(GoToHeuristicDefinition),
+            Expect(CurrentSelectedTexts(&["foo"])),
+            Expect(EditorCursorPosition(Position { line: 4, column: 3 })),
+        ])
+    })
+}
+
+#[test]
+fn toggle_line_terminator() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn main() {\n    let x = 1 // one\n}".to_string(),
+            )),
+            Editor(MatchLiteral("let".to_string())),
+            Editor(ToggleLineTerminator(';')),
+            Expect(CurrentComponentContent(
+                "fn main() {\n    let x = 1; // one\n}",
+            )),
+            Editor(ToggleLineTerminator(';')),
+            Expect(CurrentComponentContent(
+                "fn main() {\n    let x = 1 // one\n}",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn toggle_char_case() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("abc 123".to_string())),
+            Editor(SetSelectionMode(IfCurrentNotFound::LookForward, Character)),
+            Editor(CursorAddToAllSelections),
+            Editor(ToggleCharCase),
+            Expect(CurrentComponentContent("ABC 123")),
+        ])
+    })
+}
+
+#[test]
+fn insert_sibling_line() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "struct A {\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n}".to_string(),
+            )),
+            Editor(MatchLiteral("x".to_string())),
+            Editor(InsertSiblingLine(Direction::Start)),
+            Editor(Insert("w: u8,".to_string())),
+            Expect(CurrentComponentContent(
+                "struct A {\n    w: u8,\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n}",
+            )),
+            Editor(EnterNormalMode),
+            Editor(MatchLiteral("Vec".to_string())),
+            Editor(InsertSiblingLine(Direction::End)),
+            Editor(Insert("z: u8,".to_string())),
+            Expect(CurrentComponentContent(
+                "struct A {\n    w: u8,\n    x: u32,\n    y: Vec<\n        u32,\n    >,\n    z: u8,\n}",
+            )),
+        ])
+    })
+}
+
+#[test]
+fn select_word_object() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("foo bar_baz  qux".to_string())),
+            Editor(MatchLiteral("r_b".to_string())),
+            Editor(SelectWordObject { around: false }),
+            Expect(CurrentSelectedTexts(&["bar_baz"])),
+            Editor(SelectWordObject { around: true }),
+            Expect(CurrentSelectedTexts(&["bar_baz  "])),
+        ])
+    })
+}
+
+#[test]
+fn select_same_highlight() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            App(SetTheme(Theme::default())),
+            Editor(SetContent(
+                "fn main() { let x = 1; let y = 2; }".to_string(),
+            )),
+            Editor(SetLanguage(shared::language::from_extension("rs").unwrap())),
+            Editor(SetRectangle(Rectangle {
+                origin: Position::default(),
+                width: 100,
+                height: 2,
+            })),
+            Editor(ApplySyntaxHighlight),
+            Editor(MatchLiteral("let".to_string())),
+            Editor(SelectSameHighlight),
+            Expect(CurrentSelectedTexts(&["let", "let"])),
+        ])
+    })
+}
+
+#[test]
+fn select_word_occurrences() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "let count = 1;\nlet counter = count + 1;\nprint(count);".to_string(),
+            )),
+            Editor(MatchLiteral("count".to_string())),
+            Editor(SelectWordOccurrences),
+            Expect(CurrentSelectedTexts(&["count", "count", "count"])),
+            Editor(MatchLiteral("=".to_string())),
+            Editor(SelectWordOccurrences),
+            Expect(EditorInfoContent("The cursor is not on a word.")),
+        ])
+    })
+}
+
+#[test]
+fn normalize_typography() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("// “it’s done” — finally…".to_string())),
+            Editor(SelectAll),
+            Editor(NormalizeTypography),
+            Expect(CurrentComponentContent("// \"it's done\" - finally...")),
+        ])
+    })
+}
+
+#[test]
+fn extract_to_file() -> anyhow::Result<()> {
+    execute_test(|s| {
+        let path = s.temp_dir().to_path_buf().join("extracted.rs");
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("fn main() {}\nfn helper() {}\n".to_string())),
+            Editor(MatchLiteral("fn helper".to_string())),
+            Editor(ExtractToFile { path: path.clone() }),
+            Expect(CurrentComponentContent("fn main() {}\n")),
+            App(OpenFileFromPathBuf {
+                path,
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Expect(CurrentComponentContent("fn helper() {}\n")),
+        ])
+    })
+}
+
+#[test]
+fn select_block_statements() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent(
+                "fn f() {\n    let a = 1;\n    a + 1\n}\nfn g() {}\n".to_string(),
+            )),
+            Editor(MatchLiteral("a + 1".to_string())),
+            Editor(SelectBlockStatements),
+            Expect(CurrentSelectedTexts(&["let a = 1;\n    a + 1"])),
+            Editor(MatchLiteral("{}".to_string())),
+            Editor(SelectBlockStatements),
+            Expect(EditorInfoContent("No matches found.")),
+        ])
+    })
+}
+
+#[test]
+fn completion_replaces_only_the_word_after_a_dash_in_rust() -> anyhow::Result<()> {
+    execute_test(|s| {
+        Box::new([
+            App(OpenFile {
+                path: s.main_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("a-b".to_string())),
+            Editor(SetSelectionMode(IfCurrentNotFound::LookForward, Line)),
+            Editor(EnterInsertMode(Direction::End)),
+            // `-` is an operator in Rust, so only `b` is replaced
+            Editor(ExecuteCompletion {
+                replacement: "bar".to_string(),
+                edit: None,
+            }),
+            Expect(CurrentComponentContent("a-bar")),
+        ])
+    })
+}
@@ -34,6 +34,11 @@ pub(crate) struct Context {
     location_history_backward: Vec<Location>,
     location_history_forward: Vec<Location>,
     marked_paths: IndexSet<CanonicalizedPath>,
+    /// Completion trigger characters added by the user, see `KI_EDITOR_TRIGGER_CHARACTERS`.
+    user_trigger_characters: Vec<String>,
+    /// The number of blank lines kept at the end of files on save,
+    /// see `KI_EDITOR_MAX_TRAILING_BLANK_LINES`.
+    max_trailing_blank_lines: Option<usize>,
 
     /// This is true, for example, when Ki is running as a VS Code's extension
     is_running_as_embedded: bool,
@@ -95,6 +100,18 @@ impl Default for Context {
             location_history_backward: Vec::new(),
             location_history_forward: Vec::new(),
             marked_paths: Default::default(),
+            user_trigger_characters: std::env::var("KI_EDITOR_TRIGGER_CHARACTERS")
+                .map(|characters| {
+                    characters
+                        .chars()
+                        .filter(|char| !char.is_whitespace())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            max_trailing_blank_lines: std::env::var("KI_EDITOR_MAX_TRAILING_BLANK_LINES")
+                .ok()
+                .and_then(|max| max.trim().parse().ok()),
             is_running_as_embedded: false,
         }
     }
@@ -278,6 +295,14 @@ impl Context {
         self.last_non_contiguous_selection_mode.as_ref()
     }
 
+    pub(crate) fn user_trigger_characters(&self) -> &[String] {
+        &self.user_trigger_characters
+    }
+
+    pub(crate) fn max_trailing_blank_lines(&self) -> Option<usize> {
+        self.max_trailing_blank_lines
+    }
+
     pub(crate) fn keyboard_layout_kind(&self) -> &KeyboardLayoutKind {
         &self.keyboard_layout_kind
     }
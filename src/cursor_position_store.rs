@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use shared::canonicalized_path::CanonicalizedPath;
+
+use crate::position::Position;
+
+/// The number of files remembered, beyond which the least recently set ones are forgotten,
+/// so that the store does not grow without bound.
+const MAX_ENTRIES: usize = 1000;
+
+/// A sidecar JSON file that remembers the last cursor position of each file,
+/// so that reopening a file in a later session lands where it was left.
+#[derive(Clone, Debug)]
+pub(crate) struct CursorPositionStore {
+    path: PathBuf,
+    max_entries: usize,
+}
+
+impl CursorPositionStore {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            max_entries: MAX_ENTRIES,
+        }
+    }
+
+    /// The entries are ordered from the least to the most recently set.
+    fn load(&self) -> Vec<(String, usize, usize)> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn get(&self, path: &CanonicalizedPath) -> Option<Position> {
+        let path = path.display_absolute();
+        self.load()
+            .into_iter()
+            .find(|(entry_path, _, _)| entry_path == &path)
+            .map(|(_, line, column)| Position::new(line, column))
+    }
+
+    pub(crate) fn set(&self, path: &CanonicalizedPath, position: Position) -> anyhow::Result<()> {
+        let path = path.display_absolute();
+        let mut entries = self.load();
+        entries.retain(|(entry_path, _, _)| entry_path != &path);
+        entries.push((path, position.line, position.column));
+        let excess = entries.len().saturating_sub(self.max_entries);
+        entries.drain(..excess);
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(&entries)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_cursor_position_store {
+    use shared::canonicalized_path::CanonicalizedPath;
+    use tempfile::tempdir;
+
+    use crate::position::Position;
+
+    use super::CursorPositionStore;
+
+    #[test]
+    fn forget_least_recently_set_beyond_max_entries() -> anyhow::Result<()> {
+        let dir = tempdir()?;
+        let store = CursorPositionStore {
+            path: dir.path().join("cursor_positions.json"),
+            max_entries: 2,
+        };
+        let paths = ["a.rs", "b.rs", "c.rs"]
+            .into_iter()
+            .map(|name| -> anyhow::Result<_> {
+                let path = dir.path().join(name);
+                std::fs::write(&path, "")?;
+                Ok(CanonicalizedPath::try_from(path)?)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        store.set(&paths[0], Position::new(1, 0))?;
+        store.set(&paths[1], Position::new(2, 0))?;
+        // Setting an existing entry again makes it the most recent one
+        store.set(&paths[0], Position::new(3, 0))?;
+        store.set(&paths[2], Position::new(4, 0))?;
+
+        assert_eq!(store.get(&paths[0]), Some(Position::new(3, 0)));
+        assert_eq!(store.get(&paths[1]), None);
+        assert_eq!(store.get(&paths[2]), Some(Position::new(4, 0)));
+        Ok(())
+    }
+}
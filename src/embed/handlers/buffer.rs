@@ -6,10 +6,7 @@ use crate::{
     buffer::BufferOwner,
     context::Context,
     edit::{Action, ActionGroup, Edit, EditTransaction},
-    embed::{
-        app::EmbeddedApp,
-        utils::{host_position_to_ki_position, uri_to_path},
-    },
+    embed::{app::EmbeddedApp, utils::uri_to_path},
 };
 use itertools::Itertools;
 use ki_protocol_types::{
@@ -92,8 +89,8 @@ impl EmbeddedApp {
             edits
                 .into_iter()
                 .map(|diff_edit| -> anyhow::Result<_> {
-                    let start_ki_pos = host_position_to_ki_position(&diff_edit.range.start);
-                    let end_ki_pos = host_position_to_ki_position(&diff_edit.range.end);
+                    let start_ki_pos = buffer.vscode_position_to_position(&diff_edit.range.start);
+                    let end_ki_pos = buffer.vscode_position_to_position(&diff_edit.range.end);
 
                     let start_char_index = buffer.position_to_char(start_ki_pos)?;
                     let end_char_index = buffer.position_to_char(end_ki_pos)?;
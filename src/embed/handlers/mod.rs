@@ -17,5 +17,4 @@ pub(crate) mod prelude {
     // Use these types internally within the handlers module
     pub(crate) use crate::context::Context;
     pub(crate) use crate::embed::utils::*;
-    pub(crate) use crate::position::Position;
 }
@@ -24,8 +24,8 @@ impl EmbeddedApp {
                 .selections
                 .into_iter()
                 .map(|selection| {
-                    let active = to_ki_position(&selection.active);
-                    let anchor = to_ki_position(&selection.anchor);
+                    let active = buffer.vscode_position_to_position(&selection.active);
+                    let anchor = buffer.vscode_position_to_position(&selection.anchor);
 
                     // The sorting is necessary, because `active` might not always be the smaller position
                     // If start is not always smaller, than Swap Primary Cursor with Secondary Cursor will not work properly
@@ -82,10 +82,3 @@ impl EmbeddedApp {
         self.handle_selection_set_notification(params)
     }
 }
-
-pub(crate) fn to_ki_position(position: &ki_protocol_types::Position) -> Position {
-    Position {
-        line: position.line as usize,
-        column: position.character as usize,
-    }
-}
@@ -1,16 +1,6 @@
-use crate::position::Position as KiPosition;
-use ki_protocol_types::Position as HostPosition;
 use shared::canonicalized_path::CanonicalizedPath;
 use url::Url;
 
-// Convert Host protocol position to Ki editor position
-pub(crate) fn host_position_to_ki_position(pos: &HostPosition) -> KiPosition {
-    KiPosition {
-        line: pos.line as usize,
-        column: pos.character as usize,
-    }
-}
-
 // Convert a CanonicalizedPath to a file URI string
 pub(crate) fn path_to_uri(path: &CanonicalizedPath) -> String {
     Url::from_file_path(path.as_ref())
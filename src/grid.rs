@@ -19,7 +19,7 @@ pub(crate) struct Grid {
     pub(crate) width: usize,
 }
 
-const DEFAULT_TAB_SIZE: usize = 4;
+pub(crate) const DEFAULT_TAB_SIZE: usize = 4;
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub(crate) struct Cell {
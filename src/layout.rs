@@ -1,5 +1,5 @@
 use crate::context::Context;
-use crate::quickfix_list::QuickfixList;
+use crate::quickfix_list::{workspace_diagnostics, QuickfixList};
 use crate::syntax_highlight::SyntaxHighlightRequestBatchId;
 use crate::ui_tree::{ComponentKind, KindedComponent, UiTree};
 use crate::{
@@ -252,6 +252,8 @@ impl Layout {
     pub(crate) fn save_all(&self, context: &Context) -> Result<(), anyhow::Error> {
         self.background_suggestive_editors
             .iter()
+            // Read-only buffers are never saved, so they must not abort saving the others
+            .filter(|(_, editor)| !editor.borrow().editor().buffer().read_only())
             .map(|(_, editor)| editor.borrow_mut().editor_mut().save(context))
             .collect::<Result<Vec<_>, _>>()?;
         Ok(())
@@ -490,35 +492,16 @@ impl Layout {
         &self,
         source: &QuickfixListSource,
     ) -> Vec<QuickfixListItem> {
-        self.buffers()
-            .into_iter()
-            .flat_map(|buffer| {
-                let buffer = buffer.borrow();
-                match source {
-                    QuickfixListSource::Diagnostic(severity_range) => buffer
-                        .diagnostics()
-                        .into_iter()
-                        .filter_map(|diagnostic| {
-                            if !severity_range.contains(diagnostic.severity) {
-                                return None;
-                            }
-
-                            let position_range = buffer
-                                .char_index_range_to_position_range(diagnostic.range)
-                                .ok()?;
-                            Some(QuickfixListItem::new(
-                                Location {
-                                    path: buffer.path()?,
-                                    range: position_range,
-                                },
-                                Some(Info::new(
-                                    "Diagnostics".to_string(),
-                                    diagnostic.message.clone(),
-                                )),
-                            ))
-                        })
-                        .collect_vec(),
-                    QuickfixListSource::Mark => buffer
+        match source {
+            QuickfixListSource::Diagnostic(severity_range) => {
+                workspace_diagnostics(&self.buffers(), severity_range)
+            }
+            QuickfixListSource::Mark => self
+                .buffers()
+                .into_iter()
+                .flat_map(|buffer| {
+                    let buffer = buffer.borrow();
+                    buffer
                         .marks()
                         .into_iter()
                         .filter_map(|mark| {
@@ -532,11 +515,15 @@ impl Layout {
                                 None,
                             ))
                         })
-                        .collect_vec(),
-                    QuickfixListSource::Custom => buffer.quickfix_list_items(),
-                }
-            })
-            .collect_vec()
+                        .collect_vec()
+                })
+                .collect_vec(),
+            QuickfixListSource::Custom => self
+                .buffers()
+                .into_iter()
+                .flat_map(|buffer| buffer.borrow().quickfix_list_items())
+                .collect_vec(),
+        }
     }
 
     pub(crate) fn clear_quickfix_list_items(&mut self) {
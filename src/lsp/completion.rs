@@ -104,6 +104,24 @@ impl CompletionItem {
         }
     }
 
+    /// A completion of a filesystem entry, see `Buffer::path_completion_query`.
+    pub(crate) fn from_path_entry(name: String) -> Self {
+        Self {
+            kind: Some(if name.ends_with('/') {
+                CompletionItemKind::FOLDER
+            } else {
+                CompletionItemKind::FILE
+            }),
+            label: name,
+            detail: None,
+            documentation: None,
+            sort_text: None,
+            edit: None,
+            insert_text: None,
+            completion_item: Default::default(),
+        }
+    }
+
     pub(crate) fn label(&self) -> String {
         self.label.clone()
     }
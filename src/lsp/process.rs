@@ -72,6 +72,7 @@ pub(crate) enum LspNotification {
     SignatureHelp(Option<SignatureHelp>),
     Symbols(Symbols),
     CompletionItemResolve(lsp_types::CompletionItem),
+    SelectionRanges(CanonicalizedPath, Vec<SelectionRange>),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
@@ -129,9 +130,12 @@ pub(crate) enum FromEditor {
     },
     TextDocumentCodeAction {
         params: RequestParams,
+        /// The range to request code actions for, which defaults to the position of `params`.
+        range: Option<lsp_types::Range>,
         diagnostics: Vec<lsp_types::Diagnostic>,
     },
     TextDocumentSignatureHelp(RequestParams),
+    TextDocumentSelectionRange(RequestParams),
     TextDocumentDeclaration(RequestParams),
     TextDocumentImplementation(RequestParams),
     TextDocumentTypeDefinition(RequestParams),
@@ -814,6 +818,18 @@ impl LspServerProcess {
                             )))
                             .unwrap();
                     }
+                    "textDocument/selectionRange" => {
+                        let payload: <lsp_request!("textDocument/selectionRange") as Request>::Result =
+                            serde_json::from_value(response)?;
+
+                        if let (Some(payload), Some(path)) = (payload, path) {
+                            self.app_message_sender
+                                .send(AppMessage::LspNotification(
+                                    LspNotification::SelectionRanges(path, payload),
+                                ))
+                                .unwrap();
+                        }
+                    }
                     "textDocument/documentSymbol" => {
                         let payload: <lsp_request!("textDocument/documentSymbol") as Request>::Result =
                             serde_json::from_value(response)?;
@@ -1307,6 +1323,7 @@ impl LspServerProcess {
     fn text_document_code_action(
         &mut self,
         params: RequestParams,
+        range: Option<Range>,
         diagnostics: Vec<Diagnostic>,
     ) -> Result<(), anyhow::Error> {
         if !self.has_capability(|c| c.code_action_provider.is_some()) {
@@ -1322,10 +1339,10 @@ impl LspServerProcess {
                     only: None,
                 },
                 partial_result_params: Default::default(),
-                range: Range {
+                range: range.unwrap_or(Range {
                     start: params.position.into(),
                     end: params.position.into(),
-                },
+                }),
                 text_document: path_buf_to_text_document_identifier(params.path)?,
                 work_done_progress_params: Default::default(),
             },
@@ -1371,6 +1388,25 @@ impl LspServerProcess {
         )
     }
 
+    fn text_document_selection_range(
+        &mut self,
+        params: RequestParams,
+    ) -> Result<(), anyhow::Error> {
+        if !self.has_capability(|c| c.selection_range_provider.is_some()) {
+            return Ok(());
+        }
+        self.send_request::<lsp_request!("textDocument/selectionRange")>(
+            params.context,
+            Some(params.path.clone()),
+            SelectionRangeParams {
+                positions: vec![params.position.into()],
+                partial_result_params: Default::default(),
+                text_document: path_buf_to_text_document_identifier(params.path)?,
+                work_done_progress_params: Default::default(),
+            },
+        )
+    }
+
     fn workspace_execute_command(
         &mut self,
         params: RequestParams,
@@ -1440,8 +1476,9 @@ impl LspServerProcess {
             }
             FromEditor::TextDocumentCodeAction {
                 params,
+                range,
                 diagnostics,
-            } => self.text_document_code_action(params, diagnostics),
+            } => self.text_document_code_action(params, range, diagnostics),
             FromEditor::TextDocumentDocumentSymbol(params) => {
                 self.text_document_document_symbol(params)
             }
@@ -1459,6 +1496,9 @@ impl LspServerProcess {
                 content,
             } => self.text_document_did_change(file_path, version, content),
             FromEditor::TextDocumentDidSave { file_path } => self.text_document_did_save(file_path),
+            FromEditor::TextDocumentSelectionRange(params) => {
+                self.text_document_selection_range(params)
+            }
             FromEditor::TextDocumentSignatureHelp(params) => {
                 self.text_document_signature_help(params)
             }
@@ -6,6 +6,7 @@ mod cli;
 mod clipboard;
 mod components;
 mod context;
+mod cursor_position_store;
 mod edit;
 pub(crate) mod frontend;
 mod grid;
@@ -83,9 +84,12 @@ pub(crate) fn run(config: RunConfig) -> anyhow::Result<()> {
         [
             StatusLineComponent::Mode,
             StatusLineComponent::SelectionMode,
+            StatusLineComponent::SelectionSize,
+            StatusLineComponent::CursorPosition,
             StatusLineComponent::LastSearchString,
             StatusLineComponent::Reveal,
             StatusLineComponent::CurrentWorkingDirectory,
+            StatusLineComponent::CurrentFilePath,
             StatusLineComponent::GitBranch,
             StatusLineComponent::KeyboardLayout,
             StatusLineComponent::Help,
@@ -97,6 +101,9 @@ pub(crate) fn run(config: RunConfig) -> anyhow::Result<()> {
         false,
     )?;
     app.set_syntax_highlight_request_sender(syntax_highlighter_sender);
+    app.set_cursor_position_store(cursor_position_store::CursorPositionStore::new(
+        grammar::cache_dir().join("cursor_positions.json"),
+    ));
 
     let sender = app.sender();
 
@@ -167,6 +167,19 @@ impl QuickfixListItem {
     }
 }
 
+/// Aggregates the diagnostics within `severity_range` of every buffer of `buffers`
+/// into one project-wide list, ordered by path, then by position.
+pub(crate) fn workspace_diagnostics(
+    buffers: &[Rc<RefCell<Buffer>>],
+    severity_range: &DiagnosticSeverityRange,
+) -> Vec<QuickfixListItem> {
+    buffers
+        .iter()
+        .flat_map(|buffer| buffer.borrow().diagnostics_to_quickfix(severity_range))
+        .sorted()
+        .collect()
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) struct Location {
     pub(crate) path: CanonicalizedPath,
@@ -340,4 +353,57 @@ mod test_quickfix_list {
             }]
         )
     }
+
+    #[test]
+    fn workspace_diagnostics() -> anyhow::Result<()> {
+        use std::{cell::RefCell, rc::Rc};
+
+        use crate::buffer::Buffer;
+        use itertools::Itertools;
+        use shared::canonicalized_path::CanonicalizedPath;
+
+        let dir = tempfile::tempdir()?;
+        let diagnostic = |line: u32, message: &str| lsp_types::Diagnostic {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(line, 0),
+                lsp_types::Position::new(line, 1),
+            ),
+            message: message.to_string(),
+            ..Default::default()
+        };
+        let path = |name: &str| -> anyhow::Result<CanonicalizedPath> {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "a\nb\nc\n")?;
+            CanonicalizedPath::try_from(path)
+        };
+        let (a, b) = (path("a.rs")?, path("b.rs")?);
+        let buffer = |path: &CanonicalizedPath, diagnostics: Vec<lsp_types::Diagnostic>| {
+            let mut buffer = Buffer::new(None, "a\nb\nc\n");
+            buffer.set_path(path.clone());
+            buffer.set_diagnostics(diagnostics);
+            Rc::new(RefCell::new(buffer))
+        };
+        let buffers = [
+            buffer(&b, vec![diagnostic(2, "b2"), diagnostic(0, "b0")]),
+            buffer(&a, vec![diagnostic(1, "a1")]),
+        ];
+
+        let items = super::workspace_diagnostics(&buffers, &super::DiagnosticSeverityRange::All);
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (
+                    item.location().path.clone(),
+                    item.location().range.start.line,
+                    item.info().as_ref().unwrap().content().clone()
+                ))
+                .collect_vec(),
+            [
+                (a.clone(), 1, "a1".to_string()),
+                (b.clone(), 0, "b0".to_string()),
+                (b, 2, "b2".to_string()),
+            ]
+        );
+        Ok(())
+    }
 }
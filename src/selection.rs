@@ -65,6 +65,51 @@ impl SelectionSet {
         self.cursor_index = 0;
     }
 
+    /// Keep only the primary selection, like `only`, but by value.
+    pub(crate) fn collapse_to_primary(mut self) -> Self {
+        self.only();
+        self
+    }
+
+    /// Sort the selections, and coalesce the overlapping or touching ones into a single selection,
+    /// so that subsequent edits are not applied twice on the same range.
+    pub(crate) fn merge_selections(self) -> Self {
+        let primary_range = self.primary_selection().extended_range();
+        let mut merged: Vec<Selection> = Vec::new();
+        for selection in self
+            .selections
+            .iter()
+            .sorted_by_key(|selection| selection.extended_range())
+        {
+            let range = selection.extended_range();
+            match merged.last_mut() {
+                Some(last) if range.start <= last.range.end => {
+                    last.range = (last.range.start..last.range.end.max(range.end)).into()
+                }
+                _ => merged.push(selection.clone().set_initial_range(None).set_range(range)),
+            }
+        }
+        let cursor_index = merged
+            .iter()
+            .position(|selection| selection.range.is_supserset_of(&primary_range))
+            .unwrap_or_default();
+        match NonEmpty::from_vec(merged) {
+            Some(selections) => SelectionSet {
+                cursor_index,
+                selections,
+                ..self
+            },
+            None => self,
+        }
+    }
+
+    /// Keep only the last selection.
+    pub(crate) fn collapse_to_last(mut self) -> Self {
+        self.cursor_index = self.selections.len() - 1;
+        self.only();
+        self
+    }
+
     pub(crate) fn apply<F>(&self, mode: SelectionMode, f: F) -> anyhow::Result<SelectionSet>
     where
         F: Fn(&Selection) -> anyhow::Result<Selection>,
@@ -651,8 +696,22 @@ impl Selection {
     }
 
     pub(crate) fn swap_initial_range_direction(&mut self) {
-        if let Some(initial_range) = self.initial_range.take() {
-            self.initial_range = Some(std::mem::replace(&mut self.range, initial_range));
+        *self = self.swap_selection_ends();
+    }
+
+    /// Returns this selection with its active end moved to the opposite end,
+    /// so that extension continues from the other side (like Vim's `o`).
+    ///
+    /// The extended range is unchanged. A selection that is not being extended
+    /// is returned as is, because its active end is the editor's cursor direction.
+    pub(crate) fn swap_selection_ends(&self) -> Selection {
+        match self.initial_range {
+            Some(initial_range) => Selection {
+                range: initial_range,
+                initial_range: Some(self.range),
+                info: self.info.clone(),
+            },
+            None => self.clone(),
         }
     }
 
@@ -809,3 +868,82 @@ impl CharIndex {
         }
     }
 }
+
+#[cfg(test)]
+mod test_selection {
+    use nonempty::NonEmpty;
+
+    use super::{CharIndex, Selection, SelectionSet};
+    use crate::char_index_range::CharIndexRange;
+
+    fn three_cursors() -> SelectionSet {
+        let cursor = |index: usize| Selection::new((CharIndex(index)..CharIndex(index)).into());
+        SelectionSet {
+            cursor_index: 1,
+            ..SelectionSet::new(NonEmpty {
+                head: cursor(0),
+                tail: vec![cursor(5), cursor(10)],
+            })
+        }
+    }
+
+    #[test]
+    fn collapse_to_primary() {
+        let selection_set = three_cursors().collapse_to_primary();
+        assert_eq!(selection_set.len(), 1);
+        assert_eq!(
+            selection_set.primary_selection().range(),
+            (CharIndex(5)..CharIndex(5)).into()
+        );
+    }
+
+    #[test]
+    fn merge_selections() {
+        let selection =
+            |start: usize, end: usize| Selection::new((CharIndex(start)..CharIndex(end)).into());
+        let selection_set = SelectionSet::new(NonEmpty {
+            head: selection(10, 12),
+            tail: vec![selection(3, 6), selection(0, 4)],
+        })
+        .merge_selections();
+        assert_eq!(
+            selection_set
+                .selections()
+                .iter()
+                .map(|selection| selection.range())
+                .collect::<Vec<_>>(),
+            vec![
+                CharIndexRange::from(CharIndex(0)..CharIndex(6)),
+                CharIndexRange::from(CharIndex(10)..CharIndex(12))
+            ]
+        );
+        assert_eq!(
+            selection_set.primary_selection().range(),
+            (CharIndex(10)..CharIndex(12)).into()
+        );
+    }
+
+    #[test]
+    fn swap_selection_ends() {
+        let selection = Selection::new((CharIndex(8)..CharIndex(10)).into())
+            .set_initial_range(Some((CharIndex(2)..CharIndex(4)).into()));
+        let swapped = selection.swap_selection_ends();
+        assert_eq!(swapped.range(), (CharIndex(2)..CharIndex(4)).into());
+        assert_eq!(
+            swapped.initial_range,
+            Some((CharIndex(8)..CharIndex(10)).into())
+        );
+        assert_eq!(swapped.extended_range(), selection.extended_range());
+        assert_eq!(swapped.swap_selection_ends(), selection);
+    }
+
+    #[test]
+    fn collapse_to_last() {
+        let selection_set = three_cursors().collapse_to_last();
+        assert_eq!(selection_set.len(), 1);
+        assert_eq!(
+            selection_set.primary_selection().range(),
+            (CharIndex(10)..CharIndex(10)).into()
+        );
+    }
+}
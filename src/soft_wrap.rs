@@ -212,7 +212,7 @@ pub(crate) fn wrap_items(items: &[&str], wrap_width: usize) -> Vec<String> {
     if wrap_width == 0 {
         return Vec::new();
     }
-    items
+    group_string_literals(items)
         .iter()
         .flat_map(|chunk| chop_str(chunk, wrap_width))
         .fold(
@@ -235,6 +235,31 @@ pub(crate) fn wrap_items(items: &[&str], wrap_width: usize) -> Vec<String> {
         .collect_vec()
 }
 
+/// Merge the items that lie within a double-quoted string literal into the item
+/// that opens the literal, so that a literal is never split across lines,
+/// unless it is wider than the wrap width by itself.
+fn group_string_literals(items: &[&str]) -> Vec<String> {
+    let line = items.concat();
+    let literals = lazy_regex::regex!(r#""(?:[^"\\]|\\.)*""#)
+        .find_iter(&line)
+        .map(|literal| literal.range())
+        .collect_vec();
+    let mut offset = 0;
+    items
+        .iter()
+        .fold(Vec::new(), |mut result: Vec<String>, item| {
+            let within_literal = literals
+                .iter()
+                .any(|literal| literal.start < offset && offset < literal.end);
+            match result.last_mut() {
+                Some(last) if within_literal => last.push_str(item),
+                _ => result.push(item.to_string()),
+            }
+            offset += item.len();
+            result
+        })
+}
+
 /// Chop the given string into chunks by the given `max_width`
 /// The width of each chunk is paired with each chunk in the result vector.
 fn chop_str(s: &str, max_width: usize) -> Vec<(usize, String)> {
@@ -289,8 +314,9 @@ fn chop_str(s: &str, max_width: usize) -> Vec<(usize, String)> {
 #[cfg(test)]
 mod test_soft_wrap {
     use crate::position::Position;
+    use itertools::Itertools;
 
-    use super::{chop_str, soft_wrap};
+    use super::{chop_str, soft_wrap, wrap_items};
     use unicode_width::UnicodeWidthStr;
 
     #[test]
@@ -307,6 +333,17 @@ mod test_soft_wrap {
         )
     }
 
+    #[test]
+    fn wrap_items_does_not_break_string_literals() {
+        let items = lazy_regex::regex!(r"\b")
+            .split(r#"f(1, "a \"b c")"#)
+            .collect_vec();
+        assert_eq!(wrap_items(&items, 12), vec!["f(1", r#", "a \"b c")"#]);
+
+        // Literals wider than the wrap width are still chopped
+        assert_eq!(wrap_items(&[r#""abc""#], 3), vec![r#""ab"#, r#"c""#]);
+    }
+
     #[test]
     fn consider_unicode_width_1() {
         let content = "→ abc";
@@ -137,6 +137,20 @@ impl HighlightedSpans {
                 span.byte_range.end = (span.byte_range.end as isize + change) as usize;
             });
     }
+
+    /// Restore the invariant that the spans are sorted by their range,
+    /// which `apply_edit_mut` can break because it only shifts the spans within the affected range.
+    pub(crate) fn sort_spans(&mut self) {
+        let key = |span: &HighlightedSpan| (span.byte_range.start, span.byte_range.end);
+        if !self.0.is_sorted_by_key(key) {
+            self.0.sort_by_key(key)
+        }
+    }
+
+    pub(crate) fn is_sorted(&self) -> bool {
+        self.0
+            .is_sorted_by_key(|span| (span.byte_range.start, span.byte_range.end))
+    }
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq)]
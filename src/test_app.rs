@@ -2902,3 +2902,123 @@ spam
         ])
     })
 }
+
+#[test]
+fn status_line_selection_size() -> anyhow::Result<()> {
+    execute_test_helper(
+        || Box::new(NullWriter),
+        false,
+        [StatusLineComponent::SelectionSize].to_vec(),
+        |s| {
+            Box::new([
+                App(OpenFile {
+                    path: s.main_rs(),
+                    owner: BufferOwner::User,
+                    focus: true,
+                }),
+                Editor(SetContent("\tab你好 x".to_string())),
+                Editor(MatchLiteral("\tab你好".to_string())),
+                Expect(AppGridContains("5 chars, 9 bytes, 10 cols")),
+            ])
+        },
+        true,
+    )
+}
+
+#[test]
+fn status_line_current_file_path() -> anyhow::Result<()> {
+    execute_test_helper(
+        || Box::new(NullWriter),
+        false,
+        [
+            StatusLineComponent::SelectionSize,
+            StatusLineComponent::CurrentFilePath,
+        ]
+        .to_vec(),
+        |s| {
+            Box::new([
+                App(OpenFile {
+                    path: s.main_rs(),
+                    owner: BufferOwner::User,
+                    focus: true,
+                }),
+                Editor(SetContent("hello".to_string())),
+                Editor(MatchLiteral("hello".to_string())),
+                Expect(AppGridContains("5 chars, 5 bytes, 5 cols src/main.rs")),
+            ])
+        },
+        true,
+    )
+}
+
+#[test]
+fn status_line_cursor_position() -> anyhow::Result<()> {
+    execute_test_helper(
+        || Box::new(NullWriter),
+        false,
+        [
+            StatusLineComponent::SelectionSize,
+            StatusLineComponent::CursorPosition,
+        ]
+        .to_vec(),
+        |s| {
+            Box::new([
+                App(OpenFile {
+                    path: s.main_rs(),
+                    owner: BufferOwner::User,
+                    focus: true,
+                }),
+                Editor(SetContent("x\n\tab".to_string())),
+                Editor(MatchLiteral("b".to_string())),
+                Expect(AppGridContains("1 chars, 1 bytes, 1 cols 2:6")),
+            ])
+        },
+        true,
+    )
+}
+
+#[test]
+fn stale_diagnostics_are_dimmed() -> Result<(), anyhow::Error> {
+    execute_test(|s| {
+        let publish_diagnostics = || {
+            App(Dispatch::HandleLspNotification(
+                LspNotification::PublishDiagnostics(lsp_types::PublishDiagnosticsParams {
+                    uri: Url::from_file_path(s.foo_rs()).unwrap(),
+                    diagnostics: [lsp_types::Diagnostic {
+                        range: lsp_types::Range::new(
+                            lsp_types::Position::new(0, 4),
+                            lsp_types::Position::new(0, 9),
+                        ),
+                        severity: Some(lsp_types::DiagnosticSeverity::ERROR),
+                        ..Default::default()
+                    }]
+                    .to_vec(),
+                    version: None,
+                }),
+            ))
+        };
+        // The columns are added by 2, because of the line number and its separator
+        let expect_style_key =
+            |style_key: StyleKey| Expect(GridCellStyleKey(Position::new(1, 6), Some(style_key)));
+        Box::new([
+            App(OpenFile {
+                path: s.foo_rs(),
+                owner: BufferOwner::User,
+                focus: true,
+            }),
+            Editor(SetContent("who lives in a pineapple".to_string())),
+            App(TerminalDimensionChanged(Dimension {
+                height: 3,
+                width: 80,
+            })),
+            publish_diagnostics(),
+            expect_style_key(StyleKey::DiagnosticsError),
+            Editor(MatchLiteral("pineapple".to_string())),
+            Editor(EnterInsertMode(Direction::End)),
+            Editor(Insert("?".to_string())),
+            expect_style_key(StyleKey::DiagnosticsDefault),
+            publish_diagnostics(),
+            expect_style_key(StyleKey::DiagnosticsError),
+        ])
+    })
+}
@@ -682,6 +682,12 @@ impl Color {
         Ok(Color { r, g, b, a })
     }
 
+    /// Returns the CSS hex representation, for example, `#ff8800`.
+    /// The alpha channel is omitted.
+    pub(crate) fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
     /// Refer https://docs.rs/colorsys/latest/src/colorsys/rgb/transform.rs.html#61
     /// Refer https://sl.bing.net/b69EKNHqrLw
     pub(crate) fn get_contrasting_color(&self) -> Color {
@@ -133,4 +133,16 @@ who lives in a pineapple under the sea? Spongebob Squarepants! absorbent and yel
             .trim().to_string()).unwrap();
         assert_eq!(result, "who lives in a pineapple under the sea? Spongebob Squarepants! absorbent and\nyellow and porous is he? Spongebob Squarepants")
     }
+
+    #[test]
+    fn wrap_does_not_break_string_literals() {
+        let result = Transformation::Wrap
+            .apply(0, r#"let first = "who lives in a pineapple under the sea?"; let second = "absorbent and yellow and porous is he?";"#.to_string())
+            .unwrap();
+        assert_eq!(
+            result,
+            r#"let first = "who lives in a pineapple under the sea?"; let second
+ = "absorbent and yellow and porous is he?";"#
+        )
+    }
 }